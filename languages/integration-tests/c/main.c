@@ -43,8 +43,40 @@ int main(int argc, char const *argv[])
 
     phenix_generated_vector_bool_init(&person.working_hours, working_hours, sizeof(working_hours));
 
+    const char *skills_keys[] = {"rust", "c"};
+    uint64_t skills_values[] = {5, 3};
+
+    phenix_generated_map_string_uint_init(&person.skills, skills_keys, skills_values, 2);
+
+    person.has_nickname = true;
+    person.nickname = "Fel";
+    person.has_email = false;
+
+    person.location[0] = 50.08;
+    person.location[1] = 14.42;
+
+    person.team._0 = "Phenix";
+    person.team._1 = 3;
+
+    person.employment.tag_ = phenix_generated_employment_employed;
+    person.employment.employed.position.seniority = phenix_generated_nested_seniority_senior;
+    person.employment.employed.position.level = phenix_generated_nested_level_middle;
+
+    const char *aliases[] = {"Fel", "Felix F."};
+
+    phenix_generated_framed_string_init(&person.aliases, aliases, 2);
+
     phenix_generated_person_encode(&person, output);
 
+    // The position is not available when writing to a pipe.
+    long written = ftell(output);
+
+    if (written >= 0 && (size_t)written != phenix_generated_person_encoded_len(&person))
+    {
+        fprintf(stderr, "encoded length mismatch: %ld written, %zu computed\n", written, phenix_generated_person_encoded_len(&person));
+        return 1;
+    }
+
     phenix_generated_project_t project;
     project.name = "Rust";
     project.url = "https://github.com/rust-lang/rust";
@@ -7,14 +7,15 @@ use std::{
     process::{Command, Stdio},
 };
 
-use phenix_runtime::{bytes::Bytes, prelude::*, Stream};
+use phenix_runtime::{bytes::Bytes, prelude::*, FramedSpan, FramedStream, Stream};
 use tempfile::NamedTempFile;
 
 mod schema {
     include!(concat!(env!("OUT_DIR"), "/index.rs"));
 }
 
-fn expected() -> (schema::Person, Vec<schema::Project>) {
+// The person is as decoded, the streams refer to their items in the input.
+fn expected() -> (schema::Person, Vec<String>, Vec<schema::Project>) {
     let person = schema::Person {
         name: "Felix".to_string(),
         age: 42.into(),
@@ -33,9 +34,25 @@ fn expected() -> (schema::Person, Vec<schema::Project>) {
             false, false, false, false, false, false, false, false, false, true, true, true, true,
             true, true, true, true, false, false, false, false, false, false, false,
         ],
-        projects: Stream::with_offset(19),
+        skills: [("rust".to_string(), 5.into()), ("c".to_string(), 3.into())]
+            .into_iter()
+            .collect(),
+        nickname: Some("Fel".to_string()),
+        email: None,
+        location: [50.08, 14.42],
+        team: ("Phenix".to_string(), 3.into()),
+        employment: schema::Employment::Employed {
+            position: schema::Position {
+                seniority: schema::nested::Seniority::Senior,
+                level: schema::nested::Level::Middle,
+            },
+        },
+        aliases: FramedStream::Decoded(FramedSpan::new(67, 16)),
+        projects: Stream::with_offset(83),
     };
 
+    let aliases = vec!["Fel".to_string(), "Felix F.".to_string()];
+
     let projects = vec![
         schema::Project {
             name: "Rust".to_string(),
@@ -51,28 +68,66 @@ fn expected() -> (schema::Person, Vec<schema::Project>) {
         },
     ];
 
-    (person, projects)
+    (person, aliases, projects)
 }
 
-#[test]
-fn rust_to_rust() {
-    let (person, projects) = expected();
+fn encode(person: &schema::Person, aliases: &[String], projects: &[schema::Project]) -> Vec<u8> {
+    let person = schema::Person {
+        aliases: aliases.to_vec().into(),
+        ..person.clone()
+    };
 
     let mut cursor = Cursor::new(Vec::new());
 
     person.encode(&mut cursor).unwrap();
+    assert_eq!(person.encoded_len() as u64, cursor.position());
 
     for project in projects.iter() {
         Stream::push_encode(project, &mut cursor).unwrap();
     }
 
-    let bytes = cursor.into_inner();
+    cursor.into_inner()
+}
+
+#[test]
+fn rust_to_rust() {
+    let (person, aliases, projects) = expected();
+    let bytes = encode(&person, &aliases, &projects);
 
     let decoded = schema::Person::decode(&mut Bytes::new(&bytes)).unwrap();
-    let collected = decoded.projects.collect(&bytes).unwrap();
 
     assert_eq!(decoded, person);
-    assert_eq!(collected, projects);
+    assert_eq!(collect_aliases(&decoded, &bytes), aliases);
+    assert_eq!(decoded.projects.collect(&bytes).unwrap(), projects);
+}
+
+fn collect_aliases(person: &schema::Person, bytes: &[u8]) -> Vec<String> {
+    person.aliases.span().unwrap().collect(bytes).unwrap()
+}
+
+#[test]
+fn rust_view() {
+    let (person, aliases, projects) = expected();
+    let bytes = encode(&person, &aliases, &projects);
+    let view = schema::PersonView::new(&bytes).unwrap();
+
+    assert_eq!(view.name().unwrap(), person.name);
+    assert_eq!(view.skills().unwrap(), person.skills);
+    assert_eq!(view.nickname().unwrap(), person.nickname);
+    assert_eq!(view.email().unwrap(), None);
+    assert_eq!(view.working_hours().unwrap(), person.working_hours);
+    assert_eq!(view.aliases().unwrap(), person.aliases);
+
+    let pronouns = view.pronouns().unwrap();
+    assert_eq!(pronouns.subject().unwrap(), person.pronouns.subject);
+    assert_eq!(pronouns.object().unwrap(), person.pronouns.object);
+
+    let names = view
+        .projects()
+        .map(|project| project?.name())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(names, ["Rust", "Linux", "Phenix"]);
 }
 
 #[test]
@@ -94,12 +149,12 @@ fn c_to_rust() {
     let bytes = fs::read(tmp.path()).unwrap();
 
     let decoded = schema::Person::decode(&mut Bytes::new(&bytes)).unwrap();
-    let collected = decoded.projects.collect(&bytes).unwrap();
 
-    let (person, projects) = expected();
+    let (person, aliases, projects) = expected();
 
     assert_eq!(decoded, person);
-    assert_eq!(collected, projects);
+    assert_eq!(collect_aliases(&decoded, &bytes), aliases);
+    assert_eq!(decoded.projects.collect(&bytes).unwrap(), projects);
 }
 
 fn run<S: AsRef<std::ffi::OsStr>, F>(command: S, builder: F)
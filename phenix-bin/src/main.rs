@@ -2,21 +2,76 @@ use std::{
     fs,
     io::{self, Write},
     path::PathBuf,
+    process,
 };
 
-use clap::{clap_derive::ArgEnum, Parser};
+use clap::{clap_derive::ArgEnum, Parser, Subcommand};
+use phenix_compiler::compat::Compatibility;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    #[clap(short, long, arg_enum, value_parser)]
-    language: Language,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    // Required unless a subcommand is used.
+    #[clap(short, long, arg_enum, value_parser, required = true)]
+    language: Option<Language>,
 
     #[clap(short, long, value_parser)]
     output: Option<PathBuf>,
 
-    #[clap(value_parser)]
-    input: PathBuf,
+    #[clap(long, arg_enum, value_parser, default_value = "btree-map")]
+    map_type: MapType,
+
+    #[clap(value_parser, required = true)]
+    input: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    // Fails if a change is at least as incompatible as the denied level.
+    #[clap(about = "Checks compatibility of two versions of a schema")]
+    Compat {
+        #[clap(short, long, value_parser)]
+        root: String,
+
+        #[clap(long, arg_enum, value_parser, default_value = "breaking")]
+        deny: Deny,
+
+        #[clap(value_parser)]
+        old: PathBuf,
+
+        #[clap(value_parser)]
+        new: PathBuf,
+    },
+    #[clap(about = "Prints annotated bytes of encoded data")]
+    Dump {
+        #[clap(short, long, value_parser)]
+        root: String,
+
+        #[clap(value_parser)]
+        schema: PathBuf,
+
+        #[clap(value_parser)]
+        data: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Deny {
+    ReaderOnly,
+    Breaking,
+}
+
+impl From<Deny> for Compatibility {
+    fn from(deny: Deny) -> Self {
+        match deny {
+            Deny::ReaderOnly => Compatibility::ReaderOnly,
+            Deny::Breaking => Compatibility::Breaking,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
@@ -34,15 +89,95 @@ impl From<Language> for phenix_codegen::Language {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum MapType {
+    BtreeMap,
+    HashMap,
+}
+
+impl From<MapType> for phenix_codegen::MapType {
+    fn from(map_type: MapType) -> Self {
+        match map_type {
+            MapType::BtreeMap => phenix_codegen::MapType::BTreeMap,
+            MapType::HashMap => phenix_codegen::MapType::HashMap,
+        }
+    }
+}
+
 fn main() -> io::Result<()> {
     let args = Args::parse();
 
-    let input = phenix_compiler::vfs::VfsPath::new(args.input)?;
-    let project = phenix_compiler::Compiler::new().compile(input);
-    let generated = phenix_codegen::generate(project, args.language.into());
+    match args.command {
+        Some(Command::Compat {
+            root,
+            deny,
+            old,
+            new,
+        }) => return compat(&root, deny, old, new),
+        Some(Command::Dump { root, schema, data }) => return dump(&root, schema, data),
+        None => {}
+    }
+
+    let (language, input) = match (args.language, args.input) {
+        (Some(language), Some(input)) => (language, input),
+        _ => unreachable!("required arguments are checked by clap"),
+    };
+
+    let input = phenix_compiler::vfs::VfsPath::new(input)?;
+    let project = phenix_compiler::Compiler::new()
+        .compile(input)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let mut config = phenix_codegen::Config::default();
+    config.map_type(args.map_type.into());
+
+    let generated = phenix_codegen::generate_with_config(project, language.into(), &config);
 
     match args.output {
         Some(output) => fs::write(output, generated),
         None => io::stdout().lock().write_all(generated.as_bytes()),
     }
 }
+
+fn compat(root: &str, deny: Deny, old: PathBuf, new: PathBuf) -> io::Result<()> {
+    let old = phenix_compiler::Compiler::new()
+        .compile(phenix_compiler::vfs::VfsPath::new(old)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let new = phenix_compiler::Compiler::new()
+        .compile(phenix_compiler::vfs::VfsPath::new(new)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    let report = phenix_compiler::compat::check(&old, &new, root);
+    writeln!(io::stdout().lock(), "{}", report)?;
+
+    if report.compatibility() >= deny.into() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn dump(root: &str, schema: PathBuf, data: PathBuf) -> io::Result<()> {
+    let project = phenix_compiler::Compiler::new()
+        .compile(phenix_compiler::vfs::VfsPath::new(schema)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let schema = phenix_reflect::Schema::new(project)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    let root = match schema.root(root) {
+        Some(root) => root,
+        None => {
+            eprintln!("type {} not found", root);
+            process::exit(2);
+        }
+    };
+
+    let data = fs::read(data)?;
+    let annotations = schema.annotate(&root, &data);
+    write!(io::stdout().lock(), "{}", annotations)?;
+
+    if annotations.failure.is_some() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
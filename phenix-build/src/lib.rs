@@ -4,11 +4,13 @@ use std::{
 };
 
 use phenix_codegen::Language;
+pub use phenix_codegen::MapType;
 
 #[derive(Debug, Default, Clone)]
 pub struct Config {
     out_dir: Option<PathBuf>,
     out_file: Option<String>,
+    codegen: phenix_codegen::Config,
 }
 
 impl Config {
@@ -22,6 +24,11 @@ impl Config {
         self
     }
 
+    pub fn map_type(&mut self, map_type: MapType) -> &mut Self {
+        self.codegen.map_type(map_type);
+        self
+    }
+
     pub fn compile<P: AsRef<Path>>(&self, root_file: P) -> io::Result<()> {
         compile_with_config(root_file, self.clone())
     }
@@ -36,12 +43,7 @@ fn compile_with_config<P: AsRef<Path>>(root_file: P, config: Config) -> io::Resu
 
     let out_dir = config.out_dir.map(Ok).unwrap_or_else(|| {
         std::env::var_os("OUT_DIR")
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::Other,
-                    "OUT_DIR environment variable is not set",
-                )
-            })
+            .ok_or_else(|| io::Error::other("OUT_DIR environment variable is not set"))
             .map(Into::into)
     })?;
 
@@ -54,8 +56,10 @@ fn compile_with_config<P: AsRef<Path>>(root_file: P, config: Config) -> io::Resu
     let mut out_file = PathBuf::from(out_file);
     out_file.set_extension("rs");
 
-    let project = phenix_compiler::Compiler::new().compile(root_file.try_into()?);
-    let generated = phenix_codegen::generate(project, Language::Rust);
+    let project = phenix_compiler::Compiler::new()
+        .compile(root_file.try_into()?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let generated = phenix_codegen::generate_with_config(project, Language::Rust, &config.codegen);
 
     let out_file = out_dir.join(out_file);
     fs::write(out_file, generated)
@@ -6,8 +6,8 @@ fn main() {
     generate_primitives("src/generated.rs").unwrap();
 
     std::process::Command::new("rustfmt")
-        .args(&["--edition", "2021", "src/generated.rs"])
-        .spawn()
+        .args(["--edition", "2021", "src/generated.rs"])
+        .status()
         .unwrap();
 
     cbindgen::generate(crate_dir)
@@ -80,6 +80,38 @@ fn generate_primitives<P: AsRef<Path>>(path: P) -> io::Result<()> {
             writeln!(f, "}}")?;
             writeln!(f)?;
 
+            // encoded len
+            writeln!(f, "#[no_mangle]")?;
+            write!(f, "pub extern \"C\" fn ")?;
+            write!(f, "phenix_runtime_{}_encoded_len", self.name)?;
+            write!(f, "(value: {}) -> usize", self.ty)?;
+            writeln!(f, " {{")?;
+            match self.transmute {
+                Some(transmute) => {
+                    writeln!(f, "crate::call_encoded_len(&<{}>::from(value))", transmute)?
+                }
+                None => writeln!(f, "crate::call_encoded_len(&value)",)?,
+            }
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+
+            // encoded len many
+            writeln!(f, "#[no_mangle]")?;
+            write!(f, "pub extern \"C\" fn ")?;
+            write!(f, "phenix_runtime_{}_encoded_len_many", self.name)?;
+            write!(f, "(values: *const {}, n: usize) -> usize", self.ty)?;
+            writeln!(f, " {{")?;
+            match self.transmute {
+                Some(transmute) => writeln!(
+                    f,
+                    "crate::call_encoded_len_many(values.cast::<{}>(), n)",
+                    transmute
+                )?,
+                None => writeln!(f, "crate::call_encoded_len_many(values, n)",)?,
+            }
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+
             Ok(())
         }
     }
@@ -10,64 +10,132 @@ int phenix_runtime_uint_encode(uint64_t value, FILE *stream);
 
 int phenix_runtime_uint_encode_many(const uint64_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_uint_encoded_len(uint64_t value);
+
+size_t phenix_runtime_uint_encoded_len_many(const uint64_t *values, size_t n);
+
 int phenix_runtime_sint_encode(int64_t value, FILE *stream);
 
 int phenix_runtime_sint_encode_many(const int64_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_sint_encoded_len(int64_t value);
+
+size_t phenix_runtime_sint_encoded_len_many(const int64_t *values, size_t n);
+
 int phenix_runtime_float_encode(double value, FILE *stream);
 
 int phenix_runtime_float_encode_many(const double *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_float_encoded_len(double value);
+
+size_t phenix_runtime_float_encoded_len_many(const double *values, size_t n);
+
 int phenix_runtime_bool_encode(bool value, FILE *stream);
 
 int phenix_runtime_bool_encode_many(const bool *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_bool_encoded_len(bool value);
+
+size_t phenix_runtime_bool_encoded_len_many(const bool *values, size_t n);
+
 int phenix_runtime_u8_encode(uint8_t value, FILE *stream);
 
 int phenix_runtime_u8_encode_many(const uint8_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_u8_encoded_len(uint8_t value);
+
+size_t phenix_runtime_u8_encoded_len_many(const uint8_t *values, size_t n);
+
 int phenix_runtime_u16_encode(uint16_t value, FILE *stream);
 
 int phenix_runtime_u16_encode_many(const uint16_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_u16_encoded_len(uint16_t value);
+
+size_t phenix_runtime_u16_encoded_len_many(const uint16_t *values, size_t n);
+
 int phenix_runtime_u32_encode(uint32_t value, FILE *stream);
 
 int phenix_runtime_u32_encode_many(const uint32_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_u32_encoded_len(uint32_t value);
+
+size_t phenix_runtime_u32_encoded_len_many(const uint32_t *values, size_t n);
+
 int phenix_runtime_u64_encode(uint64_t value, FILE *stream);
 
 int phenix_runtime_u64_encode_many(const uint64_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_u64_encoded_len(uint64_t value);
+
+size_t phenix_runtime_u64_encoded_len_many(const uint64_t *values, size_t n);
+
 int phenix_runtime_i8_encode(int8_t value, FILE *stream);
 
 int phenix_runtime_i8_encode_many(const int8_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_i8_encoded_len(int8_t value);
+
+size_t phenix_runtime_i8_encoded_len_many(const int8_t *values, size_t n);
+
 int phenix_runtime_i16_encode(int16_t value, FILE *stream);
 
 int phenix_runtime_i16_encode_many(const int16_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_i16_encoded_len(int16_t value);
+
+size_t phenix_runtime_i16_encoded_len_many(const int16_t *values, size_t n);
+
 int phenix_runtime_i32_encode(int32_t value, FILE *stream);
 
 int phenix_runtime_i32_encode_many(const int32_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_i32_encoded_len(int32_t value);
+
+size_t phenix_runtime_i32_encoded_len_many(const int32_t *values, size_t n);
+
 int phenix_runtime_i64_encode(int64_t value, FILE *stream);
 
 int phenix_runtime_i64_encode_many(const int64_t *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_i64_encoded_len(int64_t value);
+
+size_t phenix_runtime_i64_encoded_len_many(const int64_t *values, size_t n);
+
 int phenix_runtime_f32_encode(float value, FILE *stream);
 
 int phenix_runtime_f32_encode_many(const float *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_f32_encoded_len(float value);
+
+size_t phenix_runtime_f32_encoded_len_many(const float *values, size_t n);
+
 int phenix_runtime_f64_encode(double value, FILE *stream);
 
 int phenix_runtime_f64_encode_many(const double *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_f64_encoded_len(double value);
+
+size_t phenix_runtime_f64_encoded_len_many(const double *values, size_t n);
+
 int phenix_runtime_string_encode(const char *value, FILE *stream);
 
 int phenix_runtime_string_encode_many(const char *const *values, size_t n, FILE *stream);
 
+size_t phenix_runtime_string_encoded_len(const char *value);
+
+size_t phenix_runtime_string_encoded_len_many(const char *const *values, size_t n);
+
 int phenix_runtime_encode_discriminant(size_t n, FILE *stream);
 
 int phenix_runtime_encode_discriminant_relaxed(size_t n, FILE *stream);
 
+size_t phenix_runtime_encoded_len_discriminant(size_t n);
+
+size_t phenix_runtime_encoded_len_discriminant_relaxed(size_t n);
+
+int phenix_runtime_encode_body(FILE *body, FILE *stream);
+
+size_t phenix_runtime_encoded_len_body(size_t len);
+
 #endif /* PHENIX_RUNTIME_H */
@@ -12,6 +12,16 @@ pub extern "C" fn phenix_runtime_uint_encode_many(
     crate::call_encode_many(values.cast::<phenix_runtime::Uint>(), n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_uint_encoded_len(value: u64) -> usize {
+    crate::call_encoded_len(&<phenix_runtime::Uint>::from(value))
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_uint_encoded_len_many(values: *const u64, n: usize) -> usize {
+    crate::call_encoded_len_many(values.cast::<phenix_runtime::Uint>(), n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_sint_encode(value: i64, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&<phenix_runtime::Sint>::from(value), stream)
@@ -26,6 +36,16 @@ pub extern "C" fn phenix_runtime_sint_encode_many(
     crate::call_encode_many(values.cast::<phenix_runtime::Sint>(), n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_sint_encoded_len(value: i64) -> usize {
+    crate::call_encoded_len(&<phenix_runtime::Sint>::from(value))
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_sint_encoded_len_many(values: *const i64, n: usize) -> usize {
+    crate::call_encoded_len_many(values.cast::<phenix_runtime::Sint>(), n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_float_encode(value: f64, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&<phenix_runtime::Float>::from(value), stream)
@@ -40,6 +60,16 @@ pub extern "C" fn phenix_runtime_float_encode_many(
     crate::call_encode_many(values.cast::<phenix_runtime::Float>(), n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_float_encoded_len(value: f64) -> usize {
+    crate::call_encoded_len(&<phenix_runtime::Float>::from(value))
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_float_encoded_len_many(values: *const f64, n: usize) -> usize {
+    crate::call_encoded_len_many(values.cast::<phenix_runtime::Float>(), n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_bool_encode(value: bool, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -54,6 +84,16 @@ pub extern "C" fn phenix_runtime_bool_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_bool_encoded_len(value: bool) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_bool_encoded_len_many(values: *const bool, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_u8_encode(value: u8, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -68,6 +108,16 @@ pub extern "C" fn phenix_runtime_u8_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u8_encoded_len(value: u8) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u8_encoded_len_many(values: *const u8, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_u16_encode(value: u16, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -82,6 +132,16 @@ pub extern "C" fn phenix_runtime_u16_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u16_encoded_len(value: u16) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u16_encoded_len_many(values: *const u16, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_u32_encode(value: u32, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -96,6 +156,16 @@ pub extern "C" fn phenix_runtime_u32_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u32_encoded_len(value: u32) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u32_encoded_len_many(values: *const u32, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_u64_encode(value: u64, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -110,6 +180,16 @@ pub extern "C" fn phenix_runtime_u64_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u64_encoded_len(value: u64) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_u64_encoded_len_many(values: *const u64, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_i8_encode(value: i8, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -124,6 +204,16 @@ pub extern "C" fn phenix_runtime_i8_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i8_encoded_len(value: i8) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i8_encoded_len_many(values: *const i8, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_i16_encode(value: i16, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -138,6 +228,16 @@ pub extern "C" fn phenix_runtime_i16_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i16_encoded_len(value: i16) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i16_encoded_len_many(values: *const i16, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_i32_encode(value: i32, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -152,6 +252,16 @@ pub extern "C" fn phenix_runtime_i32_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i32_encoded_len(value: i32) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i32_encoded_len_many(values: *const i32, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_i64_encode(value: i64, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -166,6 +276,16 @@ pub extern "C" fn phenix_runtime_i64_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i64_encoded_len(value: i64) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_i64_encoded_len_many(values: *const i64, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_f32_encode(value: f32, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -180,6 +300,16 @@ pub extern "C" fn phenix_runtime_f32_encode_many(
     crate::call_encode_many(values, n, stream)
 }
 
+#[no_mangle]
+pub extern "C" fn phenix_runtime_f32_encoded_len(value: f32) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_f32_encoded_len_many(values: *const f32, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
+
 #[no_mangle]
 pub extern "C" fn phenix_runtime_f64_encode(value: f64, stream: *mut libc::FILE) -> libc::c_int {
     crate::call_encode(&value, stream)
@@ -193,3 +323,13 @@ pub extern "C" fn phenix_runtime_f64_encode_many(
 ) -> libc::c_int {
     crate::call_encode_many(values, n, stream)
 }
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_f64_encoded_len(value: f64) -> usize {
+    crate::call_encoded_len(&value)
+}
+
+#[no_mangle]
+pub extern "C" fn phenix_runtime_f64_encoded_len_many(values: *const f64, n: usize) -> usize {
+    crate::call_encoded_len_many(values, n)
+}
@@ -31,6 +31,7 @@ mod manual {
         n: usize,
         stream: *mut libc::FILE,
     ) -> libc::c_int {
+        #![allow(clippy::not_unsafe_ptr_arg_deref)]
         crate::wrap(|| {
             for value in unsafe { slice::from_raw_parts(values, n) }.iter().copied() {
                 let value = unsafe { ffi::CStr::from_ptr(value) };
@@ -42,6 +43,26 @@ mod manual {
         })
     }
 
+    #[no_mangle]
+    pub extern "C" fn phenix_runtime_string_encoded_len(value: *const libc::c_char) -> usize {
+        #![allow(clippy::not_unsafe_ptr_arg_deref)]
+        let value = unsafe { ffi::CStr::from_ptr(value) };
+        (&*value.to_string_lossy()).encoded_len()
+    }
+
+    #[no_mangle]
+    pub extern "C" fn phenix_runtime_string_encoded_len_many(
+        values: *const *const libc::c_char,
+        n: usize,
+    ) -> usize {
+        #![allow(clippy::not_unsafe_ptr_arg_deref)]
+        unsafe { slice::from_raw_parts(values, n) }
+            .iter()
+            .copied()
+            .map(|value| phenix_runtime_string_encoded_len(value))
+            .sum()
+    }
+
     #[no_mangle]
     pub extern "C" fn phenix_runtime_encode_discriminant(
         n: usize,
@@ -64,6 +85,34 @@ mod manual {
             )
         })
     }
+
+    #[no_mangle]
+    pub extern "C" fn phenix_runtime_encoded_len_discriminant(n: usize) -> usize {
+        phenix_runtime::base::utils::encoded_len_discriminant(n)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn phenix_runtime_encoded_len_discriminant_relaxed(n: usize) -> usize {
+        phenix_runtime::base::utils::encoded_len_discriminant_relaxed(n)
+    }
+
+    // Writes the body of a non-exhaustive type, which was encoded into a
+    // temporary file, prefixed with its length.
+    #[no_mangle]
+    pub extern "C" fn phenix_runtime_encode_body(
+        body: *mut libc::FILE,
+        stream: *mut libc::FILE,
+    ) -> libc::c_int {
+        crate::wrap(|| {
+            let body = crate::read_all(body)?;
+            phenix_runtime::base::utils::encode_body(&body, &mut RawWriter::wrap(stream))
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn phenix_runtime_encoded_len_body(len: usize) -> usize {
+        phenix_runtime::base::utils::encoded_len_body(len)
+    }
 }
 
 fn call_encode<T: Encodable + panic::RefUnwindSafe>(
@@ -86,6 +135,14 @@ fn call_encode_many<T: Encodable + panic::RefUnwindSafe>(
     })
 }
 
+fn call_encoded_len<T: Encodable>(value: &T) -> usize {
+    value.encoded_len()
+}
+
+fn call_encoded_len_many<T: Encodable>(values: *const T, n: usize) -> usize {
+    T::encoded_len_many(unsafe { slice::from_raw_parts(values, n) })
+}
+
 fn wrap<F>(f: F) -> libc::c_int
 where
     F: FnOnce() -> io::Result<()> + panic::UnwindSafe,
@@ -97,6 +154,35 @@ where
     }
 }
 
+fn read_all(stream: *mut libc::FILE) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    let mut buf = [0u8; 4096];
+
+    unsafe {
+        libc::rewind(stream);
+
+        loop {
+            let n = libc::fread(
+                buf.as_mut_ptr().cast(),
+                mem::size_of::<u8>(),
+                buf.len(),
+                stream,
+            );
+            content.extend_from_slice(&buf[..n]);
+
+            if n < buf.len() {
+                break;
+            }
+        }
+
+        if libc::ferror(stream) != 0 {
+            return Err(io::Error::from_raw_os_error(libc::ferror(stream)));
+        }
+    }
+
+    Ok(content)
+}
+
 struct RawWriter {
     stream: *mut libc::FILE,
 }
@@ -9,8 +9,7 @@ use tera::{Context as TeraContext, Tera};
 
 use crate::{
     shared::{self, ProjectContext, TypeDependency, TypeDependencyOrder},
-    Attribute, BuiltinType, EnumType, Field, FlagsType, Module, StructType, Type, TypeId, UserType,
-    Variant,
+    BuiltinType, EnumType, Field, FlagsType, Module, StructType, Type, TypeId, UserType, Variant,
 };
 
 pub fn generate(ctx: ProjectContext) -> String {
@@ -29,6 +28,22 @@ pub fn generate(ctx: ProjectContext) -> String {
                     TypeId::Builtin(BuiltinType::Stream) => Some(MonomorphizationContext::Stream(
                         StreamContext::new(&ty.generics[0], ctx),
                     )),
+                    TypeId::Builtin(BuiltinType::Framed) => Some(MonomorphizationContext::Framed(
+                        VectorContext::framed(&ty.generics[0], ctx),
+                    )),
+                    TypeId::Builtin(BuiltinType::Map) => Some(MonomorphizationContext::Map(
+                        MapContext::new(&ty.generics[0], &ty.generics[1], ctx),
+                    )),
+                    TypeId::Builtin(BuiltinType::Array(len)) => {
+                        Some(MonomorphizationContext::Array(ArrayContext::new(
+                            &ty.generics[0],
+                            len,
+                            ctx,
+                        )))
+                    }
+                    TypeId::Builtin(BuiltinType::Tuple) => {
+                        Some(MonomorphizationContext::Tuple(TupleContext::new(ty, ctx)))
+                    }
                     _ => None,
                 })
             })
@@ -131,6 +146,12 @@ impl Templates {
             .expect("valid template");
         tera.add_raw_template("stream_impl", include_str!("templates/stream_impl.tera"))
             .expect("valid template");
+        tera.add_raw_template("framed_impl", include_str!("templates/framed_impl.tera"))
+            .expect("valid template");
+        tera.add_raw_template("map_impl", include_str!("templates/map_impl.tera"))
+            .expect("valid template");
+        tera.add_raw_template("array_impl", include_str!("templates/array_impl.tera"))
+            .expect("valid template");
 
         tera.add_raw_template(
             "encode_many_impl",
@@ -155,6 +176,13 @@ impl Templates {
     }
 
     fn render_monomorphization_decl_to(&self, mono: &MonomorphizationContext, output: &mut String) {
+        // Arrays are not a separate C type and the encoding function refers to
+        // the item type, which may not be declared yet. The function is
+        // defined before its first use anyway.
+        if let MonomorphizationContext::Array(_) = mono {
+            return;
+        }
+
         let context = mono.tera_context();
 
         let rendered = self.tera.render("struct_decl", &context).unwrap();
@@ -167,6 +195,10 @@ impl Templates {
         let template = match mono {
             MonomorphizationContext::Vector(_) => "vector_impl",
             MonomorphizationContext::Stream(_) => "stream_impl",
+            MonomorphizationContext::Framed(_) => "framed_impl",
+            MonomorphizationContext::Map(_) => "map_impl",
+            MonomorphizationContext::Array(_) => "array_impl",
+            MonomorphizationContext::Tuple(_) => "struct_impl",
         };
 
         let rendered = self.tera.render(template, &context).unwrap();
@@ -235,6 +267,9 @@ impl ItemContext {
 struct StructContext {
     name: String,
     fields: Vec<FieldContext>,
+    n_optional: usize,
+    n_optional_bytes: usize,
+    is_exhaustive: bool,
 }
 
 impl StructContext {
@@ -247,9 +282,17 @@ impl StructContext {
             .iter()
             .filter(|field| !field.ty.is_stream())
             .map(|field| FieldContext::new(field, ctx))
-            .collect();
+            .collect::<Vec<_>>();
 
-        Self { name, fields }
+        let n_optional = fields.iter().filter(|field| field.optional).count();
+
+        Self {
+            name,
+            fields,
+            n_optional,
+            n_optional_bytes: shared::byte_size(n_optional),
+            is_exhaustive: ty.is_exhaustive(),
+        }
     }
 }
 
@@ -258,6 +301,8 @@ struct EnumContext {
     name: String,
     variants: Vec<VariantContext>,
     has_data: bool,
+    is_exhaustive: bool,
+    relaxed_discriminant: bool,
 }
 
 impl EnumContext {
@@ -268,15 +313,22 @@ impl EnumContext {
         let variants = ty
             .variants
             .iter()
-            .map(|variant| VariantContext::new(variant, ctx))
+            .zip(ty.discriminants())
+            .map(|(variant, discriminant)| VariantContext::new(variant, discriminant, ctx))
             .collect();
 
         let has_data = ty.variants.iter().any(|variant| !variant.fields.is_empty());
 
+        let is_exhaustive = ty.is_exhaustive();
+
+        let relaxed_discriminant = ty.is_relaxed_discriminant();
+
         Self {
             name,
             variants,
             has_data,
+            is_exhaustive,
+            relaxed_discriminant,
         }
     }
 }
@@ -300,26 +352,22 @@ impl FlagsContext {
             .map(|flag| flag.to_case(Case::ScreamingSnake))
             .collect();
 
-        let is_exhaustive = !ty
-            .attrs
-            .iter()
-            .any(|attr| attr == &Attribute::NonExhaustive);
-
         Self {
             name,
             flags,
             // C arrays must have non-zero size. This would happen if the type
             // had zero flags.
-            n_bytes: shared::byte_size(ty.flags.len()).max(1),
-            is_exhaustive,
+            n_bytes: ty.byte_size().max(1),
+            is_exhaustive: ty.is_exhaustive(),
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, PartialEq, Eq, Hash)]
 struct FieldContext {
     name: String,
     ty: TypeContext,
+    optional: bool,
 }
 
 impl FieldContext {
@@ -327,6 +375,7 @@ impl FieldContext {
         Self {
             name: field.name.to_case(Case::Snake),
             ty: TypeContext::new(&field.ty, ctx),
+            optional: field.optional,
         }
     }
 }
@@ -336,10 +385,11 @@ struct VariantContext {
     name: String,
     fields: Vec<FieldContext>,
     has_data: bool,
+    discriminant: usize,
 }
 
 impl VariantContext {
-    fn new(variant: &Variant, ctx: &ProjectContext) -> Self {
+    fn new(variant: &Variant, discriminant: usize, ctx: &ProjectContext) -> Self {
         let fields = variant
             .fields
             .iter()
@@ -351,13 +401,16 @@ impl VariantContext {
             name: variant.name.to_case(Case::Snake),
             fields,
             has_data: !variant.fields.is_empty(),
+            discriminant,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, PartialEq, Eq, Hash)]
 struct TypeContext {
     c_name: String,
+    // Array dimensions following the name in declarations.
+    c_suffix: String,
     rt_name: String,
     rt_prefix: String,
     by_ref: bool,
@@ -365,22 +418,40 @@ struct TypeContext {
 
 impl TypeContext {
     fn new(ty: &Type, ctx: &ProjectContext) -> Self {
-        let (mut c_name, mut rt_name, keyword) = match ty.id {
+        let (mut rt_name, keyword) = match ty.id {
+            TypeId::Builtin(BuiltinType::Array(len)) => {
+                let item = TypeContext::new(&ty.generics[0], ctx);
+                let item_stripped = item.rt_name.strip_prefix(&*PREFIX).unwrap_or(&item.rt_name);
+
+                return Self {
+                    rt_name: format!("{}array_{}_{}", *PREFIX, item_stripped, len),
+                    rt_prefix: String::new(),
+                    c_name: item.c_name,
+                    c_suffix: format!("[{}]{}", len, item.c_suffix),
+                    by_ref: false,
+                };
+            }
             TypeId::Builtin(builtin)
-                if matches!(builtin, BuiltinType::Vector | BuiltinType::Stream) =>
+                if matches!(
+                    builtin,
+                    BuiltinType::Vector
+                        | BuiltinType::Stream
+                        | BuiltinType::Framed
+                        | BuiltinType::Map
+                        | BuiltinType::Tuple
+                ) =>
             {
-                let (mut c_name, mut rt_name) = builtin.ty_context();
-
-                c_name.insert_str(0, &PREFIX);
+                let (_, mut rt_name) = builtin.ty_context();
                 rt_name.insert_str(0, &PREFIX);
 
-                (c_name, rt_name, "struct")
+                (rt_name, "struct")
             }
             TypeId::Builtin(builtin) => {
                 let (c_name, rt_name) = builtin.ty_context();
 
                 return Self {
                     c_name,
+                    c_suffix: String::new(),
                     rt_name,
                     rt_prefix: "phenix_runtime_".to_string(),
                     by_ref: false,
@@ -393,24 +464,13 @@ impl TypeContext {
                 let mut name = module_prefix(source_module);
                 name.push_str(&user_ty.name().to_case(Case::Snake));
 
-                (name.clone(), name, user_ty.keyword())
+                (name, user_ty.keyword())
             }
         };
 
-        c_name.insert(0, ' ');
-        c_name.insert_str(0, keyword);
-
         for gen_ty in ty.generics.iter() {
             let gen_ty = TypeContext::new(gen_ty, ctx);
 
-            let c_name_stripped = gen_ty
-                .c_name
-                .strip_prefix(&*PREFIX)
-                .unwrap_or(&gen_ty.c_name);
-
-            c_name.push('_');
-            c_name.push_str(c_name_stripped);
-
             let rt_name_stripped = gen_ty
                 .rt_name
                 .strip_prefix(&*PREFIX)
@@ -420,10 +480,13 @@ impl TypeContext {
             rt_name.push_str(rt_name_stripped);
         }
 
-        c_name.push_str("__");
+        // The C name of generic arguments may not be a valid identifier (e.g.,
+        // `const char *`), so the name is derived from the runtime name.
+        let c_name = format!("{} {}__", keyword, rt_name);
 
         Self {
             c_name,
+            c_suffix: String::new(),
             rt_name,
             rt_prefix: String::new(),
             by_ref: true,
@@ -457,6 +520,10 @@ impl BuiltinType {
             BuiltinType::String => ("const char *", "string"),
             BuiltinType::Vector => ("vector", "vector"),
             BuiltinType::Stream => ("stream", "stream"),
+            BuiltinType::Framed => ("framed", "framed"),
+            BuiltinType::Map => ("map", "map"),
+            BuiltinType::Array(_) => ("array", "array"),
+            BuiltinType::Tuple => ("tuple", "tuple"),
         };
 
         (c_name.to_string(), rt_name.to_string())
@@ -483,17 +550,29 @@ impl UserType {
 enum MonomorphizationContext {
     Vector(VectorContext),
     Stream(StreamContext),
+    // Framed streams are encoded from an array of items like vectors.
+    Framed(VectorContext),
+    Map(MapContext),
+    Array(ArrayContext),
+    Tuple(TupleContext),
 }
 
 impl MonomorphizationContext {
     fn tera_context(&self) -> TeraContext {
         match self {
-            MonomorphizationContext::Vector(context) => {
+            MonomorphizationContext::Vector(context) | MonomorphizationContext::Framed(context) => {
                 TeraContext::from_serialize(context).unwrap()
             }
             MonomorphizationContext::Stream(context) => {
                 TeraContext::from_serialize(context).unwrap()
             }
+            MonomorphizationContext::Map(context) => TeraContext::from_serialize(context).unwrap(),
+            MonomorphizationContext::Array(context) => {
+                TeraContext::from_serialize(context).unwrap()
+            }
+            MonomorphizationContext::Tuple(context) => {
+                TeraContext::from_serialize(context).unwrap()
+            }
         }
     }
 }
@@ -508,11 +587,19 @@ struct VectorContext {
 
 impl VectorContext {
     fn new(ty: &Type, ctx: &ProjectContext) -> Self {
+        Self::with_kind(ty, "vector_", ctx)
+    }
+
+    fn framed(ty: &Type, ctx: &ProjectContext) -> Self {
+        Self::with_kind(ty, "framed_", ctx)
+    }
+
+    fn with_kind(ty: &Type, kind: &str, ctx: &ProjectContext) -> Self {
         let ty = TypeContext::new(ty, ctx);
         let rt_name_stripped = ty.rt_name.strip_prefix(&*PREFIX).unwrap_or(&ty.rt_name);
 
         Self {
-            name: PREFIX.clone() + "vector_" + rt_name_stripped,
+            name: PREFIX.clone() + kind + rt_name_stripped,
             c_name: ty.c_name,
             rt_name: ty.rt_name,
             rt_prefix: ty.rt_prefix,
@@ -542,6 +629,89 @@ impl StreamContext {
     }
 }
 
+#[derive(Debug, Serialize, PartialEq, Eq, Hash)]
+struct MapContext {
+    name: String,
+    key: TypeContext,
+    value: TypeContext,
+}
+
+impl MapContext {
+    fn new(key: &Type, value: &Type, ctx: &ProjectContext) -> Self {
+        let key = TypeContext::new(key, ctx);
+        let value = TypeContext::new(value, ctx);
+
+        let key_stripped = key.rt_name.strip_prefix(&*PREFIX).unwrap_or(&key.rt_name);
+        let value_stripped = value
+            .rt_name
+            .strip_prefix(&*PREFIX)
+            .unwrap_or(&value.rt_name);
+
+        Self {
+            name: PREFIX.clone() + "map_" + key_stripped + "_" + value_stripped,
+            key,
+            value,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq, Hash)]
+struct ArrayContext {
+    name: String,
+    c_name: String,
+    rt_name: String,
+    rt_prefix: String,
+    len: usize,
+}
+
+impl ArrayContext {
+    fn new(ty: &Type, len: usize, ctx: &ProjectContext) -> Self {
+        let ty = TypeContext::new(ty, ctx);
+        let rt_name_stripped = ty.rt_name.strip_prefix(&*PREFIX).unwrap_or(&ty.rt_name);
+
+        Self {
+            name: format!("{}array_{}_{}", *PREFIX, rt_name_stripped, len),
+            c_name: ty.c_name,
+            rt_name: ty.rt_name,
+            rt_prefix: ty.rt_prefix,
+            len,
+        }
+    }
+}
+
+// Tuples are rendered as structs with fields named by the position.
+#[derive(Debug, Serialize, PartialEq, Eq, Hash)]
+struct TupleContext {
+    name: String,
+    fields: Vec<FieldContext>,
+    n_optional: usize,
+    n_optional_bytes: usize,
+    is_exhaustive: bool,
+}
+
+impl TupleContext {
+    fn new(ty: &Type, ctx: &ProjectContext) -> Self {
+        let fields = ty
+            .generics
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| FieldContext {
+                name: format!("_{}", i),
+                ty: TypeContext::new(ty, ctx),
+                optional: false,
+            })
+            .collect();
+
+        Self {
+            name: TypeContext::new(ty, ctx).rt_name,
+            fields,
+            n_optional: 0,
+            n_optional_bytes: 0,
+            is_exhaustive: true,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct EncodeManyContext {
     keyword: &'static str,
@@ -566,8 +736,11 @@ impl EncodeManyContext {
 
     fn from_monomorphization(mono: &MonomorphizationContext) -> Option<Self> {
         let keyword = match mono {
-            MonomorphizationContext::Vector(_) => "struct",
-            MonomorphizationContext::Stream(_) => return None,
+            MonomorphizationContext::Vector(_)
+            | MonomorphizationContext::Framed(_)
+            | MonomorphizationContext::Map(_)
+            | MonomorphizationContext::Tuple(_) => "struct",
+            MonomorphizationContext::Stream(_) | MonomorphizationContext::Array(_) => return None,
         };
 
         Some(Self { keyword })
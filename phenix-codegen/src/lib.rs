@@ -22,11 +22,38 @@ impl Language {
     }
 }
 
+// Rust type used for `map<K, V>`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MapType {
+    // Works also without `std` and the order of encoded entries is
+    // deterministic.
+    #[default]
+    BTreeMap,
+    HashMap,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    map_type: MapType,
+}
+
+impl Config {
+    pub fn map_type(&mut self, map_type: MapType) -> &mut Self {
+        self.map_type = map_type;
+        self
+    }
+}
+
 pub fn generate(project: Project, lang: Language) -> String {
+    generate_with_config(project, lang, &Config::default())
+}
+
+pub fn generate_with_config(project: Project, lang: Language, config: &Config) -> String {
     let ctx = shared::ProjectContext::new(project);
 
     match lang {
-        Language::Rust => rust::generate(ctx),
+        Language::Rust => rust::generate(ctx, config),
         Language::C => c::generate(ctx),
     }
 }
@@ -52,6 +79,13 @@ pub enum BuiltinType {
     String,
     Vector,
     Stream,
+    // Stream of items in frames terminated by an empty one, can be followed by
+    // other data.
+    Framed,
+    Map,
+    // Fixed-size array of given length.
+    Array(usize),
+    Tuple,
 }
 
 #[derive(
@@ -90,12 +124,15 @@ pub struct Type {
 #[non_exhaustive]
 pub enum Attribute {
     NonExhaustive,
+    // Field decoded only on demand.
+    Lazy,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Field {
     pub name: String,
     pub ty: Type,
+    pub optional: bool,
     pub attrs: Vec<Attribute>,
 }
 
@@ -103,6 +140,8 @@ pub struct Field {
 pub struct Variant {
     pub name: String,
     pub fields: Vec<Field>,
+    // Explicit discriminant.
+    pub tag: Option<usize>,
     pub attrs: Vec<Attribute>,
 }
 
@@ -2,21 +2,22 @@ use convert_case::{Case, Casing};
 
 use crate::{
     shared::{self, ModuleTree, ProjectContext},
-    Attribute, BuiltinType, EnumType, Field, FlagsType, Module, ModuleId, StructType, Type, TypeId,
-    UserType, Variant,
+    Attribute, BuiltinType, Config, EnumType, Field, FlagsType, MapType, Module, ModuleId,
+    StructType, Type, TypeId, UserType, UserTypeId, Variant,
 };
 
-pub fn generate(ctx: ProjectContext) -> String {
-    Generator::new(ctx).generate()
+pub fn generate(ctx: ProjectContext, config: &Config) -> String {
+    Generator::new(ctx, config).generate()
 }
 
-struct Generator {
+struct Generator<'a> {
     ctx: ProjectContext,
+    config: &'a Config,
 }
 
-impl Generator {
-    fn new(ctx: ProjectContext) -> Self {
-        Self { ctx }
+impl<'a> Generator<'a> {
+    fn new(ctx: ProjectContext, config: &'a Config) -> Self {
+        Self { ctx, config }
     }
 
     fn generate(self) -> String {
@@ -60,7 +61,10 @@ impl Generator {
 
     fn generate_user_type(&self, content: &mut String, module: ModuleId, ty: &UserType) {
         match ty {
-            UserType::Struct(ty) => self.generate_struct_type(content, module, ty),
+            UserType::Struct(ty) => {
+                self.generate_struct_type(content, module, ty);
+                self.generate_view_type(content, module, ty);
+            }
             UserType::Enum(ty) => self.generate_enum_type(content, module, ty),
             UserType::Flags(ty) => self.generate_flags_type(content, ty),
         }
@@ -70,6 +74,8 @@ impl Generator {
         for attr in attrs.iter() {
             match attr {
                 Attribute::NonExhaustive => content.push_str("#[non_exhaustive]"),
+                // Lazy fields differ only in their type.
+                Attribute::Lazy => {}
             }
         }
     }
@@ -91,11 +97,325 @@ impl Generator {
         content.push('}');
     }
 
+    // Borrowed view of an encoded struct. The field boundaries are recognized
+    // once, the fields are then decoded only when accessed.
+    fn generate_view_type(&self, content: &mut String, module: ModuleId, ty: &StructType) {
+        let name = ty.name.to_case(Case::Pascal);
+
+        content.push_str("#[derive(Debug, Clone)]");
+        content.push_str("#[allow(dead_code)]");
+        content.push_str("pub struct ");
+        content.push_str(&name);
+        content.push_str("View<'a> {");
+
+        for field in ty.fields.iter() {
+            content.push_str(&field.name.to_case(Case::Snake));
+            content.push(':');
+
+            if field.optional {
+                content.push_str("Option<");
+            }
+
+            content.push_str("::phenix_runtime::bytes::ByteSlice<'a,");
+            self.generate_type(content, module, &field.ty);
+            content.push('>');
+
+            if field.optional {
+                content.push('>');
+            }
+
+            content.push(',');
+        }
+
+        // The lifetime must be used even without fields.
+        if ty.fields.is_empty() {
+            content.push_str("bytes: ::core::marker::PhantomData<&'a [u8]>,");
+        }
+
+        content.push('}');
+
+        content.push_str("#[allow(dead_code)]");
+        content.push_str("impl<'a> ");
+        content.push_str(&name);
+        content.push_str("View<'a> {");
+
+        content.push_str(
+            "pub fn new(bytes: &'a [u8]) -> Result<Self, ::phenix_runtime::DecodingError> {
+                Self::recognize(&mut ::phenix_runtime::bytes::Bytes::new(bytes))
+            }",
+        );
+
+        content.push_str(
+            "pub fn recognize(
+                bytes: &mut ::phenix_runtime::bytes::Bytes<'a>,
+            ) -> Result<Self, ::phenix_runtime::DecodingError> {",
+        );
+
+        for field in ty.fields.iter() {
+            content.push_str("let mut ");
+            content.push_str(&field.name.to_case(Case::Snake));
+            content.push_str(" = None;");
+        }
+
+        content.push_str("for part in ");
+        content.push_str(&name);
+        content.push_str("::recognize_by_parts(bytes) { match part? {");
+
+        for field in ty.fields.iter() {
+            let field_name = field.name.to_case(Case::Snake);
+
+            content.push_str(&name);
+            content.push_str("Part::");
+            content.push_str(&field_name.to_case(Case::Pascal));
+            content.push_str("(part) => ");
+            content.push_str(&field_name);
+
+            // Lazy fields are accessed as their values.
+            match (field.attrs.contains(&Attribute::Lazy), field.optional) {
+                (true, true) => {
+                    content.push_str(" = Some(part.map(::phenix_runtime::bytes::ByteSlice::cast)),")
+                }
+                (true, false) => content.push_str(" = Some(part.cast()),"),
+                (false, _) => content.push_str(" = Some(part),"),
+            }
+        }
+
+        content.push_str("}}");
+
+        // The parts are recognized for all fields unless there is an error.
+        content.push_str("Ok(Self {");
+
+        for field in ty.fields.iter() {
+            let field_name = field.name.to_case(Case::Snake);
+            content.push_str(&field_name);
+            content.push(':');
+            content.push_str(&field_name);
+            content.push_str(".unwrap(),");
+        }
+
+        if ty.fields.is_empty() {
+            content.push_str("bytes: ::core::marker::PhantomData,");
+        }
+
+        content.push_str("})}");
+
+        for field in ty.fields.iter() {
+            self.generate_view_accessor(content, module, field);
+        }
+
+        content.push('}');
+    }
+
+    fn generate_view_accessor(&self, content: &mut String, module: ModuleId, field: &Field) {
+        let field_name = field.name.to_case(Case::Snake);
+
+        // Optional fields are accessed within `map`.
+        let part = if field.optional {
+            "part".to_string()
+        } else {
+            format!("self.{}", field_name)
+        };
+
+        let mut ret = String::new();
+        let mut expr = String::new();
+        let is_result = self.generate_view_access(&mut ret, &mut expr, module, &field.ty, &part);
+
+        content.push_str("pub fn ");
+        content.push_str(&field_name);
+        content.push_str("(&self) -> ");
+
+        match (field.optional, is_result) {
+            (true, true) => {
+                content.push_str("Result<Option<");
+                content.push_str(&ret);
+                content.push_str(">, ::phenix_runtime::DecodingError>");
+            }
+            (true, false) => {
+                content.push_str("Option<");
+                content.push_str(&ret);
+                content.push('>');
+            }
+            (false, true) => {
+                content.push_str("Result<");
+                content.push_str(&ret);
+                content.push_str(", ::phenix_runtime::DecodingError>");
+            }
+            (false, false) => content.push_str(&ret),
+        }
+
+        content.push('{');
+
+        if field.optional {
+            content.push_str("self.");
+            content.push_str(&field_name);
+            content.push_str(".as_ref().map(|part| ");
+            content.push_str(&expr);
+            content.push(')');
+
+            if is_result {
+                content.push_str(".transpose()");
+            }
+        } else {
+            content.push_str(&expr);
+        }
+
+        content.push('}');
+    }
+
+    // Generates the type returned for a field and the expression that makes it
+    // from the recognized `part`. Returns whether the access can fail.
+    fn generate_view_access(
+        &self,
+        ret: &mut String,
+        expr: &mut String,
+        module: ModuleId,
+        ty: &Type,
+        part: &str,
+    ) -> bool {
+        match ty.id {
+            TypeId::User(id) if self.is_struct(id) => {
+                self.generate_view_path(ret, module, id);
+                ret.push_str("<'a>");
+
+                self.generate_view_path(expr, module, id);
+                expr.push_str("::recognize(&mut ");
+                expr.push_str(part);
+                expr.push_str(".to_bytes())");
+                true
+            }
+            // Bools in vectors are packed, so they cannot be accessed lazily.
+            TypeId::Builtin(BuiltinType::Vector)
+                if ty.generics[0].id != TypeId::Builtin(BuiltinType::Bool) =>
+            {
+                match ty.generics[0].id {
+                    TypeId::User(id) if self.is_struct(id) => {
+                        expr.push_str(part);
+                        expr.push_str(".iter()");
+                        self.generate_view_items(ret, expr, module, id, "ExactSizeIterator");
+                        false
+                    }
+                    _ => {
+                        ret.push_str("::phenix_runtime::VectorSlice<'a,");
+                        self.generate_type(ret, module, &ty.generics[0]);
+                        ret.push('>');
+
+                        expr.push_str(part);
+                        expr.push_str(".items()");
+                        true
+                    }
+                }
+            }
+            TypeId::Builtin(BuiltinType::Stream) => {
+                expr.push_str(part);
+                expr.push_str(".iter()");
+
+                match ty.generics[0].id {
+                    TypeId::User(id) if self.is_struct(id) => {
+                        self.generate_view_items(ret, expr, module, id, "Iterator");
+                    }
+                    _ => {
+                        ret.push_str("::phenix_runtime::StreamIter<'a,");
+                        self.generate_type(ret, module, &ty.generics[0]);
+                        ret.push('>');
+                    }
+                }
+
+                false
+            }
+            _ => {
+                self.generate_type(ret, module, ty);
+                expr.push_str(part);
+                expr.push_str(".decode()");
+                true
+            }
+        }
+    }
+
+    // Items of vectors and streams of struct types are accessed as views.
+    fn generate_view_items(
+        &self,
+        ret: &mut String,
+        expr: &mut String,
+        module: ModuleId,
+        id: UserTypeId,
+        iter: &str,
+    ) {
+        ret.push_str("impl ");
+        ret.push_str(iter);
+        ret.push_str("<Item = Result<");
+        self.generate_view_path(ret, module, id);
+        ret.push_str("<'a>, ::phenix_runtime::DecodingError>> + 'a");
+
+        expr.push_str(".map(|item| item.and_then(|item| ");
+        self.generate_view_path(expr, module, id);
+        expr.push_str("::recognize(&mut item.to_bytes())))");
+    }
+
+    fn generate_view_path(&self, content: &mut String, module: ModuleId, id: UserTypeId) {
+        self.generate_user_type_path(content, module, id);
+        content.push_str("View");
+    }
+
+    fn is_struct(&self, id: UserTypeId) -> bool {
+        matches!(self.ctx.find_type(id), Some(UserType::Struct(_)))
+    }
+
     fn generate_enum_type(&self, content: &mut String, module: ModuleId, ty: &EnumType) {
         content.push_str("#[derive(Encodable, Decodable, Debug, Clone, PartialEq)]");
+        content.push_str("#[phenix_runtime::by_parts]");
 
         self.generate_attributes(content, &ty.attrs);
 
+        // Variants of a non-exhaustive enum unknown to this version are decoded
+        // into an extra variant.
+        let unknown_variant = if ty.is_exhaustive() {
+            None
+        } else if ty
+            .variants
+            .iter()
+            .any(|variant| variant.name.to_case(Case::Pascal) == "Unknown")
+        {
+            Some("UnknownVariant")
+        } else {
+            Some("Unknown")
+        };
+
+        // Explicit discriminants on variants with data require a primitive
+        // representation. The extra variant then needs an explicit
+        // discriminant too, one not used by the others.
+        let has_tags = ty.variants.iter().any(|variant| variant.tag.is_some());
+        let has_data = ty.variants.iter().any(|variant| !variant.fields.is_empty())
+            || unknown_variant.is_some();
+
+        let unknown_tag = if has_tags && unknown_variant.is_some() {
+            let discriminants = ty.discriminants().collect::<Vec<_>>();
+            (0..).find(|discriminant| !discriminants.contains(discriminant))
+        } else {
+            None
+        };
+
+        if has_tags && has_data {
+            let max = ty
+                .discriminants()
+                .chain(unknown_tag)
+                .max()
+                .unwrap_or_default();
+
+            let repr = if max <= u8::MAX as usize {
+                "u8"
+            } else if max <= u16::MAX as usize {
+                "u16"
+            } else if max <= u32::MAX as usize {
+                "u32"
+            } else {
+                "u64"
+            };
+
+            content.push_str("#[repr(");
+            content.push_str(repr);
+            content.push_str(")]");
+        }
+
         content.push_str("pub enum ");
         content.push_str(&ty.name.to_case(Case::Pascal));
         content.push_str(" {");
@@ -104,6 +424,18 @@ impl Generator {
             self.generate_variant(content, module, variant);
         }
 
+        if let Some(unknown_variant) = unknown_variant {
+            content.push_str(unknown_variant);
+            content.push_str("(::phenix_runtime::UnknownVariant)");
+
+            if let Some(tag) = unknown_tag {
+                content.push('=');
+                content.push_str(&tag.to_string());
+            }
+
+            content.push(',');
+        }
+
         content.push('}');
     }
 
@@ -142,7 +474,25 @@ impl Generator {
 
         content.push_str(&field.name.to_case(Case::Snake));
         content.push(':');
-        self.generate_type(content, module, &field.ty);
+
+        // The derive macros recognize optional fields only by the `Option`
+        // name, so it must not be fully qualified.
+        if field.optional {
+            content.push_str("Option<");
+        }
+
+        if field.attrs.contains(&Attribute::Lazy) {
+            content.push_str("::phenix_runtime::Lazy<");
+            self.generate_type(content, module, &field.ty);
+            content.push('>');
+        } else {
+            self.generate_type(content, module, &field.ty);
+        }
+
+        if field.optional {
+            content.push('>');
+        }
+
         content.push(',');
     }
 
@@ -159,11 +509,35 @@ impl Generator {
             content.push('}');
         }
 
+        if let Some(tag) = variant.tag {
+            content.push('=');
+            content.push_str(&tag.to_string());
+        }
+
         content.push(',');
     }
 
     fn generate_type(&self, content: &mut String, module: ModuleId, ty: &Type) {
         match ty.id {
+            TypeId::Builtin(BuiltinType::Array(len)) => {
+                content.push('[');
+                self.generate_type(content, module, &ty.generics[0]);
+                content.push(';');
+                content.push_str(&len.to_string());
+                content.push(']');
+                return;
+            }
+            TypeId::Builtin(BuiltinType::Tuple) => {
+                content.push('(');
+
+                for ty in ty.generics.iter() {
+                    self.generate_type(content, module, ty);
+                    content.push(',');
+                }
+
+                content.push(')');
+                return;
+            }
             TypeId::Builtin(BuiltinType::Bool) => content.push_str("bool"),
             TypeId::Builtin(BuiltinType::U8) => content.push_str("u8"),
             TypeId::Builtin(BuiltinType::U16) => content.push_str("u16"),
@@ -178,21 +552,21 @@ impl Generator {
             TypeId::Builtin(BuiltinType::Uint) => content.push_str("::phenix_runtime::Uint"),
             TypeId::Builtin(BuiltinType::Sint) => content.push_str("::phenix_runtime::Sint"),
             TypeId::Builtin(BuiltinType::Float) => content.push_str("::phenix_runtime::Float"),
-            TypeId::Builtin(BuiltinType::String) => content.push_str("::std::string::String"),
-            TypeId::Builtin(BuiltinType::Vector) => content.push_str("::std::vec::Vec"),
+            TypeId::Builtin(BuiltinType::String) => {
+                content.push_str("::phenix_runtime::__private::String")
+            }
+            TypeId::Builtin(BuiltinType::Vector) => {
+                content.push_str("::phenix_runtime::__private::Vec")
+            }
             TypeId::Builtin(BuiltinType::Stream) => content.push_str("::phenix_runtime::Stream"),
-            TypeId::User(id) => {
-                let current_module = self.ctx.module(module).unwrap();
-                let source_module = self.ctx.find_module(id).unwrap();
-                let user_ty = self.ctx.find_type(id).unwrap();
-
-                if source_module != current_module {
-                    self.generate_relative_path(content, current_module, source_module);
-                    content.push_str("::");
-                }
-
-                content.push_str(user_ty.name());
+            TypeId::Builtin(BuiltinType::Framed) => {
+                content.push_str("::phenix_runtime::FramedStream")
             }
+            TypeId::Builtin(BuiltinType::Map) => match self.config.map_type {
+                MapType::BTreeMap => content.push_str("::phenix_runtime::__private::BTreeMap"),
+                MapType::HashMap => content.push_str("::phenix_runtime::__private::HashMap"),
+            },
+            TypeId::User(id) => self.generate_user_type_path(content, module, id),
         }
 
         if !ty.generics.is_empty() {
@@ -209,6 +583,19 @@ impl Generator {
         }
     }
 
+    fn generate_user_type_path(&self, content: &mut String, module: ModuleId, id: UserTypeId) {
+        let current_module = self.ctx.module(module).unwrap();
+        let source_module = self.ctx.find_module(id).unwrap();
+        let user_ty = self.ctx.find_type(id).unwrap();
+
+        if source_module != current_module {
+            self.generate_relative_path(content, current_module, source_module);
+            content.push_str("::");
+        }
+
+        content.push_str(user_ty.name());
+    }
+
     fn generate_relative_path(&self, content: &mut String, from: &Module, to: &Module) {
         let from = from.path.as_slice();
         let to = to.path.as_slice();
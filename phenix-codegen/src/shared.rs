@@ -2,7 +2,10 @@ use derive_more::Deref;
 use indexmap::IndexMap;
 use rustc_hash::FxHashMap;
 
-use crate::{Module, ModuleId, Project, Type, TypeId, UserType, UserTypeId};
+use crate::{
+    Attribute, EnumType, FlagsType, Module, ModuleId, Project, StructType, Type, TypeId, UserType,
+    UserTypeId,
+};
 
 pub struct ProjectContext {
     project: Project,
@@ -60,6 +63,39 @@ impl ProjectContext {
     }
 }
 
+impl Project {
+    pub fn find_type(&self, id: UserTypeId) -> Option<&UserType> {
+        self.modules
+            .iter()
+            .flat_map(|module| module.types.iter())
+            .find(|ty| ty.id() == id)
+    }
+
+    // Finds a user type by its name, which may be qualified by the module
+    // path, for example `nested::Project`. Unqualified names must be unique
+    // across all modules.
+    pub fn find_by_name(&self, name: &str) -> Option<&UserType> {
+        let (path, name) = match name.rsplit_once("::") {
+            Some((path, name)) => (Some(path.split("::").collect::<Vec<_>>()), name),
+            None => (None, name),
+        };
+
+        let mut found = self.modules.iter().flat_map(|module| {
+            let path = path.as_ref();
+            module
+                .types
+                .iter()
+                .filter(move |ty| ty.name() == name)
+                .filter(move |_| path.is_none_or(|path| module.path.as_slice() == path))
+        });
+
+        match (found.next(), found.next()) {
+            (Some(ty), None) => Some(ty),
+            _ => None,
+        }
+    }
+}
+
 impl UserType {
     pub fn id(&self) -> UserTypeId {
         match self {
@@ -92,6 +128,54 @@ impl UserType {
     }
 }
 
+impl StructType {
+    pub fn is_exhaustive(&self) -> bool {
+        is_exhaustive(&self.attrs)
+    }
+}
+
+impl EnumType {
+    pub fn is_exhaustive(&self) -> bool {
+        is_exhaustive(&self.attrs)
+    }
+
+    // Variants without an explicit tag have the discriminant of the previous
+    // variant plus one. The iteration stops at the first variant whose
+    // discriminant overflows.
+    pub fn discriminants(&self) -> impl Iterator<Item = usize> + '_ {
+        self.variants.iter().scan(Some(0), |next, variant| {
+            let discriminant = variant.tag.or(*next)?;
+            *next = discriminant.checked_add(1);
+            Some(discriminant)
+        })
+    }
+
+    // Discriminants that do not fit into a byte and those of non-exhaustive
+    // enums are encoded as variable-length integers. The derive macros in the
+    // runtime must implement the same rule.
+    pub fn is_relaxed_discriminant(&self) -> bool {
+        !self.is_exhaustive()
+            || self
+                .discriminants()
+                .any(|discriminant| discriminant > u8::MAX as usize)
+    }
+}
+
+impl FlagsType {
+    pub fn is_exhaustive(&self) -> bool {
+        is_exhaustive(&self.attrs)
+    }
+
+    // Exhaustive flags are encoded as a bitmap of this size without the length.
+    pub fn byte_size(&self) -> usize {
+        byte_size(self.flags.len())
+    }
+}
+
+fn is_exhaustive(attrs: &[Attribute]) -> bool {
+    !attrs.contains(&Attribute::NonExhaustive)
+}
+
 pub struct ModuleTree<'a> {
     id: Option<ModuleId>,
     name: String,
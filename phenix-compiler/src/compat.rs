@@ -0,0 +1,525 @@
+use std::fmt;
+
+use phenix_codegen::{
+    BuiltinType, EnumType, Field, FlagsType, Project, StructType, Type, TypeId, UserType,
+    UserTypeId,
+};
+use rustc_hash::FxHashSet;
+
+// How the data written with one version of a schema can be read with the
+// other version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Compatibility {
+    // Both old and new readers read data written by either version.
+    Wire,
+    // New readers read old data, but old readers may fail on new data.
+    ReaderOnly,
+    // New readers may fail on or misread old data.
+    Breaking,
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compatibility::Wire => write!(f, "wire-compatible"),
+            Compatibility::ReaderOnly => write!(f, "reader-only-compatible"),
+            Compatibility::Breaking => write!(f, "breaking"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    // Path from the root type to the changed value, for example
+    // `Person.skills.value`.
+    pub path: String,
+    pub description: String,
+    pub compatibility: Compatibility,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.compatibility, self.path, self.description
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub changes: Vec<Change>,
+}
+
+impl Report {
+    // The compatibility of the schemas is that of the worst change.
+    pub fn compatibility(&self) -> Compatibility {
+        self.changes
+            .iter()
+            .map(|change| change.compatibility)
+            .max()
+            .unwrap_or(Compatibility::Wire)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+
+        write!(f, "{}", self.compatibility())
+    }
+}
+
+// Compares the types reachable from the root type with given name in the old
+// and new version of a schema. The name may be qualified by the module path,
+// for example `nested::Project`.
+pub fn check(old: &Project, new: &Project, root: &str) -> Report {
+    let mut checker = Checker {
+        old,
+        new,
+        visited: FxHashSet::default(),
+        report: Report::default(),
+    };
+
+    match (old.find_by_name(root), new.find_by_name(root)) {
+        (Some(old), Some(new)) => checker.check_user_type(old, new, root),
+        (None, _) => checker.add(
+            root,
+            "type not found in the old schema",
+            Compatibility::Breaking,
+        ),
+        (_, None) => checker.add(
+            root,
+            "type not found in the new schema",
+            Compatibility::Breaking,
+        ),
+    }
+
+    checker.report
+}
+
+struct Checker<'a> {
+    old: &'a Project,
+    new: &'a Project,
+    // Pairs of user types that were already compared.
+    visited: FxHashSet<(UserTypeId, UserTypeId)>,
+    report: Report,
+}
+
+impl<'a> Checker<'a> {
+    fn add(&mut self, path: &str, description: impl Into<String>, compatibility: Compatibility) {
+        self.report.changes.push(Change {
+            path: path.to_string(),
+            description: description.into(),
+            compatibility,
+        });
+    }
+
+    fn check_type(&mut self, old: &Type, new: &Type, path: &str) {
+        match (old.id, new.id) {
+            (TypeId::Builtin(old_builtin), TypeId::Builtin(new_builtin))
+                if old_builtin == new_builtin && old.generics.len() == new.generics.len() =>
+            {
+                for (i, (old, new)) in old.generics.iter().zip(new.generics.iter()).enumerate() {
+                    let path = match (old_builtin, i) {
+                        (BuiltinType::Map, 0) => format!("{}.key", path),
+                        (BuiltinType::Map, _) => format!("{}.value", path),
+                        (BuiltinType::Tuple, i) => format!("{}.{}", path, i),
+                        _ => format!("{}[]", path),
+                    };
+
+                    self.check_type(old, new, &path);
+                }
+            }
+            (TypeId::User(old), TypeId::User(new)) => {
+                match (self.old.find_type(old), self.new.find_type(new)) {
+                    (Some(old), Some(new)) => self.check_user_type(old, new, path),
+                    _ => self.add(path, "type not defined", Compatibility::Breaking),
+                }
+            }
+            _ => self.add(
+                path,
+                format!(
+                    "type changed from {} to {}",
+                    self.type_name(self.old, old),
+                    self.type_name(self.new, new)
+                ),
+                Compatibility::Breaking,
+            ),
+        }
+    }
+
+    fn check_user_type(&mut self, old: &'a UserType, new: &'a UserType, path: &str) {
+        // Types used in multiple places are compared only once, which also
+        // stops the recursion of recursive types.
+        if !self.visited.insert((old.id(), new.id())) {
+            return;
+        }
+
+        // Names are not encoded.
+        if old.name() != new.name() {
+            self.add(
+                path,
+                format!("type renamed from {} to {}", old.name(), new.name()),
+                Compatibility::Wire,
+            );
+        }
+
+        match (old, new) {
+            (UserType::Struct(old), UserType::Struct(new)) => self.check_struct(old, new, path),
+            (UserType::Enum(old), UserType::Enum(new)) => self.check_enum(old, new, path),
+            (UserType::Flags(old), UserType::Flags(new)) => self.check_flags(old, new, path),
+            _ => self.add(
+                path,
+                format!("{} changed from {} to {}", old.name(), kind(old), kind(new)),
+                Compatibility::Breaking,
+            ),
+        }
+    }
+
+    fn check_struct(&mut self, old: &'a StructType, new: &'a StructType, path: &str) {
+        if !self.check_exhaustive(old.is_exhaustive(), new.is_exhaustive(), &old.name, path) {
+            return;
+        }
+
+        self.check_fields(
+            &old.fields,
+            &new.fields,
+            new.is_exhaustive(),
+            &old.name,
+            path,
+        );
+    }
+
+    fn check_enum(&mut self, old: &'a EnumType, new: &'a EnumType, path: &str) {
+        if !self.check_exhaustive(old.is_exhaustive(), new.is_exhaustive(), &old.name, path) {
+            return;
+        }
+
+        let is_exhaustive = new.is_exhaustive();
+        let old_discriminants = old.discriminants().collect::<Vec<_>>();
+        let new_discriminants = new.discriminants().collect::<Vec<_>>();
+
+        if old.is_relaxed_discriminant() != new.is_relaxed_discriminant() {
+            self.add(
+                path,
+                format!("discriminant encoding of {} changed", old.name),
+                Compatibility::Breaking,
+            );
+            return;
+        }
+
+        // Variants are identified by their discriminants.
+        for (old_variant, discriminant) in old.variants.iter().zip(old_discriminants.iter()) {
+            let new_variant = new
+                .variants
+                .iter()
+                .zip(new_discriminants.iter())
+                .find(|(_, other)| *other == discriminant)
+                .map(|(variant, _)| variant);
+
+            let new_variant = match new_variant {
+                Some(new_variant) => new_variant,
+                None => {
+                    self.add(
+                        path,
+                        format!("variant {}::{} removed", old.name, old_variant.name),
+                        Compatibility::Breaking,
+                    );
+                    continue;
+                }
+            };
+
+            if old_variant.name != new_variant.name {
+                let moved = new
+                    .variants
+                    .iter()
+                    .any(|variant| variant.name == old_variant.name);
+
+                if moved {
+                    self.add(
+                        path,
+                        format!(
+                            "discriminant of variant {}::{} changed",
+                            old.name, old_variant.name
+                        ),
+                        Compatibility::Breaking,
+                    );
+                    continue;
+                }
+
+                self.add(
+                    path,
+                    format!(
+                        "variant {}::{} renamed to {}",
+                        old.name, old_variant.name, new_variant.name
+                    ),
+                    Compatibility::Wire,
+                );
+            }
+
+            self.check_fields(
+                &old_variant.fields,
+                &new_variant.fields,
+                is_exhaustive,
+                &format!("{}::{}", old.name, old_variant.name),
+                &format!("{}.{}", path, old_variant.name),
+            );
+        }
+
+        // Old readers do not know the new discriminants, unless the enum is
+        // non-exhaustive and they keep the unknown variants as they are.
+        for (new_variant, discriminant) in new.variants.iter().zip(new_discriminants.iter()) {
+            if !old_discriminants.contains(discriminant)
+                && !old
+                    .variants
+                    .iter()
+                    .any(|variant| variant.name == new_variant.name)
+            {
+                let compatibility = if is_exhaustive {
+                    Compatibility::ReaderOnly
+                } else {
+                    Compatibility::Wire
+                };
+
+                self.add(
+                    path,
+                    format!("variant {}::{} added", new.name, new_variant.name),
+                    compatibility,
+                );
+            }
+        }
+    }
+
+    // Fields are identified by their position. The body of a non-exhaustive
+    // type is prefixed with its length, so readers can skip the trailing
+    // fields they do not know.
+    fn check_fields(
+        &mut self,
+        old: &'a [Field],
+        new: &'a [Field],
+        is_exhaustive: bool,
+        owner: &str,
+        path: &str,
+    ) {
+        for (i, (old_field, new_field)) in old.iter().zip(new.iter()).enumerate() {
+            let field_path = format!("{}.{}", path, old_field.name);
+
+            if old_field.name != new_field.name {
+                match new.iter().position(|field| field.name == old_field.name) {
+                    Some(j) => {
+                        self.add(
+                            &field_path,
+                            format!("field moved from position {} to {}", i, j),
+                            Compatibility::Breaking,
+                        );
+                        continue;
+                    }
+                    None => self.add(
+                        &field_path,
+                        format!("field renamed to {}", new_field.name),
+                        Compatibility::Wire,
+                    ),
+                }
+            }
+
+            if old_field.optional != new_field.optional {
+                let description = if new_field.optional {
+                    "field made optional"
+                } else {
+                    "field made required"
+                };
+                self.add(&field_path, description, Compatibility::Breaking);
+                continue;
+            }
+
+            self.check_type(&old_field.ty, &new_field.ty, &field_path);
+        }
+
+        for new_field in new.iter().skip(old.len()) {
+            if old.iter().any(|field| field.name == new_field.name) {
+                continue;
+            }
+
+            let field_path = format!("{}.{}", path, new_field.name);
+
+            let compatibility = if is_exhaustive {
+                Compatibility::Breaking
+            } else if new_field.optional {
+                Compatibility::Wire
+            } else {
+                // Old data miss the field.
+                Compatibility::Breaking
+            };
+
+            self.add(
+                &field_path,
+                format!("field added to {}", owner),
+                compatibility,
+            );
+        }
+
+        for old_field in old.iter().skip(new.len()) {
+            if new.iter().any(|field| field.name == old_field.name) {
+                continue;
+            }
+
+            let field_path = format!("{}.{}", path, old_field.name);
+
+            let compatibility = if is_exhaustive {
+                Compatibility::Breaking
+            } else if old_field.optional {
+                Compatibility::Wire
+            } else {
+                // Old readers require the field.
+                Compatibility::ReaderOnly
+            };
+
+            self.add(
+                &field_path,
+                format!("field removed from {}", owner),
+                compatibility,
+            );
+        }
+    }
+
+    // Flags are identified by their position.
+    fn check_flags(&mut self, old: &FlagsType, new: &FlagsType, path: &str) {
+        if !self.check_exhaustive(old.is_exhaustive(), new.is_exhaustive(), &old.name, path) {
+            return;
+        }
+
+        let is_exhaustive = new.is_exhaustive();
+
+        // Exhaustive flags are encoded without the length, which depends on the
+        // number of flags.
+        if is_exhaustive && old.byte_size() != new.byte_size() {
+            self.add(
+                path,
+                format!(
+                    "number of {} flags changed from {} to {}",
+                    old.name,
+                    old.flags.len(),
+                    new.flags.len()
+                ),
+                Compatibility::Breaking,
+            );
+            return;
+        }
+
+        for (i, (old_flag, new_flag)) in old.flags.iter().zip(new.flags.iter()).enumerate() {
+            if old_flag == new_flag {
+                continue;
+            }
+
+            match new.flags.iter().position(|flag| flag == old_flag) {
+                Some(j) => self.add(
+                    path,
+                    format!(
+                        "flag {}::{} moved from position {} to {}",
+                        old.name, old_flag, i, j
+                    ),
+                    Compatibility::Breaking,
+                ),
+                None => self.add(
+                    path,
+                    format!("flag {}::{} renamed to {}", old.name, old_flag, new_flag),
+                    Compatibility::Wire,
+                ),
+            }
+        }
+
+        // Unknown flags of exhaustive types are rejected by the strict validation.
+        for new_flag in new.flags.iter().skip(old.flags.len()) {
+            let compatibility = if is_exhaustive {
+                Compatibility::ReaderOnly
+            } else {
+                Compatibility::Wire
+            };
+
+            self.add(
+                path,
+                format!("flag {}::{} added", new.name, new_flag),
+                compatibility,
+            );
+        }
+
+        for old_flag in old.flags.iter().skip(new.flags.len()) {
+            let compatibility = if is_exhaustive {
+                Compatibility::Breaking
+            } else {
+                Compatibility::Wire
+            };
+
+            self.add(
+                path,
+                format!("flag {}::{} removed", old.name, old_flag),
+                compatibility,
+            );
+        }
+    }
+
+    // Returns false if the exhaustiveness changed, in which case the rest of
+    // the types is not compared.
+    fn check_exhaustive(&mut self, old: bool, new: bool, name: &str, path: &str) -> bool {
+        if old == new {
+            return true;
+        }
+
+        let description = if new {
+            format!("{} made exhaustive", name)
+        } else {
+            format!("{} made non-exhaustive", name)
+        };
+
+        self.add(path, description, Compatibility::Breaking);
+        false
+    }
+
+    fn type_name(&self, project: &Project, ty: &Type) -> String {
+        let name = match ty.id {
+            TypeId::Builtin(BuiltinType::Array(len)) => {
+                return format!(
+                    "array<{}, {}>",
+                    self.type_name(project, &ty.generics[0]),
+                    len
+                );
+            }
+            TypeId::Builtin(BuiltinType::Tuple) => {
+                let generics = ty
+                    .generics
+                    .iter()
+                    .map(|ty| self.type_name(project, ty))
+                    .collect::<Vec<_>>();
+                return format!("({})", generics.join(", "));
+            }
+            TypeId::Builtin(builtin) => format!("{:?}", builtin).to_lowercase(),
+            TypeId::User(id) => match project.find_type(id) {
+                Some(ty) => ty.name().to_string(),
+                None => format!("#{}", *id),
+            },
+        };
+
+        if ty.generics.is_empty() {
+            name
+        } else {
+            let generics = ty
+                .generics
+                .iter()
+                .map(|ty| self.type_name(project, ty))
+                .collect::<Vec<_>>();
+            format!("{}<{}>", name, generics.join(", "))
+        }
+    }
+}
+
+fn kind(ty: &UserType) -> &'static str {
+    match ty {
+        UserType::Struct(_) => "struct",
+        UserType::Enum(_) => "enum",
+        UserType::Flags(_) => "flags",
+    }
+}
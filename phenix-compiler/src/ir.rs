@@ -1,23 +1,26 @@
 use derive_more::From;
 
-use crate::syntax::ast::{self, HasName};
+use crate::syntax::ast::{self, HasAttrs, HasName};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct StructData {
     pub name: String,
     pub fields: Vec<FieldData>,
+    pub attrs: Vec<AttributeData>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EnumData {
     pub name: String,
     pub variants: Vec<VariantData>,
+    pub attrs: Vec<AttributeData>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FlagsData {
     pub name: String,
     pub flags: Vec<FlagData>,
+    pub attrs: Vec<AttributeData>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, From)]
@@ -31,12 +34,15 @@ pub enum ItemData {
 pub struct FieldData {
     pub name: String,
     pub ty: TypeData,
+    pub optional: bool,
+    pub attrs: Vec<AttributeData>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct VariantData {
     pub name: String,
     pub fields: Vec<FieldData>,
+    pub tag: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -44,12 +50,21 @@ pub struct FlagData {
     pub name: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AttributeData {
+    pub name: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TypeData {
     pub name: String,
     pub generics: Vec<TypeData>,
+    pub size: Option<usize>,
 }
 
+// Name of anonymous tuple types, which cannot clash with user type names.
+pub const TUPLE_NAME: &str = "()";
+
 impl ItemData {
     pub fn from_ast(node: ast::ItemDef) -> Option<Self> {
         match node.kind() {
@@ -72,8 +87,13 @@ impl StructData {
     pub fn from_ast(node: ast::StructDef) -> Option<Self> {
         let name = node.name()?.to_string();
         let fields = node.fields().filter_map(FieldData::from_ast).collect();
+        let attrs = node.attrs().filter_map(AttributeData::from_ast).collect();
 
-        Some(StructData { name, fields })
+        Some(StructData {
+            name,
+            fields,
+            attrs,
+        })
     }
 }
 
@@ -81,8 +101,13 @@ impl EnumData {
     pub fn from_ast(node: ast::EnumDef) -> Option<Self> {
         let name = node.name()?.to_string();
         let variants = node.variants().filter_map(VariantData::from_ast).collect();
+        let attrs = node.attrs().filter_map(AttributeData::from_ast).collect();
 
-        Some(EnumData { name, variants })
+        Some(EnumData {
+            name,
+            variants,
+            attrs,
+        })
     }
 }
 
@@ -90,8 +115,9 @@ impl FlagsData {
     pub fn from_ast(node: ast::FlagsDef) -> Option<Self> {
         let name = node.name()?.to_string();
         let flags = node.flags().filter_map(FlagData::from_ast).collect();
+        let attrs = node.attrs().filter_map(AttributeData::from_ast).collect();
 
-        Some(FlagsData { name, flags })
+        Some(FlagsData { name, flags, attrs })
     }
 }
 
@@ -99,8 +125,23 @@ impl FieldData {
     pub fn from_ast(node: ast::Field) -> Option<Self> {
         let name = node.name()?.to_string();
         let ty = node.ty().and_then(TypeData::from_ast)?;
-
-        Some(FieldData { name, ty })
+        let attrs = node.attrs().filter_map(AttributeData::from_ast).collect();
+
+        // Both `field?: T` and `field: option<T>` denote an optional field.
+        // Using both at once is reported in semantic analysis.
+        let (ty, optional) = match ty.name.as_str() {
+            "option" if ty.generics.len() == 1 && !node.is_optional() => {
+                (ty.generics.into_iter().next().unwrap(), true)
+            }
+            _ => (ty, node.is_optional()),
+        };
+
+        Some(FieldData {
+            name,
+            ty,
+            optional,
+            attrs,
+        })
     }
 }
 
@@ -108,8 +149,9 @@ impl VariantData {
     pub fn from_ast(node: ast::Variant) -> Option<Self> {
         let name = node.name()?.to_string();
         let fields = node.fields().filter_map(FieldData::from_ast).collect();
+        let tag = node.tag();
 
-        Some(VariantData { name, fields })
+        Some(VariantData { name, fields, tag })
     }
 }
 
@@ -121,11 +163,29 @@ impl FlagData {
     }
 }
 
+impl AttributeData {
+    pub fn from_ast(node: ast::Attribute) -> Option<Self> {
+        let name = node.name()?.to_string();
+
+        Some(AttributeData { name })
+    }
+}
+
 impl TypeData {
     pub fn from_ast(node: ast::Type) -> Option<Self> {
-        let name = node.name()?.to_string();
+        let name = if node.is_tuple() {
+            TUPLE_NAME.to_string()
+        } else {
+            node.name()?.to_string()
+        };
+
         let generics = node.generics().filter_map(TypeData::from_ast).collect();
+        let size = node.size();
 
-        Some(TypeData { name, generics })
+        Some(TypeData {
+            name,
+            generics,
+            size,
+        })
     }
 }
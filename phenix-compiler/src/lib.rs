@@ -1,5 +1,8 @@
+use std::{error::Error, fmt};
+
 use phenix_codegen::{Module, ModuleId, ModulePath, Project};
 
+pub mod compat;
 mod database;
 pub(crate) mod hash;
 mod ir;
@@ -17,6 +20,20 @@ pub struct Compiler {
     db: RootDatabase,
 }
 
+// Types that are not valid, each error is on its own line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.errors.join("\n"))
+    }
+}
+
+impl Error for CompileError {}
+
 impl Compiler {
     pub fn new() -> Self {
         Self {
@@ -24,7 +41,7 @@ impl Compiler {
         }
     }
 
-    pub fn compile(&self, root_file: VfsPath) -> Project {
+    pub fn compile(&self, root_file: VfsPath) -> Result<Project, CompileError> {
         let root_file_id = self.db.intern_file(root_file.clone());
         let modules = self.db.reachable_files(root_file_id);
 
@@ -33,6 +50,8 @@ impl Compiler {
             .parent()
             .expect("vfs path represents a file");
 
+        let mut errors = Vec::new();
+
         let modules = modules
             .iter()
             .copied()
@@ -51,7 +70,11 @@ impl Compiler {
                     .module_defs(module_file_id)
                     .values()
                     .copied()
-                    .filter_map(|item_id| semantics::make_def(&self.db, module_file_id, item_id))
+                    .filter_map(|item_id| {
+                        semantics::make_def(&self.db, module_file_id, item_id)
+                            .map_err(|error| errors.push(error))
+                            .ok()
+                    })
                     .collect::<Vec<_>>();
 
                 types.sort_by_key(|ty| match ty {
@@ -68,12 +91,18 @@ impl Compiler {
             })
             .collect();
 
-        Project { modules }
+        if errors.is_empty() {
+            Ok(Project { modules })
+        } else {
+            // Definitions are not ordered, keep the errors deterministic.
+            errors.sort();
+            Err(CompileError { errors })
+        }
     }
 
     pub fn compile_and_watch<F>(&mut self, root_file: VfsPath, mut callback: F)
     where
-        F: FnMut(Project),
+        F: FnMut(Result<Project, CompileError>),
     {
         loop {
             callback(self.compile(root_file.clone()));
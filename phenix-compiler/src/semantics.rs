@@ -1,13 +1,13 @@
 use std::path::Path;
 
 use phenix_codegen::{
-    BuiltinType, EnumType, Field, FlagsType, ModulePath, StructType, Type, TypeId, UserType,
-    UserTypeId, Variant,
+    Attribute, BuiltinType, EnumType, Field, FlagsType, ModulePath, StructType, Type, TypeId,
+    UserType, UserTypeId, Variant,
 };
 
 use crate::{
     database::ir::{DefDatabase, ItemId, VfsFileId},
-    ir::{ItemData, TypeData},
+    ir::{AttributeData, FieldData, ItemData, TypeData, VariantData, TUPLE_NAME},
 };
 
 pub fn resolve_module_path(root_dir: &Path, module_file: &Path) -> ModulePath {
@@ -59,7 +59,11 @@ pub fn resolve_module_path(root_dir: &Path, module_file: &Path) -> ModulePath {
     }
 }
 
-pub fn resolve_type(db: &dyn DefDatabase, module: VfsFileId, ty: &TypeData) -> Option<Type> {
+pub fn resolve_type(
+    db: &dyn DefDatabase,
+    module: VfsFileId,
+    ty: &TypeData,
+) -> Result<Type, String> {
     let id = match ty.name.as_str() {
         "bool" => TypeId::Builtin(BuiltinType::Bool),
         "u8" => TypeId::Builtin(BuiltinType::U8),
@@ -78,79 +82,253 @@ pub fn resolve_type(db: &dyn DefDatabase, module: VfsFileId, ty: &TypeData) -> O
         "string" => TypeId::Builtin(BuiltinType::String),
         "vector" => TypeId::Builtin(BuiltinType::Vector),
         "stream" => TypeId::Builtin(BuiltinType::Stream),
-        _ => {
-            let item_id = db.module_item_by_name(module, ty.name.clone())?;
-            TypeId::User(UserTypeId(item_id.as_usize()))
-        }
+        "framed" => TypeId::Builtin(BuiltinType::Framed),
+        "map" => TypeId::Builtin(BuiltinType::Map),
+        "array" => match ty.size {
+            Some(size) => TypeId::Builtin(BuiltinType::Array(size)),
+            None => return Err("array without a length".to_string()),
+        },
+        TUPLE_NAME => TypeId::Builtin(BuiltinType::Tuple),
+        _ => match db.module_item_by_name(module, ty.name.clone()) {
+            Some(item_id) => TypeId::User(UserTypeId(item_id.as_usize())),
+            None => return Err(format!("unknown type `{}`", ty.name)),
+        },
+    };
+
+    let arity = match id {
+        TypeId::Builtin(
+            BuiltinType::Vector | BuiltinType::Stream | BuiltinType::Framed | BuiltinType::Array(_),
+        ) => Some(1),
+        TypeId::Builtin(BuiltinType::Map) => Some(2),
+        TypeId::Builtin(BuiltinType::Tuple) => None,
+        _ => Some(0),
     };
 
+    if let Some(arity) = arity {
+        if ty.generics.len() != arity {
+            return Err(format!(
+                "type `{}` expects {} type arguments, found {}",
+                ty.name,
+                arity,
+                ty.generics.len()
+            ));
+        }
+    }
+
     let generics = ty
         .generics
         .iter()
         .map(|ty| resolve_type(db, module, ty))
-        .collect::<Option<Vec<_>>>()?;
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Keys must be comparable and hashable in the generated code.
+    if id == TypeId::Builtin(BuiltinType::Map) {
+        match generics[0].id {
+            TypeId::Builtin(
+                BuiltinType::Bool
+                | BuiltinType::U8
+                | BuiltinType::U16
+                | BuiltinType::U32
+                | BuiltinType::U64
+                | BuiltinType::I8
+                | BuiltinType::I16
+                | BuiltinType::I32
+                | BuiltinType::I64
+                | BuiltinType::Uint
+                | BuiltinType::Sint
+                | BuiltinType::String,
+            ) => {}
+            _ => {
+                return Err(format!(
+                    "type `{}` cannot be a map key",
+                    ty.generics[0].name
+                ))
+            }
+        }
+    }
+
+    Ok(Type { id, generics })
+}
 
-    Some(Type { id, generics })
+fn resolve_attrs(attrs: &[AttributeData]) -> Result<Vec<Attribute>, String> {
+    attrs
+        .iter()
+        .map(|attr| match attr.name.as_str() {
+            "non_exhaustive" => Ok(Attribute::NonExhaustive),
+            name => Err(format!("unknown attribute `{}`", name)),
+        })
+        .collect()
 }
 
-pub fn make_def(db: &dyn DefDatabase, module: VfsFileId, item_id: ItemId) -> Option<UserType> {
+fn resolve_field(
+    db: &dyn DefDatabase,
+    module: VfsFileId,
+    field: FieldData,
+) -> Result<Field, String> {
+    let attrs = field
+        .attrs
+        .iter()
+        .map(|attr| match attr.name.as_str() {
+            "lazy" => Ok(Attribute::Lazy),
+            name => Err(format!(
+                "unknown attribute `{}` of field `{}`",
+                name, field.name
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Streams span until the end of the input and cannot be skipped.
+    if attrs.contains(&Attribute::Lazy) && field.ty.name == "stream" {
+        return Err(format!("stream field `{}` cannot be lazy", field.name));
+    }
+
+    if field.optional && field.ty.name == "option" {
+        return Err(format!("field `{}` is optional twice", field.name));
+    }
+
+    let ty = resolve_type(db, module, &field.ty)
+        .map_err(|error| format!("{} in field `{}`", error, field.name))?;
+
+    Ok(Field {
+        name: field.name,
+        ty,
+        optional: field.optional,
+        attrs,
+    })
+}
+
+fn resolve_variant(
+    db: &dyn DefDatabase,
+    module: VfsFileId,
+    variant: VariantData,
+) -> Result<Variant, String> {
+    let tag = variant
+        .tag
+        .map(|tag| {
+            tag.parse().map_err(|_| {
+                format!(
+                    "discriminant {} of variant `{}` is out of range",
+                    tag, variant.name
+                )
+            })
+        })
+        .transpose()?;
+
+    let fields = variant
+        .fields
+        .into_iter()
+        .map(|field| {
+            // Optional fields are supported only in structs.
+            if field.optional {
+                return Err(format!(
+                    "optional field `{}` in variant `{}`",
+                    field.name, variant.name
+                ));
+            }
+
+            resolve_field(db, module, field)
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Variant {
+        name: variant.name,
+        fields,
+        tag,
+        attrs: Vec::new(),
+    })
+}
+
+// Errors are prefixed with the name of the type.
+pub fn make_def(
+    db: &dyn DefDatabase,
+    module: VfsFileId,
+    item_id: ItemId,
+) -> Result<UserType, String> {
     let item = db.lookup_intern_item(item_id).into_inner();
+    let name = item.name().to_string();
+
+    make_def_data(db, module, item_id, item).map_err(|error| format!("{}: {}", name, error))
+}
+
+fn make_def_data(
+    db: &dyn DefDatabase,
+    module: VfsFileId,
+    item_id: ItemId,
+    item: ItemData,
+) -> Result<UserType, String> {
     let id = UserTypeId(item_id.as_usize());
 
     let def = match item {
-        ItemData::Struct(data) => StructType {
-            id,
-            name: data.name,
-            fields: data
-                .fields
-                .into_iter()
-                .map(|field| {
-                    resolve_type(db, module, &field.ty).map(|ty| Field {
-                        name: field.name,
-                        ty,
-                        attrs: Vec::new(),
-                    })
-                })
-                .collect::<Option<_>>()?,
-            attrs: Vec::new(),
+        ItemData::Struct(data) => {
+            let attrs = resolve_attrs(&data.attrs)?;
+
+            // The body of a non-exhaustive struct must have a known length,
+            // which is not the case for streams.
+            if attrs.contains(&Attribute::NonExhaustive) {
+                if let Some(field) = data.fields.iter().find(|field| field.ty.name == "stream") {
+                    return Err(format!(
+                        "non-exhaustive struct cannot contain stream field `{}`, use `framed` instead",
+                        field.name
+                    ));
+                }
+            }
+
+            StructType {
+                id,
+                name: data.name,
+                fields: data
+                    .fields
+                    .into_iter()
+                    .map(|field| resolve_field(db, module, field))
+                    .collect::<Result<_, _>>()?,
+                attrs,
+            }
+            .into()
         }
-        .into(),
-        ItemData::Enum(data) => EnumType {
-            id,
-            name: data.name,
-            variants: data
-                .variants
-                .into_iter()
-                .map(|variant| {
-                    variant
-                        .fields
-                        .into_iter()
-                        .map(|field| {
-                            resolve_type(db, module, &field.ty).map(|ty| Field {
-                                name: field.name,
-                                ty,
-                                attrs: Vec::new(),
-                            })
-                        })
-                        .collect::<Option<_>>()
-                        .map(|fields| Variant {
-                            name: variant.name,
-                            fields,
-                            attrs: Vec::new(),
-                        })
-                })
-                .collect::<Option<_>>()?,
-            attrs: Vec::new(),
+        ItemData::Enum(data) => {
+            let def = EnumType {
+                id,
+                name: data.name,
+                variants: data
+                    .variants
+                    .into_iter()
+                    .map(|variant| resolve_variant(db, module, variant))
+                    .collect::<Result<_, _>>()?,
+                attrs: resolve_attrs(&data.attrs)?,
+            };
+
+            let discriminants = def.discriminants().collect::<Vec<_>>();
+
+            if let Some(variant) = def.variants.get(discriminants.len()) {
+                return Err(format!(
+                    "discriminant of variant `{}` overflows",
+                    variant.name
+                ));
+            }
+
+            // Discriminants must be unique.
+            for (i, discriminant) in discriminants.iter().enumerate() {
+                if let Some(j) = discriminants[..i]
+                    .iter()
+                    .position(|other| other == discriminant)
+                {
+                    return Err(format!(
+                        "variants `{}` and `{}` have the same discriminant {}",
+                        def.variants[j].name, def.variants[i].name, discriminant
+                    ));
+                }
+            }
+
+            def.into()
         }
-        .into(),
         ItemData::Flags(data) => FlagsType {
             id,
             name: data.name,
             flags: data.flags.into_iter().map(|flag| flag.name).collect(),
-            attrs: Vec::new(),
+            attrs: resolve_attrs(&data.attrs)?,
         }
         .into(),
     };
 
-    Some(def)
+    Ok(def)
 }
@@ -11,9 +11,11 @@ pub enum SyntaxKind {
     Flag,
     Name,
     Type,
+    Attribute,
 
     Ident,
     String,
+    Number,
     KwStruct,
     KwEnum,
     KwFlags,
@@ -27,6 +29,13 @@ pub enum SyntaxKind {
     Comma,
     AngleBracketLeft,
     AngleBracketRight,
+    Question,
+    ParenLeft,
+    ParenRight,
+    Hash,
+    SquareBracketLeft,
+    SquareBracketRight,
+    Equals,
 
     WhiteSpace,
     Comment,
@@ -63,6 +72,10 @@ pub trait HasName: rowan::ast::AstNode<Language = Language> {
     fn name(&self) -> Option<Name>;
 }
 
+pub trait HasAttrs: rowan::ast::AstNode<Language = Language> {
+    fn attrs(&self) -> rowan::ast::AstChildren<Attribute>;
+}
+
 pub use nodes::*;
 
 mod nodes {
@@ -116,6 +129,7 @@ mod nodes {
     def_ast_node!(Type);
     def_ast_node!(Import);
     def_ast_node!(Alias);
+    def_ast_node!(Attribute);
 
     macro_rules! impl_has_name {
         ($node:ident) => {
@@ -134,6 +148,22 @@ mod nodes {
     impl_has_name!(Variant);
     impl_has_name!(Flag);
     impl_has_name!(Type);
+    impl_has_name!(Attribute);
+
+    macro_rules! impl_has_attrs {
+        ($node:ident) => {
+            impl HasAttrs for $node {
+                fn attrs(&self) -> rowan::ast::AstChildren<Attribute> {
+                    support::children(self.syntax())
+                }
+            }
+        };
+    }
+
+    impl_has_attrs!(StructDef);
+    impl_has_attrs!(EnumDef);
+    impl_has_attrs!(FlagsDef);
+    impl_has_attrs!(Field);
 
     #[derive(Debug, Clone, PartialEq, Eq, Hash)]
     pub struct ItemDef(SyntaxNode);
@@ -223,18 +253,37 @@ mod nodes {
         pub fn ty(&self) -> Option<Type> {
             support::child(self.syntax())
         }
+
+        pub fn is_optional(&self) -> bool {
+            support::token(self.syntax(), SyntaxKind::Question).is_some()
+        }
     }
 
     impl Variant {
         pub fn fields(&self) -> impl Iterator<Item = Field> + '_ {
             self.syntax().children().filter_map(Field::cast)
         }
+
+        // Explicit discriminant, the range is checked in semantic analysis.
+        pub fn tag(&self) -> Option<String> {
+            support::token(self.syntax(), SyntaxKind::Number).map(|token| token.text().to_string())
+        }
     }
 
     impl Type {
         pub fn generics(&self) -> impl Iterator<Item = Type> + '_ {
             self.syntax().children().filter_map(Type::cast)
         }
+
+        // Number argument, e.g., the length of an array.
+        pub fn size(&self) -> Option<usize> {
+            support::token(self.syntax(), SyntaxKind::Number)
+                .and_then(|token| token.text().parse().ok())
+        }
+
+        pub fn is_tuple(&self) -> bool {
+            support::token(self.syntax(), SyntaxKind::ParenLeft).is_some()
+        }
     }
 
     impl fmt::Display for Name {
@@ -67,12 +67,36 @@ pub enum Token<'source> {
     #[token(">")]
     AngleBracketRight,
 
+    #[token("?")]
+    Question,
+
+    #[token("(")]
+    ParenLeft,
+
+    #[token(")")]
+    ParenRight,
+
+    #[token("#")]
+    Hash,
+
+    #[token("[")]
+    SquareBracketLeft,
+
+    #[token("]")]
+    SquareBracketRight,
+
+    #[token("=")]
+    Equals,
+
     #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice())]
     Ident(&'source str),
 
     #[regex("\"[^\"]*\"", |lex| lex.slice())]
     String(&'source str),
 
+    #[regex("[0-9]+", |lex| lex.slice())]
+    Number(&'source str),
+
     #[regex("//[^\n\r]*")]
     Comment,
 
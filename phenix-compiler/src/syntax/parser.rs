@@ -1,5 +1,5 @@
 use logos::{Lexer, Logos};
-use rowan::{ast::AstNode, GreenNode, GreenNodeBuilder};
+use rowan::{ast::AstNode, Checkpoint, GreenNode, GreenNodeBuilder};
 
 use super::{
     ast::{self, SyntaxKind},
@@ -50,9 +50,9 @@ impl<'source> Parser<'source> {
 
             while let Some(token) = p.peek() {
                 match token {
-                    Token::KwStruct => p.parse_struct().recover(RecoverContext::StructDef, p),
-                    Token::KwEnum => p.parse_enum().recover(RecoverContext::EnumDef, p),
-                    Token::KwFlags => p.parse_flags().recover(RecoverContext::FlagsDef, p),
+                    Token::KwStruct | Token::KwEnum | Token::KwFlags | Token::Hash => {
+                        p.parse_item_def()
+                    }
                     Token::KwImport => p.parse_import().recover(RecoverContext::Import, p),
                     Token::KwFrom
                     | Token::KwAs
@@ -63,8 +63,15 @@ impl<'source> Parser<'source> {
                     | Token::Comma
                     | Token::AngleBracketLeft
                     | Token::AngleBracketRight
+                    | Token::Question
+                    | Token::ParenLeft
+                    | Token::ParenRight
+                    | Token::SquareBracketLeft
+                    | Token::SquareBracketRight
+                    | Token::Equals
                     | Token::Ident(_)
-                    | Token::String(_) => {
+                    | Token::String(_)
+                    | Token::Number(_) => {
                         p.raise_unexpected();
                         p.make_error();
                         Some(())
@@ -151,6 +158,7 @@ impl<'source> Parser<'source> {
             RecoverContext::EnumDef => self.bump_until(|token| token.is_item_def()),
             RecoverContext::FlagsDef => self.bump_until(|token| token.is_item_def()),
             RecoverContext::Import => self.bump_until(|token| token.is_item_def()),
+            RecoverContext::Attribute => self.bump_until(|token| token.is_item_def()),
         }
     }
 
@@ -164,6 +172,21 @@ impl<'source> Parser<'source> {
         ret
     }
 
+    fn parse_node_at<F, R>(
+        &mut self,
+        checkpoint: Checkpoint,
+        kind: SyntaxKind,
+        parser: F,
+    ) -> Option<R>
+    where
+        F: FnOnce(&mut Parser<'source>) -> Option<R>,
+    {
+        self.builder.start_node_at(checkpoint, kind.into());
+        let ret = parser(self);
+        self.builder.finish_node();
+        ret
+    }
+
     fn make_error(&mut self) {
         self.builder.start_node(SyntaxKind::Error.into());
         self.bump();
@@ -189,8 +212,53 @@ impl<'source> Parser<'source> {
         }
     }
 
-    fn parse_struct(&mut self) -> Option<()> {
-        self.parse_node(SyntaxKind::StructDef, |p| {
+    // Parses the attributes and the item definition they belong to.
+    fn parse_item_def(&mut self) -> Option<()> {
+        let checkpoint = self.builder.checkpoint();
+
+        while self.peek() == Some(Token::Hash) {
+            self.parse_attribute()
+                .recover(RecoverContext::Attribute, self)?;
+            self.eat_trivia();
+        }
+
+        match self.peek() {
+            Some(Token::KwStruct) => self
+                .parse_struct(checkpoint)
+                .recover(RecoverContext::StructDef, self),
+            Some(Token::KwEnum) => self
+                .parse_enum(checkpoint)
+                .recover(RecoverContext::EnumDef, self),
+            Some(Token::KwFlags) => self
+                .parse_flags(checkpoint)
+                .recover(RecoverContext::FlagsDef, self),
+            // Other tokens are reported by the caller.
+            Some(_) => Some(()),
+            None => {
+                self.raise_unexpected();
+                None
+            }
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Option<()> {
+        self.parse_node(SyntaxKind::Attribute, |p| {
+            p.expect(|token| token == Token::Hash)?;
+            p.eat_trivia();
+
+            p.expect(|token| token == Token::SquareBracketLeft)?;
+            p.eat_trivia();
+
+            p.parse_name()?;
+            p.eat_trivia();
+
+            p.expect(|token| token == Token::SquareBracketRight)?;
+            Some(())
+        })
+    }
+
+    fn parse_struct(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        self.parse_node_at(checkpoint, SyntaxKind::StructDef, |p| {
             p.expect(|token| token == Token::KwStruct)?;
             p.eat_trivia();
 
@@ -206,8 +274,8 @@ impl<'source> Parser<'source> {
         })
     }
 
-    fn parse_enum(&mut self) -> Option<()> {
-        self.parse_node(SyntaxKind::EnumDef, |p| {
+    fn parse_enum(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        self.parse_node_at(checkpoint, SyntaxKind::EnumDef, |p| {
             p.expect(|token| token == Token::KwEnum)?;
             p.eat_trivia();
 
@@ -223,8 +291,8 @@ impl<'source> Parser<'source> {
         })
     }
 
-    fn parse_flags(&mut self) -> Option<()> {
-        self.parse_node(SyntaxKind::FlagsDef, |p| {
+    fn parse_flags(&mut self, checkpoint: Checkpoint) -> Option<()> {
+        self.parse_node_at(checkpoint, SyntaxKind::FlagsDef, |p| {
             p.expect(|token| token == Token::KwFlags)?;
             p.eat_trivia();
 
@@ -303,7 +371,7 @@ impl<'source> Parser<'source> {
     {
         loop {
             match self.peek()? {
-                Token::Ident(_) => {
+                Token::Ident(_) | Token::Hash => {
                     item_parser(self)?;
                     self.eat_trivia();
 
@@ -331,15 +399,17 @@ impl<'source> Parser<'source> {
             p.parse_name()?;
             p.eat_trivia();
 
-            match p.peek()? {
-                Token::CurlyBracketLeft => {
-                    p.bump();
-                    p.eat_trivia();
+            if p.expect(|token| token == Token::CurlyBracketLeft).is_some() {
+                p.eat_trivia();
 
-                    p.parse_def_body(Self::parse_field)?;
-                }
-                Token::Comma => {}
-                _ => return None,
+                p.parse_def_body(Self::parse_field)?;
+                p.eat_trivia();
+            }
+
+            if p.expect(|token| token == Token::Equals).is_some() {
+                p.eat_trivia();
+
+                p.expect(|token| matches!(token, Token::Number(_)))?;
             }
 
             Some(())
@@ -348,9 +418,18 @@ impl<'source> Parser<'source> {
 
     fn parse_field(&mut self) -> Option<()> {
         self.parse_node(SyntaxKind::Field, |p| {
+            while p.peek() == Some(Token::Hash) {
+                p.parse_attribute()?;
+                p.eat_trivia();
+            }
+
             p.parse_name()?;
             p.eat_trivia();
 
+            if p.expect(|token| token == Token::Question).is_some() {
+                p.eat_trivia();
+            }
+
             p.expect(|token| token == Token::Colon)?;
             p.eat_trivia();
 
@@ -369,29 +448,44 @@ impl<'source> Parser<'source> {
 
     fn parse_type(&mut self) -> Option<()> {
         self.parse_node(SyntaxKind::Type, |p| {
+            // Anonymous tuple.
+            if p.expect(|token| token == Token::ParenLeft).is_some() {
+                return p.parse_type_args(Token::ParenRight);
+            }
+
             p.parse_name()?;
             p.eat_trivia();
 
             if p.expect(|token| token == Token::AngleBracketLeft).is_some() {
-                loop {
-                    p.eat_trivia();
-
-                    p.parse_type()?;
-                    p.eat_trivia();
-
-                    match p
-                        .expect(|token| matches!(token, Token::Comma | Token::AngleBracketRight))?
-                    {
-                        Token::Comma => {}
-                        Token::AngleBracketRight => break,
-                        _ => unreachable!(),
-                    }
-                }
+                p.parse_type_args(Token::AngleBracketRight)?;
             }
 
             Some(())
         })
     }
+
+    // Parses comma-separated types or numbers until the closing token.
+    fn parse_type_args(&mut self, close: Token<'source>) -> Option<()> {
+        loop {
+            self.eat_trivia();
+
+            if self
+                .expect(|token| matches!(token, Token::Number(_)))
+                .is_none()
+            {
+                self.parse_type()?;
+            }
+
+            self.eat_trivia();
+
+            match self.expect(|token| token == Token::Comma || token == close)? {
+                Token::Comma => {}
+                _ => break,
+            }
+        }
+
+        Some(())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -400,6 +494,7 @@ enum RecoverContext {
     EnumDef,
     FlagsDef,
     Import,
+    Attribute,
 }
 
 trait RecoverExt {
@@ -424,7 +519,10 @@ enum ImportKind {
 
 impl Token<'_> {
     fn is_item_def(&self) -> bool {
-        matches!(self, Token::KwStruct | Token::KwEnum | Token::KwFlags)
+        matches!(
+            self,
+            Token::KwStruct | Token::KwEnum | Token::KwFlags | Token::Hash
+        )
     }
 }
 
@@ -444,8 +542,16 @@ impl<'source> From<Token<'source>> for SyntaxKind {
             Token::Comma => SyntaxKind::Comma,
             Token::AngleBracketLeft => SyntaxKind::AngleBracketLeft,
             Token::AngleBracketRight => SyntaxKind::AngleBracketRight,
+            Token::Question => SyntaxKind::Question,
+            Token::ParenLeft => SyntaxKind::ParenLeft,
+            Token::ParenRight => SyntaxKind::ParenRight,
+            Token::Hash => SyntaxKind::Hash,
+            Token::SquareBracketLeft => SyntaxKind::SquareBracketLeft,
+            Token::SquareBracketRight => SyntaxKind::SquareBracketRight,
+            Token::Equals => SyntaxKind::Equals,
             Token::Ident(_) => SyntaxKind::Ident,
             Token::String(_) => SyntaxKind::String,
+            Token::Number(_) => SyntaxKind::Number,
             Token::WhiteSpace | Token::Newline => SyntaxKind::WhiteSpace,
             Token::Comment => SyntaxKind::Comment,
             Token::Error => SyntaxKind::Error,
@@ -1,10 +1,15 @@
 use std::path::Path;
 
 use phenix_codegen::Project;
-use phenix_compiler::Compiler;
+use phenix_compiler::{
+    compat::{self, Compatibility},
+    Compiler,
+};
 
 fn compile<P: AsRef<Path>>(path: P) -> Project {
-    Compiler::new().compile(path.as_ref().try_into().unwrap())
+    Compiler::new()
+        .compile(path.as_ref().try_into().unwrap())
+        .unwrap()
 }
 
 #[test]
@@ -18,3 +23,40 @@ fn imports() {
     let project = compile("tests/schemas/imports/index.phenix");
     insta::assert_yaml_snapshot!(project);
 }
+
+#[test]
+fn compat() {
+    let old = compile("tests/schemas/compat/old.phenix");
+    let new = compile("tests/schemas/compat/new.phenix");
+
+    let report = compat::check(&old, &new, "Person");
+    assert_eq!(report.compatibility(), Compatibility::Breaking);
+    insta::assert_snapshot!(report.to_string());
+
+    // Unchanged schemas and additions to the non-exhaustive types.
+    let report = compat::check(&old, &old, "Person");
+    assert!(report.changes.is_empty());
+    assert_eq!(report.compatibility(), Compatibility::Wire);
+
+    let report = compat::check(&old, &new, "Pronouns");
+    assert_eq!(report.compatibility(), Compatibility::Wire);
+
+    let report = compat::check(&old, &new, "Language");
+    assert_eq!(report.compatibility(), Compatibility::Wire);
+
+    let report = compat::check(&old, &new, "Project");
+    assert_eq!(report.compatibility(), Compatibility::Breaking);
+}
+
+#[test]
+fn errors() {
+    let error = Compiler::new()
+        .compile(
+            Path::new("tests/schemas/errors/index.phenix")
+                .try_into()
+                .unwrap(),
+        )
+        .unwrap_err();
+
+    insta::assert_snapshot!(error.to_string());
+}
@@ -0,0 +1,446 @@
+use std::fmt;
+
+use phenix_codegen::{BuiltinType, EnumType, Field, StructType, Type, TypeId, UserType};
+use phenix_runtime::{
+    base,
+    bytes::{Bytes, Mark},
+    DecodingError, InvalidPrefix, UnexpectedEof, ValueError,
+};
+
+use crate::{
+    decode::{
+        check_frame_len, decode_discriminant, decode_flags, decode_frame_header, decode_len,
+        decode_optional,
+    },
+    Schema, Value,
+};
+
+// A range of bytes of a buffer with the meaning given by the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub start: usize,
+    pub end: usize,
+    // Path from the root type, for example `Person.degree.Highest.year`.
+    pub path: String,
+    // The type of a value or the role of the bytes, for example `length`.
+    pub kind: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    // Path of the value that failed to decode.
+    pub path: String,
+    pub error: DecodingError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotations<'a> {
+    bytes: &'a [u8],
+    pub annotations: Vec<Annotation>,
+    pub failure: Option<Failure>,
+}
+
+impl Schema {
+    // Walks the buffer as if the root value was decoded and annotates each
+    // range of bytes, including length prefixes, bitmaps and discriminants.
+    // The walk stops at the first error.
+    pub fn annotate<'a>(&self, ty: &Type, bytes: &'a [u8]) -> Annotations<'a> {
+        let mut annotator = Annotator {
+            schema: self,
+            path: vec![self.type_name(ty)],
+            annotations: Vec::new(),
+        };
+
+        let input = &mut Bytes::new(bytes);
+        let mut result = annotator.visit(ty, input);
+
+        if result.is_ok() && !input.is_empty() {
+            let start = input.mark();
+            input.consume(input.len());
+            annotator.push(start, input, "trailing bytes", "");
+            result = Err(ValueError::new_at(start.to_usize()).into());
+        }
+
+        let failure = result.err().map(|error| Failure {
+            path: annotator.path.concat(),
+            error,
+        });
+
+        Annotations {
+            bytes,
+            annotations: annotator.annotations,
+            failure,
+        }
+    }
+
+    fn type_name(&self, ty: &Type) -> String {
+        match ty.id {
+            TypeId::Builtin(builtin) => format!("{:?}", builtin).to_lowercase(),
+            TypeId::User(id) => self
+                .user_type(id)
+                .map(|ty| ty.name().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+struct Annotator<'a> {
+    schema: &'a Schema,
+    // Segments of the path to the current value. On error, the path is left
+    // pointing to the value that failed.
+    path: Vec<String>,
+    annotations: Vec<Annotation>,
+}
+
+impl Annotator<'_> {
+    fn push(
+        &mut self,
+        start: Mark,
+        bytes: &Bytes<'_>,
+        kind: impl Into<String>,
+        value: impl Into<String>,
+    ) {
+        self.annotations.push(Annotation {
+            start: start.to_usize(),
+            end: bytes.mark().to_usize(),
+            path: self.path.concat(),
+            kind: kind.into(),
+            value: value.into(),
+        });
+    }
+
+    fn visit_in(
+        &mut self,
+        segment: String,
+        ty: &Type,
+        bytes: &mut Bytes<'_>,
+    ) -> Result<(), DecodingError> {
+        self.path.push(segment);
+        self.visit(ty, bytes)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn visit(&mut self, ty: &Type, bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        match ty.id {
+            TypeId::Builtin(builtin) => match builtin {
+                BuiltinType::String => {
+                    let start = bytes.mark();
+                    let len = base::uint::decode(bytes).map_err(|_| InvalidPrefix::new(bytes))?;
+                    bytes.check_string_len(len as usize)?;
+                    self.push(start, bytes, "length", len.to_string());
+
+                    // The length is not trusted, like in `decode_body_len`.
+                    if len > bytes.len() as u64 {
+                        return Err(UnexpectedEof::new(bytes).into());
+                    }
+
+                    let start = bytes.mark();
+                    let content = bytes
+                        .consume_bytes(len as usize)
+                        .ok_or_else(|| UnexpectedEof::new(bytes))?;
+                    let content = std::str::from_utf8(content).map_err(|error| {
+                        ValueError::new_at(start.to_usize() + error.valid_up_to())
+                    })?;
+
+                    self.push(start, bytes, "string", format!("{:?}", content));
+                    Ok(())
+                }
+                BuiltinType::Vector => {
+                    let start = bytes.mark();
+                    let len = decode_len(bytes)?;
+                    self.push(start, bytes, "length", len.to_string());
+                    self.visit_many(&ty.generics[0], bytes, len)
+                }
+                // The items follow until the end of the input.
+                BuiltinType::Stream => {
+                    let mut i = 0;
+
+                    while !bytes.is_empty() && !bytes.is_partial() {
+                        self.visit_in(format!("[{}]", i), &ty.generics[0], bytes)?;
+                        i += 1;
+                    }
+
+                    Ok(())
+                }
+                BuiltinType::Framed => {
+                    let mut i = 0;
+
+                    loop {
+                        let start = bytes.mark();
+
+                        let (count, len) = match decode_frame_header(bytes)? {
+                            Some(header) => header,
+                            None => {
+                                self.push(start, bytes, "frame", "end");
+                                return Ok(());
+                            }
+                        };
+
+                        self.push(
+                            start,
+                            bytes,
+                            "frame",
+                            format!("{} items in {} bytes", count, len),
+                        );
+
+                        let start = bytes.mark().to_usize();
+
+                        for _ in 0..count {
+                            self.visit_in(format!("[{}]", i), &ty.generics[0], bytes)?;
+                            i += 1;
+                        }
+
+                        check_frame_len(start, len, bytes)?;
+                    }
+                }
+                BuiltinType::Map => {
+                    let start = bytes.mark();
+                    let len = decode_len(bytes)?;
+                    self.push(start, bytes, "length", len.to_string());
+
+                    for i in 0..len {
+                        self.visit_in(format!("[{}].key", i), &ty.generics[0], bytes)?;
+                        self.visit_in(format!("[{}].value", i), &ty.generics[1], bytes)?;
+                    }
+
+                    Ok(())
+                }
+                BuiltinType::Array(len) => self.visit_many(&ty.generics[0], bytes, len),
+                BuiltinType::Tuple => {
+                    for (i, ty) in ty.generics.iter().enumerate() {
+                        self.visit_in(format!(".{}", i), ty, bytes)?;
+                    }
+
+                    Ok(())
+                }
+                builtin => {
+                    let start = bytes.mark();
+                    let value = self.schema.decode(ty, bytes)?;
+                    self.push(
+                        start,
+                        bytes,
+                        format!("{:?}", builtin).to_lowercase(),
+                        show(&value),
+                    );
+                    Ok(())
+                }
+            },
+            TypeId::User(id) => {
+                let user_type = self
+                    .schema
+                    .user_type(id)
+                    .map_err(|_| ValueError::new(bytes))?;
+                bytes.enter()?;
+                match user_type {
+                    UserType::Struct(ty) => self.visit_struct(ty, bytes)?,
+                    UserType::Enum(ty) => self.visit_enum(ty, bytes)?,
+                    UserType::Flags(flags) => {
+                        let start = bytes.mark();
+                        let value = decode_flags(flags, bytes)?;
+                        self.push(start, bytes, "flags", show(&value));
+                    }
+                }
+                bytes.leave();
+                Ok(())
+            }
+        }
+    }
+
+    fn visit_many(
+        &mut self,
+        ty: &Type,
+        bytes: &mut Bytes<'_>,
+        n: usize,
+    ) -> Result<(), DecodingError> {
+        // Bools in collections are packed into a bitmap.
+        if ty.id == TypeId::Builtin(BuiltinType::Bool) {
+            let start = bytes.mark();
+            let mut values = Vec::new();
+            base::bool::decode_many(bytes, n, &mut values)?;
+
+            let bits = values
+                .iter()
+                .map(|value| if *value { '1' } else { '0' })
+                .collect::<String>();
+            self.push(start, bytes, "bool bitmap", bits);
+            return Ok(());
+        }
+
+        for i in 0..n {
+            self.visit_in(format!("[{}]", i), ty, bytes)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_struct(
+        &mut self,
+        ty: &StructType,
+        bytes: &mut Bytes<'_>,
+    ) -> Result<(), DecodingError> {
+        let is_exhaustive = ty.is_exhaustive();
+        let body = self.visit_body_len(is_exhaustive, bytes)?;
+
+        let start = bytes.mark();
+        let optional = decode_optional(ty, is_exhaustive, bytes)?;
+
+        let optional_fields = ty
+            .fields
+            .iter()
+            .filter(|field| field.optional)
+            .enumerate()
+            .map(|(bit, field)| {
+                let is_present = base::utils::try_test_bit_at(bit, optional) == Some(true);
+                (field, is_present)
+            })
+            .collect::<Vec<_>>();
+
+        if start != bytes.mark() {
+            let value = optional_fields
+                .iter()
+                .map(|(field, is_present)| {
+                    let presence = if *is_present { "present" } else { "absent" };
+                    format!("{}: {}", field.name, presence)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.push(start, bytes, "optional bitmap", value);
+        }
+
+        for field in ty.fields.iter() {
+            let is_absent = optional_fields
+                .iter()
+                .any(|(optional, is_present)| optional.name == field.name && !is_present);
+
+            if !is_absent {
+                self.visit_field(field, bytes)?;
+            }
+        }
+
+        self.visit_body_end(body, bytes)
+    }
+
+    fn visit_enum(&mut self, ty: &EnumType, bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        let is_exhaustive = ty.is_exhaustive();
+
+        let start = bytes.mark();
+        let discriminant = decode_discriminant(ty, bytes)?;
+
+        let variant = ty
+            .variants
+            .iter()
+            .zip(ty.discriminants())
+            .find(|(_, other)| *other == discriminant)
+            .map(|(variant, _)| variant);
+
+        let value = match variant {
+            Some(variant) => format!("{} ({})", variant.name, discriminant),
+            None => format!("unknown ({})", discriminant),
+        };
+        self.push(start, bytes, "discriminant", value);
+
+        let body = self.visit_body_len(is_exhaustive, bytes)?;
+
+        let variant = match variant {
+            Some(variant) => variant,
+            None => return Err(InvalidPrefix::new_at(start.to_usize()).into()),
+        };
+
+        self.path.push(format!(".{}", variant.name));
+
+        for field in variant.fields.iter() {
+            self.visit_field(field, bytes)?;
+        }
+
+        self.path.pop();
+        self.visit_body_end(body, bytes)
+    }
+
+    fn visit_field(&mut self, field: &Field, bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        self.visit_in(format!(".{}", field.name), &field.ty, bytes)
+    }
+
+    // Non-exhaustive types are prefixed with the length of their body.
+    fn visit_body_len(
+        &mut self,
+        is_exhaustive: bool,
+        bytes: &mut Bytes<'_>,
+    ) -> Result<Option<(usize, Mark)>, DecodingError> {
+        if is_exhaustive {
+            return Ok(None);
+        }
+
+        let start = bytes.mark();
+        let len = base::utils::decode_body_len(bytes)?;
+        self.push(start, bytes, "body length", len.to_string());
+        Ok(Some((len, bytes.mark())))
+    }
+
+    fn visit_body_end(
+        &mut self,
+        body: Option<(usize, Mark)>,
+        bytes: &mut Bytes<'_>,
+    ) -> Result<(), DecodingError> {
+        if let Some((len, start)) = body {
+            let mark = bytes.mark();
+            base::utils::skip_body(bytes, start, len)?;
+
+            if mark != bytes.mark() {
+                self.push(mark, bytes, "unknown fields", "");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn show(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+// At most this many bytes are shown in hex for each annotation.
+const MAX_HEX_BYTES: usize = 8;
+
+impl fmt::Display for Annotations<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for annotation in self.annotations.iter() {
+            let bytes = &self.bytes[annotation.start..annotation.end];
+            write!(f, "{:08x}  ", annotation.start)?;
+            write_hex(f, bytes)?;
+            write!(f, "  {}  {}", annotation.path, annotation.kind)?;
+
+            if !annotation.value.is_empty() {
+                write!(f, "  {}", annotation.value)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        if let Some(failure) = &self.failure {
+            let pos = failure.error.pos();
+            let end = (pos + 1).min(self.bytes.len());
+            write!(f, "{:08x}  ", pos)?;
+            write_hex(f, &self.bytes[pos.min(end)..end])?;
+            writeln!(f, "  {}  ^ {}", failure.path, failure.error)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    let mut hex = bytes
+        .iter()
+        .take(MAX_HEX_BYTES)
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if bytes.len() > MAX_HEX_BYTES {
+        hex.push_str(" ..");
+    }
+
+    write!(f, "{:<26}", hex)
+}
@@ -0,0 +1,439 @@
+use phenix_codegen::{BuiltinType, EnumType, FlagsType, StructType, Type, TypeId, UserType};
+use phenix_runtime::{
+    base,
+    bytes::{ByteSlice, Bytes},
+    Decodable, DecodingError, InvalidPrefix, UnexpectedEof, ValueError,
+};
+
+use crate::{EnumValue, Schema, StructValue, Value};
+
+impl Schema {
+    pub fn decode(&self, ty: &Type, bytes: &mut Bytes<'_>) -> Result<Value, DecodingError> {
+        let value = match ty.id {
+            TypeId::Builtin(builtin) => match builtin {
+                BuiltinType::Bool => Value::Bool(base::bool::decode(bytes)?),
+                BuiltinType::U8 => Value::U8(u8::decode(bytes)?),
+                BuiltinType::U16 => Value::U16(u16::decode(bytes)?),
+                BuiltinType::U32 => Value::U32(u32::decode(bytes)?),
+                BuiltinType::U64 => Value::U64(u64::decode(bytes)?),
+                BuiltinType::I8 => Value::I8(i8::decode(bytes)?),
+                BuiltinType::I16 => Value::I16(i16::decode(bytes)?),
+                BuiltinType::I32 => Value::I32(i32::decode(bytes)?),
+                BuiltinType::I64 => Value::I64(i64::decode(bytes)?),
+                BuiltinType::F32 => Value::F32(f32::decode(bytes)?),
+                BuiltinType::F64 => Value::F64(f64::decode(bytes)?),
+                BuiltinType::Uint => Value::Uint(base::uint::decode(bytes)?),
+                BuiltinType::Sint => Value::Sint(base::sint::decode(bytes)?),
+                BuiltinType::Float => Value::Float(base::float::decode(bytes)?),
+                BuiltinType::String => Value::String(String::decode(bytes)?),
+                BuiltinType::Vector => {
+                    let len = decode_len(bytes)?;
+                    Value::Vector(self.decode_many(&ty.generics[0], bytes, len)?)
+                }
+                BuiltinType::Stream => {
+                    let mut items = Vec::new();
+
+                    // The items follow until the end of the input.
+                    if !bytes.is_partial() {
+                        while !bytes.is_empty() {
+                            items.push(self.decode(&ty.generics[0], bytes)?);
+                        }
+                    }
+
+                    Value::Stream(items)
+                }
+                BuiltinType::Framed => {
+                    let mut items = Vec::new();
+
+                    while let Some((count, len)) = decode_frame_header(bytes)? {
+                        let start = bytes.mark().to_usize();
+
+                        // Unlike in vectors, the items are encoded one by one.
+                        for _ in 0..count {
+                            items.push(self.decode(&ty.generics[0], bytes)?);
+                        }
+
+                        check_frame_len(start, len, bytes)?;
+                    }
+
+                    Value::Vector(items)
+                }
+                BuiltinType::Map => {
+                    let len = decode_len(bytes)?;
+                    let mut entries = Vec::new();
+
+                    for _ in 0..len {
+                        let key = self.decode(&ty.generics[0], bytes)?;
+                        let value = self.decode(&ty.generics[1], bytes)?;
+                        entries.push((key, value));
+                    }
+
+                    Value::Map(entries)
+                }
+                BuiltinType::Array(len) => {
+                    Value::Array(self.decode_many(&ty.generics[0], bytes, len)?)
+                }
+                BuiltinType::Tuple => Value::Tuple(
+                    ty.generics
+                        .iter()
+                        .map(|ty| self.decode(ty, bytes))
+                        .collect::<Result<_, _>>()?,
+                ),
+                // Not well-formed types are rejected by `Schema::check_type`.
+                _ => return Err(ValueError::new(bytes).into()),
+            },
+            TypeId::User(id) => {
+                let user_type = self.user_type(id).map_err(|_| ValueError::new(bytes))?;
+                bytes.enter()?;
+                let result = match user_type {
+                    UserType::Struct(ty) => self.decode_struct(ty, bytes),
+                    UserType::Enum(ty) => self.decode_enum(ty, bytes),
+                    UserType::Flags(ty) => decode_flags(ty, bytes),
+                };
+                bytes.leave();
+                result?
+            }
+        };
+
+        Ok(value)
+    }
+
+    // Skips the value and returns its bytes.
+    pub fn recognize<'a>(
+        &self,
+        ty: &Type,
+        bytes: &mut Bytes<'a>,
+    ) -> Result<ByteSlice<'a, Value>, DecodingError> {
+        let mark = bytes.mark();
+        self.skip(ty, bytes)?;
+        Ok(bytes.take_slice_from(mark))
+    }
+
+    fn decode_many(
+        &self,
+        ty: &Type,
+        bytes: &mut Bytes<'_>,
+        n: usize,
+    ) -> Result<Vec<Value>, DecodingError> {
+        // Bools in collections are packed into bits.
+        if ty.id == TypeId::Builtin(BuiltinType::Bool) {
+            let mut values = Vec::new();
+            base::bool::decode_many(bytes, n, &mut values)?;
+            return Ok(values.into_iter().map(Value::Bool).collect());
+        }
+
+        let mut values = Vec::new();
+
+        for _ in 0..n {
+            values.push(self.decode(ty, bytes)?);
+        }
+
+        Ok(values)
+    }
+
+    fn decode_struct(
+        &self,
+        ty: &StructType,
+        bytes: &mut Bytes<'_>,
+    ) -> Result<Value, DecodingError> {
+        let is_exhaustive = ty.is_exhaustive();
+
+        let body = if is_exhaustive {
+            None
+        } else {
+            let len = base::utils::decode_body_len(bytes)?;
+            Some((len, bytes.mark()))
+        };
+
+        let optional = decode_optional(ty, is_exhaustive, bytes)?;
+        let mut optional_bit = 0;
+
+        let mut fields = Vec::new();
+
+        for field in ty.fields.iter() {
+            if field.optional {
+                // The flags of non-exhaustive structs written by an older
+                // version may miss the bits for new fields.
+                let is_present = base::utils::try_test_bit_at(optional_bit, optional) == Some(true);
+                optional_bit += 1;
+
+                if !is_present {
+                    continue;
+                }
+            }
+
+            fields.push((field.name.clone(), self.decode(&field.ty, bytes)?));
+        }
+
+        if let Some((len, start)) = body {
+            base::utils::skip_body(bytes, start, len)?;
+        }
+
+        Ok(Value::Struct(StructValue { fields }))
+    }
+
+    fn decode_enum(&self, ty: &EnumType, bytes: &mut Bytes<'_>) -> Result<Value, DecodingError> {
+        let is_exhaustive = ty.is_exhaustive();
+        let discriminant = decode_discriminant(ty, bytes)?;
+
+        let body = if is_exhaustive {
+            None
+        } else {
+            let len = base::utils::decode_body_len(bytes)?;
+            Some((len, bytes.mark()))
+        };
+
+        let variant = ty
+            .variants
+            .iter()
+            .zip(ty.discriminants())
+            .find(|(_, other)| *other == discriminant)
+            .map(|(variant, _)| variant)
+            .ok_or_else(|| InvalidPrefix::new(bytes))?;
+
+        let fields = variant
+            .fields
+            .iter()
+            .map(|field| Ok((field.name.clone(), self.decode(&field.ty, bytes)?)))
+            .collect::<Result<_, DecodingError>>()?;
+
+        if let Some((len, start)) = body {
+            base::utils::skip_body(bytes, start, len)?;
+        }
+
+        Ok(Value::Enum(EnumValue {
+            variant: variant.name.clone(),
+            fields,
+        }))
+    }
+
+    fn skip(&self, ty: &Type, bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        match ty.id {
+            TypeId::Builtin(builtin) => match builtin {
+                BuiltinType::Bool => base::bool::recognize(bytes).map(drop),
+                BuiltinType::U8 => u8::recognize(bytes).map(drop),
+                BuiltinType::U16 => u16::recognize(bytes).map(drop),
+                BuiltinType::U32 => u32::recognize(bytes).map(drop),
+                BuiltinType::U64 => u64::recognize(bytes).map(drop),
+                BuiltinType::I8 => i8::recognize(bytes).map(drop),
+                BuiltinType::I16 => i16::recognize(bytes).map(drop),
+                BuiltinType::I32 => i32::recognize(bytes).map(drop),
+                BuiltinType::I64 => i64::recognize(bytes).map(drop),
+                BuiltinType::F32 => f32::recognize(bytes).map(drop),
+                BuiltinType::F64 => f64::recognize(bytes).map(drop),
+                BuiltinType::Uint => base::uint::recognize(bytes).map(drop),
+                BuiltinType::Sint => base::sint::recognize(bytes).map(drop),
+                BuiltinType::Float => base::float::recognize(bytes).map(drop),
+                BuiltinType::String => String::recognize(bytes).map(drop),
+                BuiltinType::Vector => {
+                    let len = decode_len(bytes)?;
+                    self.skip_many(&ty.generics[0], bytes, len)
+                }
+                BuiltinType::Stream => {
+                    if !bytes.is_partial() {
+                        bytes.consume(bytes.len());
+                    }
+
+                    Ok(())
+                }
+                BuiltinType::Framed => {
+                    while let Some((_, len)) = decode_frame_header(bytes)? {
+                        bytes
+                            .consume_bytes(len)
+                            .ok_or_else(|| UnexpectedEof::new(bytes))?;
+                    }
+
+                    Ok(())
+                }
+                BuiltinType::Map => {
+                    let len = decode_len(bytes)?;
+
+                    for _ in 0..len {
+                        self.skip(&ty.generics[0], bytes)?;
+                        self.skip(&ty.generics[1], bytes)?;
+                    }
+
+                    Ok(())
+                }
+                BuiltinType::Array(len) => self.skip_many(&ty.generics[0], bytes, len),
+                BuiltinType::Tuple => ty.generics.iter().try_for_each(|ty| self.skip(ty, bytes)),
+                _ => Err(ValueError::new(bytes).into()),
+            },
+            TypeId::User(id) => {
+                let user_type = self.user_type(id).map_err(|_| ValueError::new(bytes))?;
+                bytes.enter()?;
+                let result = match user_type {
+                    UserType::Struct(ty) => self.skip_struct(ty, bytes),
+                    UserType::Enum(ty) => self.skip_enum(ty, bytes),
+                    UserType::Flags(ty) => skip_flags(ty, bytes).map(drop),
+                };
+                bytes.leave();
+                result
+            }
+        }
+    }
+
+    fn skip_many(&self, ty: &Type, bytes: &mut Bytes<'_>, n: usize) -> Result<(), DecodingError> {
+        if ty.id == TypeId::Builtin(BuiltinType::Bool) {
+            return base::bool::recognize_many(bytes, n).map(drop);
+        }
+
+        for _ in 0..n {
+            self.skip(ty, bytes)?;
+        }
+
+        Ok(())
+    }
+
+    fn skip_struct(&self, ty: &StructType, bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        let is_exhaustive = ty.is_exhaustive();
+
+        // The fields are not recognized individually, because the body may
+        // contain fields unknown to this version.
+        if !is_exhaustive {
+            return base::utils::recognize_body(bytes);
+        }
+
+        let optional = decode_optional(ty, is_exhaustive, bytes)?;
+        let mut optional_bit = 0;
+
+        for field in ty.fields.iter() {
+            if field.optional {
+                let is_present = base::utils::test_bit_at(optional_bit, optional);
+                optional_bit += 1;
+
+                if !is_present {
+                    continue;
+                }
+            }
+
+            self.skip(&field.ty, bytes)?;
+        }
+
+        Ok(())
+    }
+
+    fn skip_enum(&self, ty: &EnumType, bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        let is_exhaustive = ty.is_exhaustive();
+
+        // Unknown variants are skipped the same way as the known ones.
+        if !is_exhaustive {
+            base::utils::decode_discriminant_relaxed(bytes)?;
+            return base::utils::recognize_body(bytes);
+        }
+
+        let discriminant = decode_discriminant(ty, bytes)?;
+
+        let variant = ty
+            .variants
+            .iter()
+            .zip(ty.discriminants())
+            .find(|(_, other)| *other == discriminant)
+            .map(|(variant, _)| variant)
+            .ok_or_else(|| InvalidPrefix::new(bytes))?;
+
+        variant
+            .fields
+            .iter()
+            .try_for_each(|field| self.skip(&field.ty, bytes))
+    }
+}
+
+pub(crate) fn decode_len(bytes: &mut Bytes<'_>) -> Result<usize, DecodingError> {
+    let len = base::uint::decode(bytes).map_err(|_| InvalidPrefix::new(bytes))? as usize;
+    bytes.check_collection_len(len)?;
+    Ok(len)
+}
+
+// Returns the number of items and their length in bytes, or nothing for the
+// frame that ends the stream.
+pub(crate) fn decode_frame_header(
+    bytes: &mut Bytes<'_>,
+) -> Result<Option<(usize, usize)>, DecodingError> {
+    let count = decode_len(bytes)?;
+
+    if count == 0 {
+        return Ok(None);
+    }
+
+    let len = base::uint::decode(bytes).map_err(|_| InvalidPrefix::new(bytes))? as usize;
+    Ok(Some((count, len)))
+}
+
+pub(crate) fn check_frame_len(
+    start: usize,
+    len: usize,
+    bytes: &Bytes<'_>,
+) -> Result<(), DecodingError> {
+    if bytes.mark().to_usize() - start != len {
+        return Err(ValueError::new_at(start).into());
+    }
+
+    Ok(())
+}
+
+pub(crate) fn decode_optional<'a>(
+    ty: &StructType,
+    is_exhaustive: bool,
+    bytes: &mut Bytes<'a>,
+) -> Result<&'a [u8], DecodingError> {
+    let count = ty.fields.iter().filter(|field| field.optional).count();
+
+    if !is_exhaustive {
+        base::utils::decode_optional_relaxed(bytes)
+    } else if count > 0 {
+        base::bool::recognize_many(bytes, count).map(|slice| slice.as_bytes())
+    } else {
+        Ok(&[])
+    }
+}
+
+pub(crate) fn decode_discriminant(
+    ty: &EnumType,
+    bytes: &mut Bytes<'_>,
+) -> Result<usize, DecodingError> {
+    if ty.is_relaxed_discriminant() {
+        base::utils::decode_discriminant_relaxed(bytes)
+    } else {
+        base::utils::decode_discriminant(bytes)
+    }
+}
+
+pub(crate) fn decode_flags(ty: &FlagsType, bytes: &mut Bytes<'_>) -> Result<Value, DecodingError> {
+    let flags = skip_flags(ty, bytes)?;
+
+    // Unknown flags from a newer version of non-exhaustive types are lost.
+    let names = ty
+        .flags
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| base::utils::try_test_bit_at(*i, flags) == Some(true))
+        .map(|(_, name)| name.clone())
+        .collect();
+
+    Ok(Value::Flags(names))
+}
+
+fn skip_flags<'a>(ty: &FlagsType, bytes: &mut Bytes<'a>) -> Result<&'a [u8], DecodingError> {
+    let is_exhaustive = ty.is_exhaustive();
+
+    let n_bytes = if is_exhaustive {
+        ty.byte_size()
+    } else {
+        base::utils::decode_discriminant_relaxed(bytes)?
+    };
+
+    bytes.allocate(n_bytes)?;
+
+    if bytes.is_strict()
+        && is_exhaustive
+        && bytes.len() >= n_bytes
+        && base::bool::has_padding(&bytes[..n_bytes], ty.flags.len())
+    {
+        return Err(ValueError::new(bytes).into());
+    }
+
+    bytes
+        .consume_bytes(n_bytes)
+        .ok_or_else(|| UnexpectedEof::new(bytes).into())
+}
@@ -0,0 +1,300 @@
+use std::{error::Error, fmt};
+
+use phenix_codegen::{BuiltinType, EnumType, Field, FlagsType, StructType, Type, TypeId, UserType};
+use phenix_runtime::{base, io, Encodable};
+
+use crate::{Schema, SchemaError, Value};
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Io(io::Error),
+    // The value does not have the shape of the schema type with given name.
+    TypeMismatch(String),
+    MissingField(String),
+    UnknownField(String),
+    UnknownVariant(String),
+    UnknownFlag(String),
+    Schema(SchemaError),
+}
+
+impl From<io::Error> for EncodingError {
+    fn from(error: io::Error) -> Self {
+        EncodingError::Io(error)
+    }
+}
+
+impl From<SchemaError> for EncodingError {
+    fn from(error: SchemaError) -> Self {
+        EncodingError::Schema(error)
+    }
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::Io(error) => write!(f, "{}", error),
+            EncodingError::TypeMismatch(ty) => write!(f, "value is not of type {}", ty),
+            EncodingError::MissingField(name) => write!(f, "missing field {}", name),
+            EncodingError::UnknownField(name) => write!(f, "unknown field {}", name),
+            EncodingError::UnknownVariant(name) => write!(f, "unknown variant {}", name),
+            EncodingError::UnknownFlag(name) => write!(f, "unknown flag {}", name),
+            EncodingError::Schema(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for EncodingError {}
+
+impl Schema {
+    pub fn encode<W: io::Write>(
+        &self,
+        ty: &Type,
+        value: &Value,
+        writer: &mut W,
+    ) -> Result<(), EncodingError> {
+        match (ty.id, value) {
+            (TypeId::Builtin(builtin), value) => match (builtin, value) {
+                (BuiltinType::Bool, Value::Bool(value)) => base::bool::encode(*value, writer)?,
+                (BuiltinType::U8, Value::U8(value)) => value.encode(writer)?,
+                (BuiltinType::U16, Value::U16(value)) => value.encode(writer)?,
+                (BuiltinType::U32, Value::U32(value)) => value.encode(writer)?,
+                (BuiltinType::U64, Value::U64(value)) => value.encode(writer)?,
+                (BuiltinType::I8, Value::I8(value)) => value.encode(writer)?,
+                (BuiltinType::I16, Value::I16(value)) => value.encode(writer)?,
+                (BuiltinType::I32, Value::I32(value)) => value.encode(writer)?,
+                (BuiltinType::I64, Value::I64(value)) => value.encode(writer)?,
+                (BuiltinType::F32, Value::F32(value)) => value.encode(writer)?,
+                (BuiltinType::F64, Value::F64(value)) => value.encode(writer)?,
+                (BuiltinType::Uint, Value::Uint(value)) => base::uint::encode(*value, writer)?,
+                (BuiltinType::Sint, Value::Sint(value)) => base::sint::encode(*value, writer)?,
+                (BuiltinType::Float, Value::Float(value)) => base::float::encode(*value, writer)?,
+                (BuiltinType::String, Value::String(value)) => value.encode(writer)?,
+                (BuiltinType::Vector, Value::Vector(values)) => {
+                    base::uint::encode(values.len() as u64, writer)?;
+                    self.encode_many(&ty.generics[0], values, writer)?;
+                }
+                // The items are expected to be at the end of the input.
+                (BuiltinType::Stream, Value::Stream(values)) => {
+                    for value in values.iter() {
+                        self.encode(&ty.generics[0], value, writer)?;
+                    }
+                }
+                // All items are written in a single frame.
+                (BuiltinType::Framed, Value::Vector(values)) => {
+                    if !values.is_empty() {
+                        let mut items = Vec::new();
+
+                        for value in values.iter() {
+                            self.encode(&ty.generics[0], value, &mut items)?;
+                        }
+
+                        base::uint::encode(values.len() as u64, writer)?;
+                        base::utils::encode_body(&items, writer)?;
+                    }
+
+                    base::uint::encode(0, writer)?;
+                }
+                // Entries are sorted by the encoded keys like in the runtime.
+                (BuiltinType::Map, Value::Map(entries)) => {
+                    let mut encoded = Vec::with_capacity(entries.len());
+
+                    for (key, value) in entries.iter() {
+                        let mut key_bytes = Vec::new();
+                        self.encode(&ty.generics[0], key, &mut key_bytes)?;
+                        encoded.push((key_bytes, value));
+                    }
+
+                    encoded.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+                    base::uint::encode(entries.len() as u64, writer)?;
+
+                    for (key, value) in encoded {
+                        writer.write_all(&key)?;
+                        self.encode(&ty.generics[1], value, writer)?;
+                    }
+                }
+                (BuiltinType::Array(len), Value::Array(values)) if values.len() == len => {
+                    self.encode_many(&ty.generics[0], values, writer)?;
+                }
+                (BuiltinType::Tuple, Value::Tuple(values)) if values.len() == ty.generics.len() => {
+                    for (ty, value) in ty.generics.iter().zip(values.iter()) {
+                        self.encode(ty, value, writer)?;
+                    }
+                }
+                (builtin, _) => {
+                    return Err(EncodingError::TypeMismatch(format!("{:?}", builtin)));
+                }
+            },
+            (TypeId::User(id), value) => match (self.user_type(id)?, value) {
+                (UserType::Struct(ty), Value::Struct(value)) => {
+                    self.encode_struct(ty, &value.fields, writer)?
+                }
+                (UserType::Enum(ty), Value::Enum(value)) => {
+                    self.encode_enum(ty, &value.variant, &value.fields, writer)?
+                }
+                (UserType::Flags(ty), Value::Flags(names)) => encode_flags(ty, names, writer)?,
+                (ty, _) => return Err(EncodingError::TypeMismatch(ty.name().to_string())),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn encode_many<W: io::Write>(
+        &self,
+        ty: &Type,
+        values: &[Value],
+        writer: &mut W,
+    ) -> Result<(), EncodingError> {
+        // Bools in collections are packed into bits.
+        if ty.id == TypeId::Builtin(BuiltinType::Bool) {
+            let values = values
+                .iter()
+                .map(|value| match value {
+                    Value::Bool(value) => Ok(*value),
+                    _ => Err(EncodingError::TypeMismatch(format!(
+                        "{:?}",
+                        BuiltinType::Bool
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            return Ok(base::bool::encode_many(&values, writer)?);
+        }
+
+        for value in values.iter() {
+            self.encode(ty, value, writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_struct<W: io::Write>(
+        &self,
+        ty: &StructType,
+        fields: &[(String, Value)],
+        writer: &mut W,
+    ) -> Result<(), EncodingError> {
+        check_fields(ty.fields.iter().map(|field| &field.name), fields)?;
+
+        let optional = ty
+            .fields
+            .iter()
+            .filter(|field| field.optional)
+            .map(|field| find_field(fields, &field.name).is_some())
+            .collect::<Vec<_>>();
+
+        if ty.is_exhaustive() {
+            if !optional.is_empty() {
+                base::bool::encode_many(&optional, writer)?;
+            }
+
+            self.encode_fields(&ty.fields, fields, writer)
+        } else {
+            let mut body = Vec::new();
+            base::utils::encode_optional_relaxed(&optional, &mut body)?;
+            self.encode_fields(&ty.fields, fields, &mut body)?;
+
+            Ok(base::utils::encode_body(&body, writer)?)
+        }
+    }
+
+    fn encode_enum<W: io::Write>(
+        &self,
+        ty: &EnumType,
+        variant: &str,
+        fields: &[(String, Value)],
+        writer: &mut W,
+    ) -> Result<(), EncodingError> {
+        let (variant, discriminant) = ty
+            .variants
+            .iter()
+            .zip(ty.discriminants())
+            .find(|(other, _)| other.name == variant)
+            .ok_or_else(|| EncodingError::UnknownVariant(variant.to_string()))?;
+
+        check_fields(variant.fields.iter().map(|field| &field.name), fields)?;
+
+        let is_exhaustive = ty.is_exhaustive();
+
+        if ty.is_relaxed_discriminant() {
+            base::utils::encode_discriminant_relaxed(discriminant, writer)?;
+        } else {
+            base::utils::encode_discriminant(discriminant, writer)?;
+        }
+
+        if is_exhaustive {
+            self.encode_fields(&variant.fields, fields, writer)
+        } else {
+            // The body is encoded into a buffer first so that it can be
+            // prefixed with its length.
+            let mut body = Vec::new();
+            self.encode_fields(&variant.fields, fields, &mut body)?;
+
+            Ok(base::utils::encode_body(&body, writer)?)
+        }
+    }
+
+    // Absent optional fields are skipped, their presence is encoded in the
+    // flags.
+    fn encode_fields<W: io::Write>(
+        &self,
+        schema: &[Field],
+        fields: &[(String, Value)],
+        writer: &mut W,
+    ) -> Result<(), EncodingError> {
+        for field in schema.iter() {
+            match find_field(fields, &field.name) {
+                Some(value) => self.encode(&field.ty, value, writer)?,
+                None if field.optional => {}
+                None => return Err(EncodingError::MissingField(field.name.clone())),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_flags<W: io::Write>(
+    ty: &FlagsType,
+    names: &[String],
+    writer: &mut W,
+) -> Result<(), EncodingError> {
+    let mut flags = vec![0u8; ty.byte_size()];
+
+    for name in names.iter() {
+        let bit = ty
+            .flags
+            .iter()
+            .position(|flag| flag == name)
+            .ok_or_else(|| EncodingError::UnknownFlag(name.clone()))?;
+
+        base::utils::set_bit_at(bit, &mut flags);
+    }
+
+    if !ty.is_exhaustive() {
+        base::utils::encode_discriminant_relaxed(flags.len(), writer)?;
+    }
+
+    Ok(writer.write_all(&flags)?)
+}
+
+fn find_field<'a>(fields: &'a [(String, Value)], name: &str) -> Option<&'a Value> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| value)
+}
+
+// Fields not in the schema would be silently dropped otherwise.
+fn check_fields<'a, I>(names: I, fields: &[(String, Value)]) -> Result<(), EncodingError>
+where
+    I: Iterator<Item = &'a String> + Clone,
+{
+    match fields
+        .iter()
+        .find(|(field, _)| !names.clone().any(|name| name == field))
+    {
+        Some((field, _)) => Err(EncodingError::UnknownField(field.clone())),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,510 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead},
+};
+
+use phenix_codegen::{BuiltinType, EnumType, FlagsType, StructType, Type, TypeId, UserType};
+use phenix_runtime::{bytes::Bytes, DecodingError, ValueError};
+use serde::{
+    ser::{SerializeMap, SerializeSeq},
+    Serialize, Serializer,
+};
+use serde_json::{Map, Value as Json};
+
+use crate::{EnumValue, Schema, SchemaError, StructValue, Value};
+
+#[derive(Debug)]
+pub enum JsonError {
+    Decoding(DecodingError),
+    Json(serde_json::Error),
+    Io(io::Error),
+    Schema(SchemaError),
+    // The JSON does not match the schema type at given path.
+    Invalid { path: String, expected: String },
+}
+
+impl JsonError {
+    fn invalid(expected: impl Into<String>) -> Self {
+        JsonError::Invalid {
+            path: String::new(),
+            expected: expected.into(),
+        }
+    }
+
+    fn in_field(self, name: &str) -> Self {
+        self.prepend_path(&format!(".{}", name))
+    }
+
+    fn in_index(self, index: usize) -> Self {
+        self.prepend_path(&format!("[{}]", index))
+    }
+
+    fn prepend_path(self, segment: &str) -> Self {
+        match self {
+            JsonError::Invalid { path, expected } => JsonError::Invalid {
+                path: format!("{}{}", segment, path),
+                expected,
+            },
+            error => error,
+        }
+    }
+}
+
+impl From<DecodingError> for JsonError {
+    fn from(error: DecodingError) -> Self {
+        JsonError::Decoding(error)
+    }
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError::Json(error)
+    }
+}
+
+impl From<io::Error> for JsonError {
+    fn from(error: io::Error) -> Self {
+        JsonError::Io(error)
+    }
+}
+
+impl From<SchemaError> for JsonError {
+    fn from(error: SchemaError) -> Self {
+        JsonError::Schema(error)
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Decoding(error) => write!(f, "{}", error),
+            JsonError::Json(error) => write!(f, "{}", error),
+            JsonError::Io(error) => write!(f, "{}", error),
+            JsonError::Schema(error) => write!(f, "{}", error),
+            JsonError::Invalid { path, expected } if path.is_empty() => {
+                write!(f, "expected {}", expected)
+            }
+            JsonError::Invalid { path, expected } => {
+                write!(f, "expected {} at {}", expected, path)
+            }
+        }
+    }
+}
+
+impl Error for JsonError {}
+
+// Flags are lists of the set flag names and enums are objects with a single
+// entry from the variant name to the object of its fields. Maps with string
+// keys are objects, other maps are lists of key-value pairs.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Bool(value) => serializer.serialize_bool(*value),
+            Value::U8(value) => serializer.serialize_u8(*value),
+            Value::U16(value) => serializer.serialize_u16(*value),
+            Value::U32(value) => serializer.serialize_u32(*value),
+            Value::U64(value) | Value::Uint(value) => serializer.serialize_u64(*value),
+            Value::I8(value) => serializer.serialize_i8(*value),
+            Value::I16(value) => serializer.serialize_i16(*value),
+            Value::I32(value) => serializer.serialize_i32(*value),
+            Value::I64(value) | Value::Sint(value) => serializer.serialize_i64(*value),
+            Value::F32(value) => serializer.serialize_f32(*value),
+            Value::F64(value) | Value::Float(value) => serializer.serialize_f64(*value),
+            Value::String(value) => serializer.serialize_str(value),
+            Value::Vector(values)
+            | Value::Stream(values)
+            | Value::Array(values)
+            | Value::Tuple(values) => serializer.collect_seq(values),
+            Value::Map(entries) => {
+                if entries
+                    .iter()
+                    .all(|(key, _)| matches!(key, Value::String(_)))
+                {
+                    serializer.collect_map(entries.iter().map(|(key, value)| (key, value)))
+                } else {
+                    serializer.collect_seq(entries.iter().map(|(key, value)| (key, value)))
+                }
+            }
+            Value::Struct(value) => serialize_fields(&value.fields, serializer),
+            Value::Enum(value) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(&value.variant, &Fields(&value.fields))?;
+                map.end()
+            }
+            Value::Flags(names) => {
+                let mut seq = serializer.serialize_seq(Some(names.len()))?;
+
+                for name in names.iter() {
+                    seq.serialize_element(name)?;
+                }
+
+                seq.end()
+            }
+        }
+    }
+}
+
+struct Fields<'a>(&'a [(String, Value)]);
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_fields(self.0, serializer)
+    }
+}
+
+fn serialize_fields<S: Serializer>(
+    fields: &[(String, Value)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(fields.iter().map(|(name, value)| (name, value)))
+}
+
+impl Schema {
+    // Decodes the whole buffer into JSON.
+    pub fn to_json(&self, ty: &Type, bytes: &[u8]) -> Result<Json, JsonError> {
+        let value = self.decode_all(ty, bytes)?;
+        Ok(serde_json::to_value(&value)?)
+    }
+
+    pub fn parse_json(&self, ty: &Type, json: &Json) -> Result<Value, JsonError> {
+        let value = match ty.id {
+            TypeId::Builtin(builtin) => match builtin {
+                BuiltinType::Bool => Value::Bool(json.as_bool().ok_or_else(|| invalid(builtin))?),
+                BuiltinType::U8 => Value::U8(from_u64(json, builtin)?),
+                BuiltinType::U16 => Value::U16(from_u64(json, builtin)?),
+                BuiltinType::U32 => Value::U32(from_u64(json, builtin)?),
+                BuiltinType::U64 => Value::U64(from_u64(json, builtin)?),
+                BuiltinType::Uint => Value::Uint(from_u64(json, builtin)?),
+                BuiltinType::I8 => Value::I8(from_i64(json, builtin)?),
+                BuiltinType::I16 => Value::I16(from_i64(json, builtin)?),
+                BuiltinType::I32 => Value::I32(from_i64(json, builtin)?),
+                BuiltinType::I64 => Value::I64(from_i64(json, builtin)?),
+                BuiltinType::Sint => Value::Sint(from_i64(json, builtin)?),
+                BuiltinType::F32 => {
+                    Value::F32(json.as_f64().ok_or_else(|| invalid(builtin))? as f32)
+                }
+                BuiltinType::F64 => Value::F64(json.as_f64().ok_or_else(|| invalid(builtin))?),
+                BuiltinType::Float => Value::Float(json.as_f64().ok_or_else(|| invalid(builtin))?),
+                BuiltinType::String => {
+                    Value::String(json.as_str().ok_or_else(|| invalid(builtin))?.to_string())
+                }
+                BuiltinType::Vector => Value::Vector(self.parse_json_seq(&ty.generics[0], json)?),
+                BuiltinType::Stream => Value::Stream(self.parse_json_seq(&ty.generics[0], json)?),
+                BuiltinType::Framed => Value::Vector(self.parse_json_seq(&ty.generics[0], json)?),
+                BuiltinType::Array(len) => {
+                    let values = self.parse_json_seq(&ty.generics[0], json)?;
+
+                    if values.len() != len {
+                        return Err(JsonError::invalid(format!("array of length {}", len)));
+                    }
+
+                    Value::Array(values)
+                }
+                BuiltinType::Tuple => {
+                    let items = json
+                        .as_array()
+                        .filter(|items| items.len() == ty.generics.len())
+                        .ok_or_else(|| {
+                            JsonError::invalid(format!("tuple of length {}", ty.generics.len()))
+                        })?;
+
+                    Value::Tuple(
+                        ty.generics
+                            .iter()
+                            .zip(items.iter())
+                            .enumerate()
+                            .map(|(i, (ty, json))| {
+                                self.parse_json(ty, json).map_err(|error| error.in_index(i))
+                            })
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
+                BuiltinType::Map => Value::Map(self.parse_json_map(ty, json)?),
+                builtin => return Err(SchemaError::UnsupportedType(builtin).into()),
+            },
+            TypeId::User(id) => match self.user_type(id)? {
+                UserType::Struct(ty) => self.parse_json_struct(ty, json)?,
+                UserType::Enum(ty) => self.parse_json_enum(ty, json)?,
+                UserType::Flags(ty) => parse_json_flags(ty, json)?,
+            },
+        };
+
+        Ok(value)
+    }
+
+    // Writes the root value on the first line and the items of its stream, if
+    // it ends with one, on the following lines.
+    pub fn to_ndjson<W: io::Write>(
+        &self,
+        ty: &Type,
+        bytes: &[u8],
+        mut writer: W,
+    ) -> Result<(), JsonError> {
+        let mut value = self.decode_all(ty, bytes)?;
+        let items = self.take_stream(ty, &mut value);
+
+        serde_json::to_writer(&mut writer, &value)?;
+        writer.write_all(b"\n")?;
+
+        for item in items.iter() {
+            serde_json::to_writer(&mut writer, item)?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn parse_ndjson<R: BufRead>(&self, ty: &Type, reader: R) -> Result<Value, JsonError> {
+        let mut lines = reader.lines();
+
+        let first = match lines.next() {
+            Some(line) => line?,
+            None => return Err(JsonError::invalid("root value")),
+        };
+
+        let mut value = self.parse_json(ty, &serde_json::from_str(&first)?)?;
+
+        let stream = self
+            .stream_field(ty)
+            .map(|(name, item_ty)| (name.to_string(), item_ty.clone()));
+
+        if let (Some((name, item_ty)), Value::Struct(value)) = (stream, &mut value) {
+            let mut items = Vec::new();
+
+            for (i, line) in lines.enumerate() {
+                let line = line?;
+
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let item = self
+                    .parse_json(&item_ty, &serde_json::from_str(&line)?)
+                    .map_err(|error| error.in_index(i).in_field(&name))?;
+                items.push(item);
+            }
+
+            value.fields.retain(|(field, _)| *field != name);
+            value.fields.push((name, Value::Stream(items)));
+        }
+
+        Ok(value)
+    }
+
+    fn decode_all(&self, ty: &Type, bytes: &[u8]) -> Result<Value, DecodingError> {
+        let mut bytes = Bytes::new(bytes);
+        let value = self.decode(ty, &mut bytes)?;
+
+        if bytes.is_empty() {
+            Ok(value)
+        } else {
+            Err(ValueError::new(&bytes).into())
+        }
+    }
+
+    // Name and item type of the stream that a struct ends with.
+    fn stream_field(&self, ty: &Type) -> Option<(&str, &Type)> {
+        match ty.id {
+            TypeId::User(id) => match self.user_type(id) {
+                Ok(UserType::Struct(ty)) => ty
+                    .fields
+                    .last()
+                    .filter(|field| field.ty.id == TypeId::Builtin(BuiltinType::Stream))
+                    .map(|field| (field.name.as_str(), &field.ty.generics[0])),
+                _ => None,
+            },
+            TypeId::Builtin(_) => None,
+        }
+    }
+
+    fn take_stream(&self, ty: &Type, value: &mut Value) -> Vec<Value> {
+        let name = match self.stream_field(ty) {
+            Some((name, _)) => name,
+            None => return Vec::new(),
+        };
+
+        match value {
+            Value::Struct(value) => {
+                match value.fields.iter().position(|(field, _)| field == name) {
+                    Some(i) => match value.fields.remove(i) {
+                        (_, Value::Stream(items)) => items,
+                        _ => Vec::new(),
+                    },
+                    None => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn parse_json_seq(&self, ty: &Type, json: &Json) -> Result<Vec<Value>, JsonError> {
+        json.as_array()
+            .ok_or_else(|| JsonError::invalid("array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, json)| self.parse_json(ty, json).map_err(|error| error.in_index(i)))
+            .collect()
+    }
+
+    fn parse_json_map(&self, ty: &Type, json: &Json) -> Result<Vec<(Value, Value)>, JsonError> {
+        let (key_ty, value_ty) = (&ty.generics[0], &ty.generics[1]);
+
+        match json {
+            Json::Object(entries) if key_ty.id == TypeId::Builtin(BuiltinType::String) => entries
+                .iter()
+                .map(|(key, json)| {
+                    let value = self
+                        .parse_json(value_ty, json)
+                        .map_err(|error| error.in_field(key))?;
+                    Ok((Value::String(key.clone()), value))
+                })
+                .collect(),
+            Json::Array(entries) => entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    let (key, value) = match entry.as_array().map(Vec::as_slice) {
+                        Some([key, value]) => (key, value),
+                        _ => return Err(JsonError::invalid("key-value pair").in_index(i)),
+                    };
+
+                    let key = self
+                        .parse_json(key_ty, key)
+                        .map_err(|error| error.in_index(i))?;
+                    let value = self
+                        .parse_json(value_ty, value)
+                        .map_err(|error| error.in_index(i))?;
+                    Ok((key, value))
+                })
+                .collect(),
+            _ => Err(JsonError::invalid("map")),
+        }
+    }
+
+    fn parse_json_struct(&self, ty: &StructType, json: &Json) -> Result<Value, JsonError> {
+        let object = json
+            .as_object()
+            .ok_or_else(|| JsonError::invalid(format!("object of {}", ty.name)))?;
+
+        let mut fields = Vec::new();
+
+        for field in ty.fields.iter() {
+            match object.get(&field.name) {
+                Some(Json::Null) | None if field.optional => {}
+                // The stream items may follow separately.
+                None if field.ty.id == TypeId::Builtin(BuiltinType::Stream) => {
+                    fields.push((field.name.clone(), Value::Stream(Vec::new())));
+                }
+                Some(json) => {
+                    let value = self
+                        .parse_json(&field.ty, json)
+                        .map_err(|error| error.in_field(&field.name))?;
+                    fields.push((field.name.clone(), value));
+                }
+                None => {
+                    return Err(JsonError::invalid("value").in_field(&field.name));
+                }
+            }
+        }
+
+        check_fields(ty.fields.iter().map(|field| &field.name), object)?;
+
+        Ok(Value::Struct(StructValue { fields }))
+    }
+
+    // Unit variants may be given also as bare strings.
+    fn parse_json_enum(&self, ty: &EnumType, json: &Json) -> Result<Value, JsonError> {
+        let expected = || JsonError::invalid(format!("variant of {}", ty.name));
+
+        let (name, fields) = match json {
+            Json::String(name) => (name, None),
+            Json::Object(object) if object.len() == 1 => {
+                let (name, fields) = object.iter().next().unwrap();
+                (name, Some(fields))
+            }
+            _ => return Err(expected()),
+        };
+
+        let variant = ty
+            .variants
+            .iter()
+            .find(|variant| variant.name == *name)
+            .ok_or_else(expected)?;
+
+        let empty = Map::new();
+        let object = match fields {
+            None => &empty,
+            Some(fields) => fields
+                .as_object()
+                .ok_or_else(|| JsonError::invalid("object").in_field(name))?,
+        };
+
+        let fields = variant
+            .fields
+            .iter()
+            .map(|field| {
+                let json = object
+                    .get(&field.name)
+                    .ok_or_else(|| JsonError::invalid("value"))
+                    .map_err(|error| error.in_field(&field.name).in_field(name))?;
+                let value = self
+                    .parse_json(&field.ty, json)
+                    .map_err(|error| error.in_field(&field.name).in_field(name))?;
+                Ok((field.name.clone(), value))
+            })
+            .collect::<Result<_, JsonError>>()?;
+
+        check_fields(variant.fields.iter().map(|field| &field.name), object)
+            .map_err(|error| error.in_field(name))?;
+
+        Ok(Value::Enum(EnumValue {
+            variant: variant.name.clone(),
+            fields,
+        }))
+    }
+}
+
+fn parse_json_flags(ty: &FlagsType, json: &Json) -> Result<Value, JsonError> {
+    json.as_array()
+        .ok_or_else(|| JsonError::invalid(format!("list of {} flags", ty.name)))?
+        .iter()
+        .enumerate()
+        .map(|(i, json)| {
+            json.as_str()
+                .filter(|name| ty.flags.iter().any(|flag| flag == name))
+                .map(str::to_string)
+                .ok_or_else(|| JsonError::invalid(format!("flag of {}", ty.name)).in_index(i))
+        })
+        .collect::<Result<_, _>>()
+        .map(Value::Flags)
+}
+
+fn check_fields<'a, I>(names: I, object: &Map<String, Json>) -> Result<(), JsonError>
+where
+    I: Iterator<Item = &'a String> + Clone,
+{
+    match object
+        .keys()
+        .find(|key| !names.clone().any(|name| name == *key))
+    {
+        Some(key) => Err(JsonError::invalid("no field").in_field(key)),
+        None => Ok(()),
+    }
+}
+
+fn invalid(builtin: BuiltinType) -> JsonError {
+    JsonError::invalid(format!("{:?}", builtin).to_lowercase())
+}
+
+fn from_u64<T: TryFrom<u64>>(json: &Json, builtin: BuiltinType) -> Result<T, JsonError> {
+    json.as_u64()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| invalid(builtin))
+}
+
+fn from_i64<T: TryFrom<i64>>(json: &Json, builtin: BuiltinType) -> Result<T, JsonError> {
+    json.as_i64()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| invalid(builtin))
+}
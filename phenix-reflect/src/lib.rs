@@ -0,0 +1,15 @@
+// Decoding and encoding of values of any type described by a compiled project,
+// without the generated code, and their transcoding to and from JSON.
+
+mod annotate;
+mod decode;
+mod encode;
+mod json;
+mod schema;
+mod value;
+
+pub use annotate::{Annotation, Annotations, Failure};
+pub use encode::EncodingError;
+pub use json::JsonError;
+pub use schema::{Schema, SchemaError};
+pub use value::{EnumValue, StructValue, Value};
@@ -0,0 +1,139 @@
+use std::{error::Error, fmt};
+
+use phenix_codegen::{BuiltinType, Field, Project, Type, TypeId, UserType, UserTypeId};
+use rustc_hash::FxHashMap;
+
+// The project is not well-formed, which can happen only if it was not produced
+// by the compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    DuplicateType(UserTypeId),
+    UndefinedType(UserTypeId),
+    // A builtin type with a wrong number of generic arguments.
+    InvalidGenerics(BuiltinType),
+    UnsupportedType(BuiltinType),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::DuplicateType(id) => write!(f, "type id {} is not unique", **id),
+            SchemaError::UndefinedType(id) => write!(f, "type id {} is not defined", **id),
+            SchemaError::InvalidGenerics(builtin) => {
+                write!(f, "invalid generic arguments of {:?}", builtin)
+            }
+            SchemaError::UnsupportedType(builtin) => write!(f, "unsupported type {:?}", builtin),
+        }
+    }
+}
+
+impl Error for SchemaError {}
+
+pub struct Schema {
+    project: Project,
+    // Module and type indices of user types.
+    types: FxHashMap<UserTypeId, (usize, usize)>,
+}
+
+impl Schema {
+    pub fn new(project: Project) -> Result<Self, SchemaError> {
+        let mut types = FxHashMap::default();
+
+        for (i, module) in project.modules.iter().enumerate() {
+            for (j, ty) in module.types.iter().enumerate() {
+                if types.insert(ty.id(), (i, j)).is_some() {
+                    return Err(SchemaError::DuplicateType(ty.id()));
+                }
+            }
+        }
+
+        let schema = Self { project, types };
+
+        // Types of all fields are checked upfront, so that only the types given
+        // by the caller can fail to be resolved when decoding or encoding.
+        for module in schema.project.modules.iter() {
+            for ty in module.types.iter() {
+                let fields: Box<dyn Iterator<Item = &Field>> = match ty {
+                    UserType::Struct(ty) => Box::new(ty.fields.iter()),
+                    UserType::Enum(ty) => {
+                        Box::new(ty.variants.iter().flat_map(|variant| variant.fields.iter()))
+                    }
+                    UserType::Flags(_) => Box::new(std::iter::empty()),
+                };
+
+                for field in fields {
+                    schema.check_type(&field.ty)?;
+                }
+            }
+        }
+
+        Ok(schema)
+    }
+
+    // Checks that the type is well-formed and all user types it refers to are
+    // defined.
+    pub fn check_type(&self, ty: &Type) -> Result<(), SchemaError> {
+        let builtin = match ty.id {
+            TypeId::Builtin(builtin) => builtin,
+            TypeId::User(id) => return self.user_type(id).map(drop),
+        };
+
+        let n_generics = match builtin {
+            BuiltinType::Bool
+            | BuiltinType::U8
+            | BuiltinType::U16
+            | BuiltinType::U32
+            | BuiltinType::U64
+            | BuiltinType::I8
+            | BuiltinType::I16
+            | BuiltinType::I32
+            | BuiltinType::I64
+            | BuiltinType::F32
+            | BuiltinType::F64
+            | BuiltinType::Uint
+            | BuiltinType::Sint
+            | BuiltinType::Float
+            | BuiltinType::String => 0,
+            BuiltinType::Vector
+            | BuiltinType::Stream
+            | BuiltinType::Framed
+            | BuiltinType::Array(_) => 1,
+            BuiltinType::Map => 2,
+            BuiltinType::Tuple => ty.generics.len(),
+            builtin => return Err(SchemaError::UnsupportedType(builtin)),
+        };
+
+        if ty.generics.len() != n_generics {
+            return Err(SchemaError::InvalidGenerics(builtin));
+        }
+
+        ty.generics.iter().try_for_each(|ty| self.check_type(ty))
+    }
+
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
+    pub fn find_type(&self, id: UserTypeId) -> Option<&UserType> {
+        self.types
+            .get(&id)
+            .map(|(i, j)| &self.project.modules[*i].types[*j])
+    }
+
+    // Finds a user type by its name, see `Project::find_by_name`.
+    pub fn find_by_name(&self, name: &str) -> Option<&UserType> {
+        self.project.find_by_name(name)
+    }
+
+    // The type of the root value of a buffer.
+    pub fn root(&self, name: &str) -> Option<Type> {
+        self.find_by_name(name).map(|ty| Type {
+            id: TypeId::User(ty.id()),
+            generics: Vec::new(),
+        })
+    }
+
+    pub(crate) fn user_type(&self, id: UserTypeId) -> Result<&UserType, SchemaError> {
+        self.find_type(id).ok_or(SchemaError::UndefinedType(id))
+    }
+}
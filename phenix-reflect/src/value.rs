@@ -0,0 +1,59 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Uint(u64),
+    Sint(i64),
+    Float(f64),
+    String(String),
+    Vector(Vec<Value>),
+    Stream(Vec<Value>),
+    // Entries in the encoded order.
+    Map(Vec<(Value, Value)>),
+    Array(Vec<Value>),
+    Tuple(Vec<Value>),
+    Struct(StructValue),
+    Enum(EnumValue),
+    // Names of the set flags.
+    Flags(Vec<String>),
+}
+
+// Absent optional fields are omitted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StructValue {
+    pub fields: Vec<(String, Value)>,
+}
+
+impl StructValue {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        find_field(&self.fields, name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumValue {
+    pub variant: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl EnumValue {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        find_field(&self.fields, name)
+    }
+}
+
+fn find_field<'a>(fields: &'a [(String, Value)], name: &str) -> Option<&'a Value> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| value)
+}
@@ -0,0 +1,688 @@
+use std::{collections::BTreeMap, path::Path};
+
+use phenix_codegen::{BuiltinType, Type, TypeId, UserType, UserTypeId};
+use phenix_compiler::Compiler;
+use phenix_reflect::{
+    EncodingError, EnumValue, JsonError, Schema, SchemaError, StructValue, Value,
+};
+use phenix_runtime::{
+    bytes::Bytes, Decodable, DecodingError, Encodable, Flags, IsFlag, Sint, Stream, Uint,
+    UnexpectedEof,
+};
+use serde_json::json;
+
+fn compile<P: AsRef<Path>>(path: P) -> phenix_codegen::Project {
+    Compiler::new()
+        .compile(path.as_ref().try_into().unwrap())
+        .unwrap()
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Person {
+    name: String,
+    age: Uint,
+    balance: Sint,
+    height: f32,
+    working_hours: Vec<bool>,
+    skills: BTreeMap<String, Uint>,
+    nickname: Option<String>,
+    location: [f64; 2],
+    team: (String, u16),
+    degree: Degree,
+    pronouns: Pronouns,
+    citizenship: Flags<Country>,
+    aliases: phenix_runtime::FramedStream<String>,
+    projects: phenix_runtime::Stream<Project>,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+struct Pronouns {
+    subject: Pronoun,
+    object: Option<Pronoun>,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[repr(u16)]
+enum Degree {
+    None,
+    Highest { name: String, year: u16 } = 300,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Project {
+    name: String,
+    score: phenix_runtime::Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, IsFlag)]
+enum Country {
+    Austria,
+    Belgium,
+    Bulgaria,
+    Croatia,
+    Cyprus,
+    CzechRepublic,
+    Denmark,
+    Estonia,
+    Finland,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+enum Pronoun {
+    He,
+    She,
+    They,
+}
+
+fn person_bytes() -> Vec<u8> {
+    let person = Person {
+        name: "Jane".to_string(),
+        age: Uint(300),
+        balance: Sint(-42),
+        height: 1.75,
+        working_hours: vec![true, false, true],
+        skills: [("rust".to_string(), Uint(5))].into_iter().collect(),
+        nickname: None,
+        location: [48.2, 16.4],
+        team: ("core".to_string(), 7),
+        degree: Degree::Highest {
+            name: "Master".to_string(),
+            year: 2020,
+        },
+        pronouns: Pronouns {
+            subject: Pronoun::She,
+            object: Some(Pronoun::They),
+        },
+        citizenship: [Country::Austria, Country::Finland].into_iter().into(),
+        aliases: vec!["JJ".to_string(), "J".to_string()].into(),
+        projects: Stream::with_offset(0),
+    };
+
+    let mut writer = person.encode_with_stream(Vec::new()).unwrap();
+
+    for (name, score) in [("phenix", 9.5), ("other", 1.0)] {
+        writer
+            .push(&Project {
+                name: name.to_string(),
+                score: score.into(),
+            })
+            .unwrap();
+    }
+
+    writer.finish().unwrap()
+}
+
+fn field(name: &str, value: Value) -> (String, Value) {
+    (name.to_string(), value)
+}
+
+fn project_value(name: &str, score: f64) -> Value {
+    Value::Struct(StructValue {
+        fields: vec![
+            field("name", Value::String(name.to_string())),
+            field("score", Value::Float(score)),
+        ],
+    })
+}
+
+fn person_value() -> Value {
+    Value::Struct(StructValue {
+        fields: vec![
+            field("name", Value::String("Jane".to_string())),
+            field("age", Value::Uint(300)),
+            field("balance", Value::Sint(-42)),
+            field("height", Value::F32(1.75)),
+            field(
+                "working_hours",
+                Value::Vector(vec![
+                    Value::Bool(true),
+                    Value::Bool(false),
+                    Value::Bool(true),
+                ]),
+            ),
+            field(
+                "skills",
+                Value::Map(vec![(Value::String("rust".to_string()), Value::Uint(5))]),
+            ),
+            field(
+                "location",
+                Value::Array(vec![Value::F64(48.2), Value::F64(16.4)]),
+            ),
+            field(
+                "team",
+                Value::Tuple(vec![Value::String("core".to_string()), Value::U16(7)]),
+            ),
+            field(
+                "degree",
+                Value::Enum(EnumValue {
+                    variant: "Highest".to_string(),
+                    fields: vec![
+                        field("name", Value::String("Master".to_string())),
+                        field("year", Value::U16(2020)),
+                    ],
+                }),
+            ),
+            field(
+                "pronouns",
+                Value::Struct(StructValue {
+                    fields: vec![
+                        field("subject", unit_variant("She")),
+                        field("object", unit_variant("They")),
+                    ],
+                }),
+            ),
+            field(
+                "citizenship",
+                Value::Flags(vec!["AUSTRIA".to_string(), "FINLAND".to_string()]),
+            ),
+            field(
+                "aliases",
+                Value::Vector(vec![
+                    Value::String("JJ".to_string()),
+                    Value::String("J".to_string()),
+                ]),
+            ),
+            field(
+                "projects",
+                Value::Stream(vec![
+                    project_value("phenix", 9.5),
+                    project_value("other", 1.0),
+                ]),
+            ),
+        ],
+    })
+}
+
+fn unit_variant(name: &str) -> Value {
+    Value::Enum(EnumValue {
+        variant: name.to_string(),
+        fields: Vec::new(),
+    })
+}
+
+#[test]
+fn decode() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let root = schema.root("Person").unwrap();
+    let bytes = person_bytes();
+
+    let input = &mut Bytes::new(&bytes);
+    let value = schema.decode(&root, input).unwrap();
+    assert_eq!(value, person_value());
+    assert!(input.is_empty());
+}
+
+#[test]
+fn recognize() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let bytes = person_bytes();
+
+    let input = &mut Bytes::new(&bytes);
+    let slice = schema
+        .recognize(&schema.root("Person").unwrap(), input)
+        .unwrap();
+    assert_eq!(slice.as_bytes(), bytes.as_slice());
+
+    // Recognize a value in the middle of the input.
+    let mut bytes = Vec::new();
+    Uint(1).encode(&mut bytes).unwrap();
+    Project {
+        name: "phenix".to_string(),
+        score: 9.5.into(),
+    }
+    .encode(&mut bytes)
+    .unwrap();
+    Uint(2).encode(&mut bytes).unwrap();
+
+    let project = schema.root("Project").unwrap();
+    let input = &mut Bytes::new(&bytes[1..]);
+    let slice = schema.recognize(&project, input).unwrap();
+    assert_eq!(slice.as_bytes(), &bytes[1..bytes.len() - 1]);
+    assert_eq!(
+        schema.decode(&project, &mut Bytes::new(slice.as_bytes())),
+        Ok(project_value("phenix", 9.5))
+    );
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+enum Narrow {
+    Foo = 200,
+    Bar,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+enum Wide {
+    Foo,
+    Bar = 256,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+enum Open {
+    Foo = 200,
+    Bar,
+}
+
+fn encoded<T: Encodable>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn discriminant_encoding() {
+    let project = compile("tests/schemas/discriminants.phenix");
+    let schema = Schema::new(project.clone()).unwrap();
+
+    // The derive macros choose the same encoding of discriminants as the
+    // schema.
+    for (name, relaxed, bytes) in [
+        ("Narrow", false, encoded(&Narrow::Bar)),
+        ("Wide", true, encoded(&Wide::Bar)),
+        ("Open", true, encoded(&Open::Bar)),
+    ] {
+        let ty = project.modules[0]
+            .types
+            .iter()
+            .find_map(|ty| match ty {
+                UserType::Enum(ty) if ty.name == name => Some(ty),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(ty.is_relaxed_discriminant(), relaxed, "{}", name);
+
+        let root = schema.root(name).unwrap();
+        let input = &mut Bytes::new(&bytes);
+        let value = schema.decode(&root, input).unwrap();
+        assert!(input.is_empty(), "{}", name);
+
+        let mut encoded = Vec::new();
+        schema.encode(&root, &value, &mut encoded).unwrap();
+        assert_eq!(encoded, bytes, "{}", name);
+    }
+}
+
+#[test]
+fn encode() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let root = schema.root("Person").unwrap();
+
+    let mut bytes = Vec::new();
+    schema.encode(&root, &person_value(), &mut bytes).unwrap();
+    assert_eq!(bytes, person_bytes());
+
+    // The generated code reads what was encoded from a value.
+    let person = Person::decode(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(person.name, "Jane");
+    assert_eq!(
+        person.aliases.span().unwrap().collect(&bytes).unwrap(),
+        vec!["JJ", "J"]
+    );
+    assert_eq!(
+        person.projects.collect(&bytes).unwrap()[0].name,
+        "phenix".to_string()
+    );
+}
+
+#[test]
+fn encode_errors() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let pronouns = schema.root("Pronouns").unwrap();
+
+    let encode = |value: Value| schema.encode(&pronouns, &value, &mut Vec::new());
+
+    assert!(matches!(
+        encode(Value::Struct(StructValue::default())),
+        Err(EncodingError::MissingField(name)) if name == "subject"
+    ));
+    assert!(matches!(
+        encode(Value::Struct(StructValue {
+            fields: vec![field("subject", unit_variant("It"))],
+        })),
+        Err(EncodingError::UnknownVariant(name)) if name == "It"
+    ));
+    assert!(matches!(
+        encode(Value::Struct(StructValue {
+            fields: vec![
+                field("subject", unit_variant("He")),
+                field("pronoun", unit_variant("He")),
+            ],
+        })),
+        Err(EncodingError::UnknownField(name)) if name == "pronoun"
+    ));
+    assert!(matches!(
+        encode(Value::Uint(1)),
+        Err(EncodingError::TypeMismatch(name)) if name == "Pronouns"
+    ));
+}
+
+#[test]
+fn find_by_name() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+
+    assert_eq!(schema.find_by_name("Country").unwrap().name(), "Country");
+    assert_eq!(
+        schema.find_by_name("nested::Country").unwrap().name(),
+        "Country"
+    );
+    assert!(schema.find_by_name("index::Country").is_none());
+    assert!(schema.find_by_name("Unknown").is_none());
+}
+
+fn person_json() -> serde_json::Value {
+    json!({
+        "name": "Jane",
+        "age": 300,
+        "balance": -42,
+        "height": 1.75,
+        "working_hours": [true, false, true],
+        "skills": { "rust": 5 },
+        "location": [48.2, 16.4],
+        "team": ["core", 7],
+        "degree": { "Highest": { "name": "Master", "year": 2020 } },
+        "pronouns": { "subject": { "She": {} }, "object": { "They": {} } },
+        "citizenship": ["AUSTRIA", "FINLAND"],
+        "aliases": ["JJ", "J"],
+        "projects": [
+            { "name": "phenix", "score": 9.5 },
+            { "name": "other", "score": 1.0 },
+        ],
+    })
+}
+
+#[test]
+fn to_json() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let root = schema.root("Person").unwrap();
+
+    assert_eq!(
+        schema.to_json(&root, &person_bytes()).unwrap(),
+        person_json()
+    );
+
+    let mut bytes = person_bytes();
+    bytes.truncate(3);
+    assert!(matches!(
+        schema.to_json(&root, &bytes),
+        Err(JsonError::Decoding(_))
+    ));
+}
+
+#[test]
+fn malformed_schema() {
+    let project = compile("tests/schemas/index.phenix");
+
+    let mut duplicate = project.clone();
+    let ty = duplicate.modules[0].types[0].clone();
+    let id = ty.id();
+    duplicate.modules[0].types.push(ty);
+    assert_eq!(
+        Schema::new(duplicate).err(),
+        Some(SchemaError::DuplicateType(id))
+    );
+
+    let mut undefined = project.clone();
+    let mut invalid = project.clone();
+
+    for (project, ty) in [
+        (&mut undefined, TypeId::User(UserTypeId(usize::MAX))),
+        (&mut invalid, TypeId::Builtin(BuiltinType::Vector)),
+    ] {
+        let field = project.modules[0]
+            .types
+            .iter_mut()
+            .find_map(|ty| match ty {
+                UserType::Struct(ty) => ty.fields.first_mut(),
+                _ => None,
+            })
+            .unwrap();
+
+        field.ty = Type {
+            id: ty,
+            generics: Vec::new(),
+        };
+    }
+
+    assert_eq!(
+        Schema::new(undefined).err(),
+        Some(SchemaError::UndefinedType(UserTypeId(usize::MAX)))
+    );
+    assert_eq!(
+        Schema::new(invalid).err(),
+        Some(SchemaError::InvalidGenerics(BuiltinType::Vector))
+    );
+
+    // Types given by the caller are not checked upfront.
+    let schema = Schema::new(project).unwrap();
+    let undefined = Type {
+        id: TypeId::User(UserTypeId(usize::MAX)),
+        generics: Vec::new(),
+    };
+    assert!(schema.decode(&undefined, &mut Bytes::new(&[0])).is_err());
+    assert!(matches!(
+        schema.parse_json(&undefined, &json!({})),
+        Err(JsonError::Schema(SchemaError::UndefinedType(_)))
+    ));
+}
+
+#[test]
+fn parse_json() {
+    // The schema can be loaded from a serialized project.
+    let project = serde_json::to_string(&compile("tests/schemas/index.phenix")).unwrap();
+    let schema = Schema::new(serde_json::from_str(&project).unwrap()).unwrap();
+    let root = schema.root("Person").unwrap();
+
+    let value = schema.parse_json(&root, &person_json()).unwrap();
+    assert_eq!(value, person_value());
+
+    let mut bytes = Vec::new();
+    schema.encode(&root, &value, &mut bytes).unwrap();
+    assert_eq!(bytes, person_bytes());
+
+    // Null optional fields and bare unit variants are accepted.
+    let pronouns = schema.root("Pronouns").unwrap();
+    assert_eq!(
+        schema
+            .parse_json(&pronouns, &json!({ "subject": "He", "object": null }))
+            .unwrap(),
+        Value::Struct(StructValue {
+            fields: vec![field("subject", unit_variant("He"))],
+        })
+    );
+}
+
+#[test]
+fn parse_json_errors() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let root = schema.root("Person").unwrap();
+
+    let error = |path: &str, value: serde_json::Value| {
+        let mut json = person_json();
+        *json.pointer_mut(path).unwrap() = value;
+        schema.parse_json(&root, &json).unwrap_err().to_string()
+    };
+
+    assert_eq!(error("/team/1", json!(70000)), "expected u16 at .team[1]");
+    assert_eq!(
+        error("/citizenship/1", json!("FRANCE")),
+        "expected flag of Country at .citizenship[1]"
+    );
+    assert_eq!(
+        error("/degree", json!({ "Lowest": {} })),
+        "expected variant of Degree at .degree"
+    );
+    assert_eq!(
+        error("/degree/Highest/year", json!("2020")),
+        "expected u16 at .degree.Highest.year"
+    );
+    assert_eq!(
+        error("/location", json!([1.0])),
+        "expected array of length 2 at .location"
+    );
+    assert_eq!(
+        error("/projects/1", json!({ "name": "other" })),
+        "expected value at .projects[1].score"
+    );
+    assert_eq!(
+        error("/pronouns", json!({ "subject": "He", "verb": "is" })),
+        "expected no field at .pronouns.verb"
+    );
+}
+
+#[test]
+fn ndjson() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let root = schema.root("Person").unwrap();
+
+    let mut output = Vec::new();
+    schema
+        .to_ndjson(&root, &person_bytes(), &mut output)
+        .unwrap();
+
+    let lines = std::str::from_utf8(&output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut person = person_json();
+    let projects = person.as_object_mut().unwrap().remove("projects").unwrap();
+
+    assert_eq!(lines[0], person);
+    assert_eq!(lines[1..], projects.as_array().unwrap()[..]);
+
+    let value = schema.parse_ndjson(&root, output.as_slice()).unwrap();
+    assert_eq!(value, person_value());
+
+    // A root without a stream is a single line.
+    let project = schema.root("Project").unwrap();
+    let mut bytes = Vec::new();
+    schema
+        .encode(&project, &project_value("phenix", 9.5), &mut bytes)
+        .unwrap();
+
+    let mut output = Vec::new();
+    schema.to_ndjson(&project, &bytes, &mut output).unwrap();
+    assert_eq!(output, b"{\"name\":\"phenix\",\"score\":9.5}\n");
+}
+
+#[test]
+fn annotate() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let bytes = person_bytes();
+
+    let annotations = schema.annotate(&schema.root("Person").unwrap(), &bytes);
+    assert_eq!(annotations.failure, None);
+
+    // The annotations cover the whole buffer.
+    let mut end = 0;
+
+    for annotation in annotations.annotations.iter() {
+        assert_eq!(annotation.start, end);
+        end = annotation.end;
+    }
+
+    assert_eq!(end, bytes.len());
+
+    let find = |path: &str, kind: &str| {
+        annotations
+            .annotations
+            .iter()
+            .find(|annotation| annotation.path == path && annotation.kind == kind)
+            .map(|annotation| {
+                (
+                    &bytes[annotation.start..annotation.end],
+                    annotation.value.as_str(),
+                )
+            })
+            .unwrap()
+    };
+
+    assert_eq!(find("Person.name", "length"), (&[4][..], "4"));
+    assert_eq!(find("Person.name", "string"), (&b"Jane"[..], "\"Jane\""));
+    assert_eq!(find("Person.age", "uint"), (&[249, 44, 1][..], "300"));
+    assert_eq!(find("Person.working_hours", "bool bitmap").1, "101");
+    assert_eq!(
+        find("Person", "optional bitmap"),
+        (&[0][..], "nickname: absent")
+    );
+    assert_eq!(
+        find("Person.degree", "discriminant"),
+        (&[249, 44, 1][..], "Highest (300)")
+    );
+    assert_eq!(find("Person.degree.Highest.year", "u16").1, "2020");
+    assert_eq!(find("Person.pronouns", "body length").1, "6");
+    assert_eq!(
+        find("Person.pronouns", "optional bitmap").1,
+        "object: present"
+    );
+    assert_eq!(
+        find("Person.citizenship", "flags").1,
+        "[\"AUSTRIA\",\"FINLAND\"]"
+    );
+    assert_eq!(
+        find("Person.aliases", "frame"),
+        (&[2, 5][..], "2 items in 5 bytes")
+    );
+    assert_eq!(find("Person.aliases[1]", "string").1, "\"J\"");
+    assert_eq!(find("Person.projects[1].score", "float").1, "1.0");
+}
+
+#[test]
+fn annotate_failure() {
+    let schema = Schema::new(compile("tests/schemas/index.phenix")).unwrap();
+    let project = schema.root("Project").unwrap();
+
+    let mut bytes = Vec::new();
+    schema
+        .encode(&project, &project_value("phenix", 9.5), &mut bytes)
+        .unwrap();
+
+    let annotations = schema.annotate(&project, &bytes[..4]);
+    assert_eq!(
+        annotations
+            .failure
+            .as_ref()
+            .map(|failure| (failure.path.as_str(), &failure.error)),
+        Some((
+            "Project.name",
+            &DecodingError::from(UnexpectedEof::new_at(1))
+        ))
+    );
+
+    assert_eq!(
+        annotations.to_string(),
+        "\
+00000000  06                          Project.name  length  6
+00000001  70                          Project.name  ^ unexpected end of input when parsing from byte 1
+"
+    );
+
+    bytes.push(0);
+    let annotations = schema.annotate(&project, &bytes);
+    assert_eq!(
+        annotations.to_string(),
+        "\
+00000000  06                          Project.name  length  6
+00000001  70 68 65 6e 69 78           Project.name  string  \"phenix\"
+00000007  f9 40 23                    Project.score  float  9.5
+0000000a  00                          Project  trailing bytes
+0000000a  00                          Project  ^ invalid value when parsing from byte 10
+"
+    );
+}
+
+#[test]
+fn annotate_huge_length() {
+    let schema = Schema::new(compile("tests/schemas/lengths.phenix")).unwrap();
+    let ty = schema.root("Msg").unwrap();
+
+    let annotations = schema.annotate(&ty, &[0xff; 9]);
+    assert!(annotations.failure.is_some());
+    assert_eq!(
+        annotations.failure.unwrap().error,
+        DecodingError::from(UnexpectedEof::new_at(9))
+    );
+}
@@ -1,24 +1,85 @@
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, ToTokens};
-use syn::{punctuated::Punctuated, DataEnum, DataStruct, Ident};
+use syn::{punctuated::Punctuated, DataEnum, DataStruct, Field, Ident, Lifetime, Type};
 
 use crate::util;
 
-pub fn decode_struct(data: &DataStruct, name: Ident, is_exhaustive: bool) -> TokenStream2 {
+// Decodes a value of given type. If the lifetime of the input is given, the
+// value may borrow from it.
+fn decode_value(ty: &Type, lifetime: Option<&Lifetime>, context: TokenStream2) -> TokenStream2 {
+    let decode = match lifetime {
+        Some(lifetime) => {
+            quote!(<#ty as ::phenix_runtime::DecodableRef<#lifetime>>::decode_ref(bytes))
+        }
+        None => quote!(<#ty as ::phenix_runtime::Decodable>::decode(bytes)),
+    };
+
+    with_context(decode, context)
+}
+
+// Attaches the context to the error of given decoding expression if enabled.
+fn with_context(decode: TokenStream2, context: TokenStream2) -> TokenStream2 {
+    if cfg!(feature = "context") {
+        quote!(#decode.map_err(|error| error #context)?)
+    } else {
+        quote!(#decode?)
+    }
+}
+
+fn field_context(i: usize, field: &Field, ty: &Type) -> TokenStream2 {
+    let field_name = match field.ident {
+        Some(ref ident) => ident.to_string(),
+        None => i.to_string(),
+    };
+    let ty_name = util::type_name(ty);
+
+    quote!(.in_field(#field_name).expected(#ty_name))
+}
+
+pub fn decode_struct(
+    data: &DataStruct,
+    name: Ident,
+    is_exhaustive: bool,
+    lifetime: Option<&Lifetime>,
+) -> TokenStream2 {
     if !is_exhaustive {
-        return util::non_exhaustive_not_supported(name.span(), util::TypeKind::Struct)
-            .into_compile_error();
+        if let Err(error) = util::check_non_exhaustive_struct(data) {
+            return error.into_compile_error();
+        }
     }
 
     let mut body = TokenStream2::new();
+    let ty_name = name.to_string();
+
+    if !is_exhaustive {
+        body.extend(decode_body_start(&ty_name));
+    }
 
     let optional_fields = util::get_optional_fields(&data.fields);
 
-    if !optional_fields.is_empty() {
+    if !is_exhaustive {
+        let decode = with_context(
+            quote!(::phenix_runtime::base::utils::decode_optional_relaxed(
+                bytes
+            )),
+            quote!(.expected("optional fields").in_type(#ty_name)),
+        );
+
+        if optional_fields.is_empty() {
+            body.extend(quote!(#decode;));
+        } else {
+            body.extend(quote!(let optional__ = #decode;));
+        }
+    } else if !optional_fields.is_empty() {
         let count = optional_fields.len();
 
+        let recognize = with_context(
+            quote!(::phenix_runtime::base::bool::recognize_many(bytes, #count)),
+            quote!(.expected("optional fields").in_type(#ty_name)),
+        );
+
         body.extend(quote! {
-            let optional__ = ::phenix_runtime::base::bool::recognize_many(bytes, #count)?;
+            let optional__ = #recognize;
             let optional__ = optional__.as_bytes();
         });
     }
@@ -34,23 +95,40 @@ pub fn decode_struct(data: &DataStruct, name: Ident, is_exhaustive: bool) -> Tok
 
         let decode_field = match util::unwrap_option_type(&field.ty) {
             Some(option_ty) => {
+                let context = field_context(i, field, option_ty);
+                let decode_value =
+                    decode_value(option_ty, lifetime, quote!(#context.in_type(#ty_name)));
+                // The flags of non-exhaustive structs written by an older
+                // version may miss the bits for new fields.
+                let test_bit = if is_exhaustive {
+                    quote!(::phenix_runtime::base::utils::test_bit_at(#optional_bit, optional__))
+                } else {
+                    quote!(::phenix_runtime::base::utils::try_test_bit_at(#optional_bit, optional__) == ::core::option::Option::Some(true))
+                };
                 let decode_field = quote! {
-                    if ::phenix_runtime::base::utils::test_bit_at(#optional_bit, optional__) {
-                        ::std::option::Option::Some(<#option_ty>::decode(bytes)?)
+                    if #test_bit {
+                        ::core::option::Option::Some(#decode_value)
                     } else {
-                        ::std::option::Option::None
+                        ::core::option::Option::None
                     }
                 };
 
                 optional_bit += 1;
                 decode_field
             }
-            None => quote!(<#field_ty>::decode(bytes)?),
+            None => {
+                let context = field_context(i, field, field_ty);
+                decode_value(field_ty, lifetime, quote!(#context.in_type(#ty_name)))
+            }
         };
 
         body.extend(quote!( let #field_name = #decode_field;));
     }
 
+    if !is_exhaustive {
+        body.extend(decode_body_end(&ty_name));
+    }
+
     let fields = data.fields.iter().enumerate().map(|(i, field)| {
         field
             .ident
@@ -59,46 +137,70 @@ pub fn decode_struct(data: &DataStruct, name: Ident, is_exhaustive: bool) -> Tok
     });
 
     body.extend(quote! {
-        ::std::result::Result::Ok(Self {
+        ::core::result::Result::Ok(Self {
             #(#fields,)*
         })
     });
     body
 }
 
-pub fn decode_enum(data: &DataEnum, name: Ident, is_exhaustive: bool) -> TokenStream2 {
-    if !is_exhaustive {
-        return util::non_exhaustive_not_supported(name.span(), util::TypeKind::Enum)
-            .into_compile_error();
-    }
-
+pub fn decode_enum(
+    data: &DataEnum,
+    name: Ident,
+    is_exhaustive: bool,
+    lifetime: Option<&Lifetime>,
+) -> TokenStream2 {
     let mut body = TokenStream2::new();
+    let ty_name = name.to_string();
+
+    let discriminants = match util::discriminants(data) {
+        Ok(discriminants) => discriminants,
+        Err(error) => return error.into_compile_error(),
+    };
+
+    let unknown_variant = match util::unknown_variant(data, is_exhaustive) {
+        Ok(unknown_variant) => unknown_variant,
+        Err(error) => return error.into_compile_error(),
+    };
+
+    let decode_discriminant = if util::is_relaxed_discriminant(&discriminants, is_exhaustive) {
+        quote!(::phenix_runtime::base::utils::decode_discriminant_relaxed(
+            bytes
+        ))
+    } else {
+        quote!(::phenix_runtime::base::utils::decode_discriminant(bytes))
+    };
 
-    body.extend(
-        quote!(let discriminant = ::phenix_runtime::base::utils::decode_discriminant(bytes)?;),
+    let decode_discriminant = with_context(
+        decode_discriminant,
+        quote!(.expected("discriminant").in_type(#ty_name)),
     );
 
-    let mut match_body = TokenStream2::new();
+    body.extend(quote!(let discriminant = #decode_discriminant;));
 
-    for (i, variant) in data.variants.iter().enumerate() {
-        if variant.discriminant.is_some() {
-            return syn::Error::new(
-                variant.ident.span(),
-                "explicit discriminants are not supported",
-            )
-            .into_compile_error();
-        }
+    if !is_exhaustive {
+        body.extend(decode_body_start(&ty_name));
+    }
 
+    let mut match_body = TokenStream2::new();
+
+    for (variant, discriminant) in util::known_variants(data).zip(discriminants.iter()) {
         let variant_name = variant.ident.clone();
+        let variant_str = variant_name.to_string();
 
         let decode_fields = variant.fields.iter().enumerate().map(|(i, field)| {
             let field_name = field
                 .ident
                 .clone()
                 .unwrap_or_else(|| util::unnamed_field_name(i));
-            let field_ty = &field.ty;
+            let context = field_context(i, field, &field.ty);
+            let decode_field = decode_value(
+                &field.ty,
+                lifetime,
+                quote!(#context.in_variant(#variant_str).in_type(#ty_name)),
+            );
 
-            quote!(let #field_name = <#field_ty>::decode(bytes)?;)
+            quote!(let #field_name = #decode_field;)
         });
 
         let fields_list = variant
@@ -127,22 +229,78 @@ pub fn decode_enum(data: &DataEnum, name: Ident, is_exhaustive: bool) -> TokenSt
         };
 
         match_body.extend(quote! {
-            #i => {
+            #discriminant => {
                 #(#decode_fields)*
                 #name::#variant_name #initialize
             }
         });
     }
 
-    match_body
-        .extend(quote!(_ => return ::std::result::Result::Err(::phenix_runtime::InvalidPrefix::new(bytes).into())));
+    if let Some(variant) = unknown_variant {
+        let variant_name = &variant.ident;
+        let variant_str = variant_name.to_string();
+        let decode_body = with_context(
+            quote!(::phenix_runtime::base::utils::decode_unknown_body(
+                bytes, len__
+            )),
+            quote!(.in_variant(#variant_str).in_type(#ty_name)),
+        );
+
+        match_body.extend(quote! {
+            _ => {
+                let body = #decode_body;
+                #name::#variant_name(::phenix_runtime::UnknownVariant::new(discriminant, body))
+            }
+        });
+    } else {
+        let invalid_discriminant = if cfg!(feature = "context") {
+            quote! {
+                ::phenix_runtime::DecodingError::from(::phenix_runtime::InvalidPrefix::new(bytes))
+                    .expected("discriminant")
+                    .in_type(#ty_name)
+            }
+        } else {
+            quote!(::phenix_runtime::InvalidPrefix::new(bytes).into())
+        };
+
+        match_body.extend(quote!(_ => return ::core::result::Result::Err(#invalid_discriminant)));
+    }
 
     body.extend(quote! {
         let value = match discriminant {
             #match_body
         };
-
-        ::std::result::Result::Ok(value)
     });
+
+    if !is_exhaustive {
+        body.extend(decode_body_end(&ty_name));
+    }
+
+    body.extend(quote!(::core::result::Result::Ok(value)));
     body
 }
+
+// Reads the length of the body of a non-exhaustive type.
+fn decode_body_start(ty_name: &str) -> TokenStream2 {
+    let decode_len = with_context(
+        quote!(::phenix_runtime::base::utils::decode_body_len(bytes)),
+        quote!(.expected("length").in_type(#ty_name)),
+    );
+
+    quote! {
+        let len__ = #decode_len;
+        let start__ = bytes.mark();
+    }
+}
+
+// Skips the unknown rest of the body of a non-exhaustive type.
+fn decode_body_end(ty_name: &str) -> TokenStream2 {
+    let skip_body = with_context(
+        quote!(::phenix_runtime::base::utils::skip_body(
+            bytes, start__, len__
+        )),
+        quote!(.in_type(#ty_name)),
+    );
+
+    quote!(#skip_body;)
+}
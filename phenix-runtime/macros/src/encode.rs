@@ -1,81 +1,191 @@
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
-use syn::{parse_quote, punctuated::Punctuated, DataEnum, DataStruct, Expr, Ident};
+use syn::{parse_quote, punctuated::Punctuated, Data, DataEnum, DataStruct, Expr, Ident};
 
 use crate::util;
 
-pub fn encode_struct(data: &DataStruct, name: Ident, is_exhaustive: bool) -> TokenStream2 {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    Blocking,
+    Async,
+}
+
+impl Flavor {
+    // Encodes a value referenced by given expression.
+    fn encode(self, value: TokenStream2) -> TokenStream2 {
+        match self {
+            Flavor::Blocking => quote!(#value.encode(writer)?;),
+            Flavor::Async => {
+                quote!(::phenix_runtime::AsyncEncodable::encode_async(#value, writer).await?;)
+            }
+        }
+    }
+
+    // Encodes a value using a function from `phenix_runtime::base`.
+    fn encode_base(self, encode: TokenStream2, value: TokenStream2) -> TokenStream2 {
+        match self {
+            Flavor::Blocking => quote!(#encode(#value, writer)?;),
+            Flavor::Async => quote! {
+                ::phenix_runtime::async_io::write_with(writer, |writer| #encode(#value, writer)).await?;
+            },
+        }
+    }
+
+    // Encodes the body into a buffer first so that it can be prefixed with its
+    // length.
+    fn encode_body(self, body: TokenStream2) -> TokenStream2 {
+        let encode_body = self.encode_base(
+            quote!(::phenix_runtime::base::utils::encode_body),
+            quote!(&body__),
+        );
+
+        quote! {
+            let mut body__ = ::phenix_runtime::__private::Vec::new();
+            {
+                let writer = &mut body__;
+                #body
+            }
+            #encode_body
+        }
+    }
+}
+
+pub fn encode_struct(data: &DataStruct, is_exhaustive: bool, flavor: Flavor) -> TokenStream2 {
     if !is_exhaustive {
-        return util::non_exhaustive_not_supported(name.span(), util::TypeKind::Struct)
-            .into_compile_error();
+        if let Err(error) = util::check_non_exhaustive_struct(data) {
+            return error.into_compile_error();
+        }
     }
 
+    let mut body = if is_exhaustive {
+        encode_fields(data, data.fields.len(), true, flavor)
+    } else {
+        flavor.encode_body(encode_fields(data, data.fields.len(), false, flavor))
+    };
+    body.extend(quote!(::core::result::Result::Ok(())));
+    body
+}
+
+// Encodes the struct without its last field, which is a stream, and returns a
+// writer for the stream items.
+pub fn encode_with_stream(data: &DataStruct) -> Option<TokenStream2> {
+    let (kind, item_ty) = util::unwrap_stream_type(&data.fields.iter().last()?.ty)?;
+
+    let constructor = match kind {
+        util::StreamKind::Plain => quote!(new),
+        util::StreamKind::Framed => quote!(framed),
+    };
+
+    let encode_fields = encode_fields(data, data.fields.len() - 1, true, Flavor::Blocking);
+
+    Some(quote! {
+        pub fn encode_with_stream<W: ::phenix_runtime::io::Write>(
+            &self,
+            mut writer: W,
+        ) -> ::phenix_runtime::io::Result<::phenix_runtime::StreamWriter<W, #item_ty>> {
+            {
+                let writer = &mut writer;
+                #encode_fields
+            }
+
+            ::core::result::Result::Ok(::phenix_runtime::StreamWriter::#constructor(writer))
+        }
+    })
+}
+
+// Encodes the optional fields flags and the first `n` fields. The flags of
+// non-exhaustive structs are always present, even if there are no optional
+// fields yet.
+fn encode_fields(data: &DataStruct, n: usize, is_exhaustive: bool, flavor: Flavor) -> TokenStream2 {
     let mut body = TokenStream2::new();
 
     let optional_fields = util::get_optional_fields(&data.fields);
 
-    if !optional_fields.is_empty() {
-        let mut optional_list = Punctuated::<Expr, syn::token::Comma>::new();
+    let mut optional_list = Punctuated::<Expr, syn::token::Comma>::new();
 
-        for field in optional_fields.iter() {
-            optional_list.push(parse_quote!(self.#field.is_some()));
-        }
+    for field in optional_fields.iter() {
+        optional_list.push(parse_quote!(self.#field.is_some()));
+    }
 
-        body.extend(quote! {
-            ::phenix_runtime::base::bool::encode_many(&[#optional_list], writer)?;
-        });
+    if !is_exhaustive {
+        body.extend(flavor.encode_base(
+            quote!(::phenix_runtime::base::utils::encode_optional_relaxed),
+            quote!(&[#optional_list]),
+        ));
+    } else if !optional_fields.is_empty() {
+        body.extend(flavor.encode_base(
+            quote!(::phenix_runtime::base::bool::encode_many),
+            quote!(&[#optional_list]),
+        ));
     }
 
-    for (i, field) in data.fields.iter().enumerate() {
+    for (i, field) in data.fields.iter().enumerate().take(n) {
         let field_name = field.ident.clone().unwrap_or_else(|| parse_quote!(#i));
-        let encode_field = if util::is_option_type(&field.ty) {
-            quote! {
-                if let ::std::option::Option::Some(#field_name) = self.#field_name {
-                    #field_name.encode(writer)?;
+        let encode_field = match (util::is_option_type(&field.ty), flavor) {
+            (true, _) => {
+                let encode = flavor.encode(quote!(#field_name));
+                quote! {
+                    if let ::core::option::Option::Some(#field_name) = &self.#field_name {
+                        #encode
+                    }
                 }
             }
-        } else {
-            quote!(self.#field_name.encode(writer)?;)
+            (false, Flavor::Blocking) => flavor.encode(quote!(self.#field_name)),
+            (false, Flavor::Async) => flavor.encode(quote!(&self.#field_name)),
         };
 
         body.extend(encode_field);
     }
 
-    body.extend(quote!(::std::result::Result::Ok(())));
     body
 }
 
-pub fn encode_enum(data: &DataEnum, name: Ident, is_exhaustive: bool) -> TokenStream2 {
-    if !is_exhaustive {
-        return util::non_exhaustive_not_supported(name.span(), util::TypeKind::Enum)
-            .into_compile_error();
-    }
-
+pub fn encode_enum(
+    data: &DataEnum,
+    name: Ident,
+    is_exhaustive: bool,
+    flavor: Flavor,
+) -> TokenStream2 {
     let mut body = TokenStream2::new();
 
-    let mut match_body = TokenStream2::new();
+    let discriminants = match util::discriminants(data) {
+        Ok(discriminants) => discriminants,
+        Err(error) => return error.into_compile_error(),
+    };
 
-    for (i, variant) in data.variants.iter().enumerate() {
-        if variant.discriminant.is_some() {
-            return syn::Error::new(
-                variant.ident.span(),
-                "explicit discriminants are not supported",
-            )
-            .into_compile_error();
-        }
+    let unknown_variant = match util::unknown_variant(data, is_exhaustive) {
+        Ok(unknown_variant) => unknown_variant,
+        Err(error) => return error.into_compile_error(),
+    };
+
+    let mut match_body = TokenStream2::new();
 
+    for (variant, discriminant) in util::known_variants(data).zip(discriminants.iter()) {
         let variant_pat = util::into_variant_pat(&name, variant, false);
 
-        match_body.extend(quote!(#variant_pat => #i,));
+        match_body.extend(quote!(#variant_pat => #discriminant,));
+    }
+
+    if let Some(variant) = unknown_variant {
+        let variant_pat = util::into_variant_pat(&name, variant, true);
+
+        match_body.extend(quote!(#variant_pat => f0.discriminant(),));
     }
 
     body.extend(quote! {
         let discriminant = match self {
             #match_body
         };
-        ::phenix_runtime::base::utils::encode_discriminant(discriminant, writer)?;
     });
 
+    let encode_discriminant = if util::is_relaxed_discriminant(&discriminants, is_exhaustive) {
+        quote!(::phenix_runtime::base::utils::encode_discriminant_relaxed)
+    } else {
+        quote!(::phenix_runtime::base::utils::encode_discriminant)
+    };
+
+    body.extend(flavor.encode_base(encode_discriminant, quote!(discriminant)));
+
     match_body = TokenStream2::new();
 
     for variant in data.variants.iter() {
@@ -89,24 +199,148 @@ pub fn encode_enum(data: &DataEnum, name: Ident, is_exhaustive: bool) -> TokenSt
             syn::Fields::Named(ref fields) => {
                 for field in fields.named.iter() {
                     let field_name = field.ident.clone().unwrap();
-                    match_body.extend(quote!(#field_name.encode(writer)?;));
+                    match_body.extend(flavor.encode(quote!(#field_name)));
                 }
             }
             syn::Fields::Unnamed(ref fields) => {
                 for field_name in (0..fields.unnamed.len()).map(util::unnamed_field_name) {
-                    match_body.extend(quote!(#field_name.encode(writer)?;));
+                    match_body.extend(flavor.encode(quote!(#field_name)));
                 }
             }
             syn::Fields::Unit => {}
         });
     }
 
-    body.extend(quote! {
+    let encode_variant = quote! {
         match self {
             #match_body
         }
-    });
+    };
+
+    if is_exhaustive {
+        body.extend(encode_variant);
+    } else {
+        body.extend(flavor.encode_body(encode_variant));
+    }
+
+    body.extend(quote!(::core::result::Result::Ok(())));
+    body
+}
+
+pub fn encoded_len_struct(data: &DataStruct, is_exhaustive: bool) -> TokenStream2 {
+    let optional_fields = util::get_optional_fields(&data.fields);
+    let count = optional_fields.len();
+
+    let mut body = if is_exhaustive {
+        quote!(let mut len__ = ::phenix_runtime::base::bool::encoded_len_many(#count);)
+    } else {
+        quote!(let mut len__ = ::phenix_runtime::base::utils::encoded_len_optional_relaxed(#count);)
+    };
+
+    for (i, field) in data.fields.iter().enumerate() {
+        let field_name = field.ident.clone().unwrap_or_else(|| parse_quote!(#i));
+
+        if util::is_option_type(&field.ty) {
+            body.extend(quote! {
+                if let ::core::option::Option::Some(#field_name) = &self.#field_name {
+                    len__ += ::phenix_runtime::Encodable::encoded_len(#field_name);
+                }
+            });
+        } else {
+            body.extend(
+                quote!(len__ += ::phenix_runtime::Encodable::encoded_len(&self.#field_name);),
+            );
+        }
+    }
+
+    if is_exhaustive {
+        body.extend(quote!(len__));
+    } else {
+        body.extend(quote!(::phenix_runtime::base::utils::encoded_len_body(
+            len__
+        )));
+    }
 
-    body.extend(quote!(::std::result::Result::Ok(())));
     body
 }
+
+pub fn encoded_len_enum(data: &DataEnum, name: Ident, is_exhaustive: bool) -> TokenStream2 {
+    let discriminants = match util::discriminants(data) {
+        Ok(discriminants) => discriminants,
+        Err(error) => return error.into_compile_error(),
+    };
+
+    let mut match_body = TokenStream2::new();
+
+    for (variant, discriminant) in util::known_variants(data).zip(discriminants.iter()) {
+        let variant_pat = util::into_variant_pat(&name, variant, true);
+        let fields = variant.fields.iter().enumerate().map(|(i, field)| {
+            field
+                .ident
+                .clone()
+                .unwrap_or_else(|| util::unnamed_field_name(i))
+        });
+
+        match_body.extend(quote! {
+            #variant_pat => (#discriminant, 0 #(+ ::phenix_runtime::Encodable::encoded_len(#fields))*),
+        });
+    }
+
+    // The discriminant of an unknown variant is stored in the value.
+    if let Some(variant) = data
+        .variants
+        .iter()
+        .find(|variant| util::is_unknown_variant(variant))
+    {
+        let variant_pat = util::into_variant_pat(&name, variant, true);
+
+        match_body.extend(quote! {
+            #variant_pat => (f0.discriminant(), ::phenix_runtime::Encodable::encoded_len(f0)),
+        });
+    }
+
+    let encoded_len_discriminant = if util::is_relaxed_discriminant(&discriminants, is_exhaustive) {
+        quote!(::phenix_runtime::base::utils::encoded_len_discriminant_relaxed)
+    } else {
+        quote!(::phenix_runtime::base::utils::encoded_len_discriminant)
+    };
+
+    let len = if is_exhaustive {
+        quote!(len__)
+    } else {
+        quote!(::phenix_runtime::base::utils::encoded_len_body(len__))
+    };
+
+    quote! {
+        let (discriminant, len__): (usize, usize) = match self {
+            #match_body
+        };
+
+        #encoded_len_discriminant(discriminant) + #len
+    }
+}
+
+pub fn encode_body(
+    data: &Data,
+    name: &Ident,
+    is_exhaustive: bool,
+    flavor: Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    match data {
+        Data::Struct(data) => Ok(encode_struct(data, is_exhaustive, flavor)),
+        Data::Enum(data) => Ok(encode_enum(data, name.clone(), is_exhaustive, flavor)),
+        Data::Union(_) => Err(util::unions_not_supported(name.span())),
+    }
+}
+
+pub fn encoded_len_body(
+    data: &Data,
+    name: &Ident,
+    is_exhaustive: bool,
+) -> Result<TokenStream2, syn::Error> {
+    match data {
+        Data::Struct(data) => Ok(encoded_len_struct(data, is_exhaustive)),
+        Data::Enum(data) => Ok(encoded_len_enum(data, name.clone(), is_exhaustive)),
+        Data::Union(_) => Err(util::unions_not_supported(name.span())),
+    }
+}
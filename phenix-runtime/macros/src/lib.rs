@@ -1,13 +1,15 @@
+use encode::Flavor;
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Ident, TokenStream as TokenStream2};
 use quote::quote;
-use syn::{parse_macro_input, parse_quote, DeriveInput, ItemStruct};
+use syn::{parse_macro_input, parse_quote, DeriveInput};
 
 mod decode;
 mod encode;
 mod parts;
 mod recognize;
 mod util;
+mod validate;
 
 #[proc_macro_derive(Encodable)]
 pub fn encodable(tokens: TokenStream) -> TokenStream {
@@ -16,34 +18,75 @@ pub fn encodable(tokens: TokenStream) -> TokenStream {
 
     let name = input.ident;
 
-    let generics =
-        util::add_trait_bounds(input.generics, parse_quote!(::phenix_runtime::Encodable));
+    let generics = util::add_trait_bounds(
+        input.generics.clone(),
+        parse_quote!(::phenix_runtime::Encodable),
+    );
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let mut encode_body = TokenStream2::new();
+    let encode_body = match encode::encode_body(&input.data, &name, is_exhaustive, Flavor::Blocking)
+    {
+        Ok(body) => body,
+        Err(error) => return error.into_compile_error().into(),
+    };
 
-    match input.data {
-        syn::Data::Struct(data) => {
-            encode_body.extend(encode::encode_struct(&data, name.clone(), is_exhaustive));
-        }
-        syn::Data::Enum(data) => {
-            encode_body.extend(encode::encode_enum(&data, name.clone(), is_exhaustive));
-        }
-        syn::Data::Union(_) => {
-            return util::unions_not_supported(name.span())
-                .into_compile_error()
-                .into();
-        }
-    }
+    let encoded_len_body = match encode::encoded_len_body(&input.data, &name, is_exhaustive) {
+        Ok(body) => body,
+        Err(error) => return error.into_compile_error().into(),
+    };
 
-    let expanded = quote! {
+    let mut expanded = quote! {
         impl #impl_generics phenix_runtime::Encodable for #name #ty_generics #where_clause {
-            fn encode<W: ::std::io::Write>(&self, writer: &mut W) -> ::std::io::Result<()> {
+            fn encode<W: ::phenix_runtime::io::Write>(&self, writer: &mut W) -> ::phenix_runtime::io::Result<()> {
                 #encode_body
             }
+
+            fn encoded_len(&self) -> usize {
+                #encoded_len_body
+            }
         }
     };
 
+    if let (syn::Data::Struct(data), true) = (&input.data, is_exhaustive) {
+        if let Some(encode_with_stream) = encode::encode_with_stream(data) {
+            expanded.extend(quote! {
+                impl #impl_generics #name #ty_generics #where_clause {
+                    #encode_with_stream
+                }
+            });
+        }
+    }
+
+    if cfg!(feature = "futures") {
+        let generics = util::add_trait_bounds(
+            input.generics,
+            parse_quote!(::phenix_runtime::AsyncEncodable),
+        );
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        let encode_body =
+            match encode::encode_body(&input.data, &name, is_exhaustive, Flavor::Async) {
+                Ok(body) => body,
+                Err(error) => return error.into_compile_error().into(),
+            };
+
+        expanded.extend(quote! {
+            impl #impl_generics ::phenix_runtime::AsyncEncodable for #name #ty_generics #where_clause {
+                fn encode_async<'writer, W>(
+                    &'writer self,
+                    writer: &'writer mut W,
+                ) -> ::phenix_runtime::futures::future::BoxFuture<'writer, ::phenix_runtime::io::Result<()>>
+                where
+                    W: ::phenix_runtime::futures::io::AsyncWrite + ::core::marker::Unpin + ::core::marker::Send,
+                {
+                    ::phenix_runtime::__private::Box::pin(async move {
+                        #encode_body
+                    })
+                }
+            }
+        });
+    }
+
     TokenStream::from(expanded)
 }
 
@@ -52,6 +95,10 @@ pub fn decodable(tokens: TokenStream) -> TokenStream {
     let input = parse_macro_input!(tokens as DeriveInput);
     let is_exhaustive = util::is_exhaustive(&input);
 
+    if input.generics.lifetimes().next().is_some() {
+        return decodable_ref(input, is_exhaustive);
+    }
+
     let name = input.ident;
 
     let generics =
@@ -60,23 +107,28 @@ pub fn decodable(tokens: TokenStream) -> TokenStream {
 
     let mut decode_body = TokenStream2::new();
     let mut recognize_body = TokenStream2::new();
+    let mut validate_body = TokenStream2::new();
 
     match input.data {
         syn::Data::Struct(data) => {
-            decode_body.extend(decode::decode_struct(&data, name.clone(), is_exhaustive));
-            recognize_body.extend(recognize::recognize_struct(
+            decode_body.extend(decode::decode_struct(
                 &data,
                 name.clone(),
                 is_exhaustive,
+                None,
             ));
+            recognize_body.extend(recognize::recognize_struct(&data, is_exhaustive));
+            validate_body.extend(validate::validate_struct(&data, is_exhaustive));
         }
         syn::Data::Enum(data) => {
-            decode_body.extend(decode::decode_enum(&data, name.clone(), is_exhaustive));
-            recognize_body.extend(recognize::recognize_enum(
+            decode_body.extend(decode::decode_enum(
                 &data,
                 name.clone(),
                 is_exhaustive,
+                None,
             ));
+            recognize_body.extend(recognize::recognize_enum(&data, is_exhaustive));
+            validate_body.extend(validate::validate_enum(&data, is_exhaustive));
         }
         syn::Data::Union(_) => {
             return util::unions_not_supported(name.span())
@@ -89,14 +141,79 @@ pub fn decodable(tokens: TokenStream) -> TokenStream {
         impl #impl_generics ::phenix_runtime::Decodable for #name #ty_generics #where_clause {
             fn decode(
                 bytes: &mut ::phenix_runtime::bytes::Bytes<'_>,
-            ) -> ::std::result::Result<Self, ::phenix_runtime::DecodingError> {
-                #decode_body
+            ) -> ::core::result::Result<Self, ::phenix_runtime::DecodingError> {
+                bytes.enter()?;
+                let result: ::core::result::Result<_, ::phenix_runtime::DecodingError> = { #decode_body };
+                bytes.leave();
+                result
             }
 
             fn recognize<'a>(
                 bytes: &mut ::phenix_runtime::bytes::Bytes<'a>,
-            ) -> ::std::result::Result<::phenix_runtime::bytes::ByteSlice<'a, Self>, ::phenix_runtime::DecodingError> {
-                #recognize_body
+            ) -> ::core::result::Result<::phenix_runtime::bytes::ByteSlice<'a, Self>, ::phenix_runtime::DecodingError> {
+                bytes.enter()?;
+                let result: ::core::result::Result<_, ::phenix_runtime::DecodingError> = { #recognize_body };
+                bytes.leave();
+                result
+            }
+
+            fn validate(
+                bytes: &mut ::phenix_runtime::bytes::Bytes<'_>,
+            ) -> ::core::result::Result<(), ::phenix_runtime::DecodingError> {
+                bytes.enter()?;
+                let result: ::core::result::Result<_, ::phenix_runtime::DecodingError> = { #validate_body };
+                bytes.leave();
+                result
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+// Types with lifetime parameters may borrow from the input, so only the
+// borrowed decoding is implemented for them.
+fn decodable_ref(input: DeriveInput, is_exhaustive: bool) -> TokenStream {
+    let name = input.ident;
+
+    let lifetime = syn::Lifetime::new("'bytes", proc_macro2::Span::call_site());
+    let mut lifetime_def = syn::LifetimeDef::new(lifetime.clone());
+    lifetime_def
+        .bounds
+        .extend(input.generics.lifetimes().map(|def| def.lifetime.clone()));
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let generics = util::add_trait_bounds(
+        input.generics.clone(),
+        parse_quote!(::phenix_runtime::DecodableRef<#lifetime>),
+    );
+    let generics = util::add_generic_param(generics, syn::GenericParam::Lifetime(lifetime_def));
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    let decode_body = match input.data {
+        syn::Data::Struct(data) => {
+            decode::decode_struct(&data, name.clone(), is_exhaustive, Some(&lifetime))
+        }
+        syn::Data::Enum(data) => {
+            decode::decode_enum(&data, name.clone(), is_exhaustive, Some(&lifetime))
+        }
+        syn::Data::Union(_) => {
+            return util::unions_not_supported(name.span())
+                .into_compile_error()
+                .into();
+        }
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::phenix_runtime::DecodableRef<#lifetime> for #name #ty_generics #where_clause {
+            fn decode_ref(
+                bytes: &mut ::phenix_runtime::bytes::Bytes<#lifetime>,
+            ) -> ::core::result::Result<Self, ::phenix_runtime::DecodingError> {
+                bytes.enter()?;
+                let result: ::core::result::Result<_, ::phenix_runtime::DecodingError> = { #decode_body };
+                bytes.leave();
+                result
             }
         }
     };
@@ -145,7 +262,7 @@ pub fn is_flag(tokens: TokenStream) -> TokenStream {
 
     let expanded = quote! {
         impl ::phenix_runtime::IsFlag for #name {
-            type IntoIter = ::std::array::IntoIter<Self, #count>;
+            type IntoIter = ::core::array::IntoIter<Self, #count>;
 
             const COUNT: usize = #count;
             const IS_EXHAUSTIVE: bool = #is_exhaustive;
@@ -167,10 +284,8 @@ pub fn is_flag(tokens: TokenStream) -> TokenStream {
 
 #[proc_macro_attribute]
 pub fn by_parts(_: TokenStream, item: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(item as ItemStruct);
-    let is_exhaustive = util::is_exhaustive_struct(&input);
-
-    let item = input.clone();
+    let input = parse_macro_input!(item as DeriveInput);
+    let is_exhaustive = util::is_exhaustive(&input);
 
     let name = input.ident.clone();
     let generics = input.generics.clone();
@@ -179,22 +294,54 @@ pub fn by_parts(_: TokenStream, item: TokenStream) -> TokenStream {
     let (parts_name, lifetime, parts_generics) = parts::prepare(&input);
     let (_, parts_ty_generics, parts_where_clause) = parts_generics.split_for_impl();
 
-    let parts = input.fields.iter().enumerate().map(|(i, field)| {
-        let variant = parts::variant_name(i, field);
-        let ty = &field.ty;
-
+    let part = |variant: Ident, ty: &syn::Type| {
         let data = match util::unwrap_option_type(ty) {
-            Some(optional) => quote!(::std::option::Option<::phenix_runtime::bytes::ByteSlice<#lifetime, #optional>>),
+            Some(optional) => {
+                quote!(::core::option::Option<::phenix_runtime::bytes::ByteSlice<#lifetime, #optional>>)
+            }
             None => quote!(::phenix_runtime::bytes::ByteSlice<#lifetime, #ty>),
         };
 
         quote!(#variant(#data))
-    });
+    };
+
+    let (parts, recognize_by_parts) = match input.data {
+        syn::Data::Struct(ref data) => {
+            let parts = data
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| part(parts::field_name(i, field), &field.ty))
+                .collect::<Vec<_>>();
+
+            let recognize_by_parts = parts::impl_recognize_by_parts(&input, data, is_exhaustive);
+            (parts, recognize_by_parts)
+        }
+        syn::Data::Enum(ref data) => {
+            let mut parts = vec![quote!(Discriminant(usize))];
+
+            for variant in util::known_variants(data) {
+                for (i, field) in variant.fields.iter().enumerate() {
+                    parts.push(part(parts::variant_name(variant, i, field), &field.ty));
+                }
+            }
+
+            let recognize_by_parts =
+                parts::impl_recognize_enum_by_parts(&input, data, is_exhaustive);
+            (parts, recognize_by_parts)
+        }
+        syn::Data::Union(_) => {
+            return util::unions_not_supported(name.span())
+                .into_compile_error()
+                .into();
+        }
+    };
 
-    let recognize_by_parts = parts::impl_recognize_by_parts(&item, is_exhaustive);
+    // Type parameters are captured by the returned iterator.
+    let type_params = generics.type_params().map(|param| &param.ident);
 
     let expanded = quote! {
-        #item
+        #input
 
         #[derive(Debug)]
         pub enum #parts_name #parts_ty_generics #parts_where_clause {
@@ -204,9 +351,10 @@ pub fn by_parts(_: TokenStream, item: TokenStream) -> TokenStream {
         impl #impl_generics #name #ty_generics #where_clause {
             pub fn recognize_by_parts<'input, #lifetime>(
                 bytes: &'input mut ::phenix_runtime::bytes::Bytes<#lifetime>,
-            ) -> impl ::std::iter::Iterator<Item = ::std::result::Result<#parts_name #parts_ty_generics, ::phenix_runtime::DecodingError>> + 'input
+            ) -> impl ::core::iter::Iterator<Item = ::core::result::Result<#parts_name #parts_ty_generics, ::phenix_runtime::DecodingError>> + ::phenix_runtime::__private::Captures<#lifetime> + 'input
             where
-                Self: ::phenix_runtime::Decodable
+                Self: ::phenix_runtime::Decodable,
+                #(#type_params: ::phenix_runtime::Decodable + 'input,)*
             {
                 #recognize_by_parts
             }
@@ -1,23 +1,41 @@
 use convert_case::{Case, Casing};
 use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote};
-use syn::{Field, Generics, ItemStruct, Lifetime};
+use syn::{
+    parse_quote, DataEnum, DataStruct, DeriveInput, Field, Generics, Lifetime, TypeGenerics,
+};
 
 use crate::util;
 
-pub fn prepare(item: &ItemStruct) -> (Ident, Lifetime, Generics) {
-    let parts_name = format_ident!("{}Part", item.ident);
+pub fn prepare(input: &DeriveInput) -> (Ident, Lifetime, Generics) {
+    let parts_name = format_ident!("{}Part", input.ident);
 
     let lifetime = syn::Lifetime::new("'bytes", proc_macro2::Span::call_site());
-    let parts_generics = util::add_generic_param(
-        item.generics.clone(),
-        syn::GenericParam::Lifetime(syn::LifetimeDef::new(lifetime.clone())),
-    );
+
+    // Enums without fields have only the discriminant part and structs without
+    // fields have no parts, neither borrows the bytes. Unknown variants have no
+    // parts either.
+    let has_fields = match input.data {
+        syn::Data::Enum(ref data) => {
+            util::known_variants(data).any(|variant| !variant.fields.is_empty())
+        }
+        syn::Data::Struct(ref data) => !data.fields.is_empty(),
+        _ => true,
+    };
+
+    let parts_generics = if has_fields {
+        util::add_generic_param(
+            input.generics.clone(),
+            syn::GenericParam::Lifetime(syn::LifetimeDef::new(lifetime.clone())),
+        )
+    } else {
+        input.generics.clone()
+    };
 
     (parts_name, lifetime, parts_generics)
 }
 
-pub fn variant_name(i: usize, field: &Field) -> Ident {
+pub fn field_name(i: usize, field: &Field) -> Ident {
     let name = field
         .ident
         .clone()
@@ -26,29 +44,59 @@ pub fn variant_name(i: usize, field: &Field) -> Ident {
     format_ident!("{}", name.to_string().to_case(Case::Pascal))
 }
 
-pub fn impl_recognize_by_parts(item: &ItemStruct, is_exhaustive: bool) -> TokenStream2 {
-    if !is_exhaustive {
-        return util::non_exhaustive_not_supported(item.ident.span(), util::TypeKind::Struct)
-            .into_compile_error();
-    }
+pub fn variant_name(variant: &syn::Variant, i: usize, field: &Field) -> Ident {
+    format_ident!("{}{}", variant.ident, field_name(i, field))
+}
+
+// The iterator over the parts is defined inside the function and so it cannot
+// use the generics of the type, it needs its own.
+fn prepare_iter(input: &DeriveInput, lifetime: &Lifetime) -> (Ident, Generics) {
+    let by_parts = format_ident!("{}ByParts", input.ident);
+
+    let input_lifetime = syn::Lifetime::new("'input", proc_macro2::Span::call_site());
+    let iter_generics = util::add_generic_param(
+        input.generics.clone(),
+        syn::GenericParam::Lifetime(syn::LifetimeDef::new(lifetime.clone())),
+    );
+    let iter_generics = util::add_generic_param(
+        iter_generics,
+        syn::GenericParam::Lifetime(syn::LifetimeDef::new(input_lifetime)),
+    );
+    let iter_generics =
+        util::add_trait_bounds(iter_generics, parse_quote!(::phenix_runtime::Decodable));
+
+    (by_parts, iter_generics)
+}
 
-    let (parts_name, lifetime, parts_generics) = prepare(item);
+pub fn impl_recognize_by_parts(
+    input: &DeriveInput,
+    data: &DataStruct,
+    is_exhaustive: bool,
+) -> TokenStream2 {
+    let (parts_name, lifetime, parts_generics) = prepare(input);
     let (_, parts_ty_generics, _) = parts_generics.split_for_impl();
 
-    let by_parts = format_ident!("{}ByParts", item.ident);
-    let state = format_ident!("{}State", item.ident);
+    let (by_parts, iter_generics) = prepare_iter(input, &lifetime);
+    let (iter_impl_generics, iter_ty_generics, iter_where_clause) = iter_generics.split_for_impl();
+
+    let state = format_ident!("{}State", input.ident);
 
     let final_state = Ident::new("T", Span::call_site());
     let error_state = Ident::new("E", Span::call_site());
 
-    let states = item
+    let states = data
         .fields
         .iter()
         .enumerate()
         .map(|(i, _)| format_ident!("S{}", i))
         .collect::<Vec<_>>();
 
-    let initial_state = states.first().unwrap().clone();
+    let initial_state = match states.first() {
+        Some(state) => state.clone(),
+        None => {
+            return impl_recognize_empty_by_parts(&parts_name, &parts_ty_generics, is_exhaustive)
+        }
+    };
 
     let transitions = states
         .iter()
@@ -59,17 +107,22 @@ pub fn impl_recognize_by_parts(item: &ItemStruct, is_exhaustive: bool) -> TokenS
 
     let mut recognizers = Vec::new();
 
-    for (s, (i, field)) in states.iter().zip(item.fields.iter().enumerate()) {
-        let variant = variant_name(i, field);
+    for (s, (i, field)) in states.iter().zip(data.fields.iter().enumerate()) {
+        let variant = field_name(i, field);
         let field_ty = &field.ty;
 
         let recognize_field = match util::unwrap_option_type(&field.ty) {
             Some(option_ty) => {
+                let test_bit = if is_exhaustive {
+                    quote!(::phenix_runtime::base::utils::test_bit_at(#optional_bit, &self.optional))
+                } else {
+                    quote!(::phenix_runtime::base::utils::try_test_bit_at(#optional_bit, &self.optional) == ::core::option::Option::Some(true))
+                };
                 let recognize_field = quote! {
-                    #state :: #s => if ::phenix_runtime::base::utils::test_bit_at(#optional_bit, optional) {
-                        <#option_ty>::recognize(bytes).map(::std::option::Option::Some).map(#parts_name :: #variant)
+                    #state :: #s => if #test_bit {
+                        <#option_ty as ::phenix_runtime::Decodable>::recognize(self.bytes).map(::core::option::Option::Some).map(#parts_name :: #variant)
                     } else {
-                        ::std::result::Result::Ok(::std::option::Option::None).map(#parts_name :: #variant)
+                        ::core::result::Result::Ok(::core::option::Option::None).map(#parts_name :: #variant)
                     }
                 };
 
@@ -77,7 +130,7 @@ pub fn impl_recognize_by_parts(item: &ItemStruct, is_exhaustive: bool) -> TokenS
                 recognize_field
             }
             None => {
-                quote!(#state :: #s => <#field_ty>::recognize(bytes).map(#parts_name :: #variant))
+                quote!(#state :: #s => <#field_ty as ::phenix_runtime::Decodable>::recognize(self.bytes).map(#parts_name :: #variant))
             }
         };
 
@@ -102,55 +155,83 @@ pub fn impl_recognize_by_parts(item: &ItemStruct, is_exhaustive: bool) -> TokenS
                     #state :: #error_state => #state :: #final_state,
                 }
             }
-
-            fn recognize<#lifetime>(
-                &self,
-                bytes: &mut ::phenix_runtime::bytes::Bytes<#lifetime>,
-                optional: &[u8],
-                error: &mut ::std::option::Option<::phenix_runtime::DecodingError>,
-            ) -> ::std::option::Option<::std::result::Result<#parts_name #parts_ty_generics, ::phenix_runtime::DecodingError>> {
-                ::std::option::Option::Some(match self {
-                    #(#recognizers,)*
-                    #state :: #final_state => return ::std::option::Option::None,
-                    #state :: #error_state => return error.take().map(Err),
-                })
-            }
         }
 
-        struct #by_parts<#lifetime, 'input> {
+        struct #by_parts #iter_ty_generics {
             bytes: &'input mut ::phenix_runtime::bytes::Bytes<#lifetime>,
-            optional: Vec<u8>,
+            optional: ::phenix_runtime::__private::Vec<u8>,
+            // The start and length of the body of a non-exhaustive struct.
+            body: ::core::option::Option<(::phenix_runtime::bytes::Mark, usize)>,
             state: #state,
-            error: Option<::phenix_runtime::DecodingError>,
+            error: ::core::option::Option<::phenix_runtime::DecodingError>,
+            ty: ::core::marker::PhantomData<fn() -> #parts_name #parts_ty_generics>,
         }
 
-        impl<#lifetime, 'input> ::std::iter::Iterator for #by_parts<#lifetime, 'input> {
-            type Item = ::std::result::Result<#parts_name #parts_ty_generics, ::phenix_runtime::DecodingError>;
+        impl #iter_impl_generics ::core::iter::Iterator for #by_parts #iter_ty_generics #iter_where_clause {
+            type Item = ::core::result::Result<#parts_name #parts_ty_generics, ::phenix_runtime::DecodingError>;
+
+            fn next(&mut self) -> ::core::option::Option<Self::Item> {
+                let value = match self.state {
+                    #(#recognizers,)*
+                    #state :: #final_state => return ::core::option::Option::None,
+                    #state :: #error_state => return self.error.take().map(::core::result::Result::Err),
+                };
+
+                // Nothing can be recognized after an error.
+                if value.is_err() {
+                    self.body = ::core::option::Option::None;
+                    self.state = #state :: #final_state;
+                    return ::core::option::Option::Some(value);
+                }
 
-            fn next(&mut self) -> ::std::option::Option<Self::Item> {
-                let value = self.state.recognize(self.bytes, &self.optional, &mut self.error)?;
                 self.state = self.state.next();
-                Some(value)
+
+                // Skip the fields unknown to this version.
+                if self.state == #state :: #final_state {
+                    if let ::core::option::Option::Some((start, len)) = self.body.take() {
+                        if let ::core::result::Result::Err(error) = ::phenix_runtime::base::utils::skip_body(self.bytes, start, len) {
+                            self.state = #state :: #error_state;
+                            self.error = ::core::option::Option::Some(error);
+                        }
+                    }
+                }
+
+                ::core::option::Option::Some(value)
             }
         }
     });
 
-    let optional_fields = util::get_optional_fields(&item.fields);
+    let optional_fields = util::get_optional_fields(&data.fields);
 
-    if !optional_fields.is_empty() {
+    if !is_exhaustive {
+        body.extend(quote! {
+            let header = ::phenix_runtime::base::utils::decode_body_len(bytes).and_then(|len| {
+                let start = bytes.mark();
+                ::phenix_runtime::base::utils::decode_optional_relaxed(bytes)
+                    .map(|optional| (start, len, optional.to_vec()))
+            });
+
+            let (body, optional, state, error) = match header {
+                ::core::result::Result::Ok((start, len, optional)) => (::core::option::Option::Some((start, len)), optional, #state :: #initial_state, ::core::option::Option::None),
+                ::core::result::Result::Err(error) => (::core::option::Option::None, ::phenix_runtime::__private::Vec::new(), #state :: #error_state, ::core::option::Option::Some(error)),
+            };
+        });
+    } else if !optional_fields.is_empty() {
         let count = optional_fields.len();
 
         body.extend(quote! {
+            let body = ::core::option::Option::None;
             let (optional, state, error) = match ::phenix_runtime::base::bool::recognize_many(bytes, #count) {
-                ::std::result::Result::Ok(optional) => (optional.as_bytes().to_vec(), #state :: #initial_state, ::std::option::Option::None),
-                ::std::result::Result::Err(error) => (::std::vec::Vec::new(), #state :: #error_state, ::std::option::Option::Some(error)),
+                ::core::result::Result::Ok(optional) => (optional.as_bytes().to_vec(), #state :: #initial_state, ::core::option::Option::None),
+                ::core::result::Result::Err(error) => (::phenix_runtime::__private::Vec::new(), #state :: #error_state, ::core::option::Option::Some(error)),
             };
         });
     } else {
         body.extend(quote! {
-            let optional = ::std::vec::Vec::new();
+            let body = ::core::option::Option::None;
+            let optional = ::phenix_runtime::__private::Vec::new();
             let state = #state :: #initial_state;
-            let error = ::std::option::Option::None;
+            let error = ::core::option::Option::None;
         });
     }
 
@@ -158,9 +239,200 @@ pub fn impl_recognize_by_parts(item: &ItemStruct, is_exhaustive: bool) -> TokenS
         #by_parts {
             bytes,
             optional,
+            body,
             state,
             error,
+            ty: ::core::marker::PhantomData,
         }
     });
     body
 }
+
+// There are no parts, but the body of a non-exhaustive struct is still skipped.
+fn impl_recognize_empty_by_parts(
+    parts_name: &Ident,
+    parts_ty_generics: &TypeGenerics,
+    is_exhaustive: bool,
+) -> TokenStream2 {
+    let result = if is_exhaustive {
+        quote! {
+            let _ = bytes;
+            let result: ::core::result::Result<(), ::phenix_runtime::DecodingError> = ::core::result::Result::Ok(());
+        }
+    } else {
+        quote! {
+            let result = ::phenix_runtime::base::utils::decode_body_len(bytes).and_then(|len| {
+                let start = bytes.mark();
+                ::phenix_runtime::base::utils::skip_body(bytes, start, len)
+            });
+        }
+    };
+
+    quote! {
+        #result
+        result
+            .err()
+            .map(::core::result::Result::Err::<#parts_name #parts_ty_generics, _>)
+            .into_iter()
+    }
+}
+
+// The discriminant is yielded first, followed by the fields of the variant.
+// Unknown variants of non-exhaustive enums have no fields.
+pub fn impl_recognize_enum_by_parts(
+    input: &DeriveInput,
+    data: &DataEnum,
+    is_exhaustive: bool,
+) -> TokenStream2 {
+    let (parts_name, lifetime, parts_generics) = prepare(input);
+    let (_, parts_ty_generics, _) = parts_generics.split_for_impl();
+
+    let (by_parts, iter_generics) = prepare_iter(input, &lifetime);
+    let (iter_impl_generics, iter_ty_generics, iter_where_clause) = iter_generics.split_for_impl();
+
+    let state = format_ident!("{}State", input.ident);
+
+    let discriminant_state = Ident::new("D", Span::call_site());
+    let final_state = Ident::new("T", Span::call_site());
+    let error_state = Ident::new("E", Span::call_site());
+
+    let discriminants = match util::discriminants(data) {
+        Ok(discriminants) => discriminants,
+        Err(error) => return error.into_compile_error(),
+    };
+
+    let mut states = Vec::new();
+    let mut variant_states = Vec::new();
+    let mut recognizers = Vec::new();
+
+    for (i, (variant, discriminant)) in util::known_variants(data)
+        .zip(discriminants.iter())
+        .enumerate()
+    {
+        let field_states = variant
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(j, _)| format_ident!("V{}F{}", i, j))
+            .collect::<Vec<_>>();
+
+        let first = field_states.first().unwrap_or(&final_state);
+        variant_states.push(quote!(#discriminant => ::core::result::Result::Ok(#state :: #first)));
+
+        for (j, field) in variant.fields.iter().enumerate() {
+            let s = &field_states[j];
+            let next = field_states.get(j + 1).unwrap_or(&final_state);
+            let part = variant_name(variant, j, field);
+            let field_ty = &field.ty;
+
+            recognizers.push(quote! {
+                #state :: #s => (<#field_ty as ::phenix_runtime::Decodable>::recognize(self.bytes).map(#parts_name :: #part), #state :: #next)
+            });
+        }
+
+        states.extend(field_states);
+    }
+
+    let decode_discriminant = if util::is_relaxed_discriminant(&discriminants, is_exhaustive) {
+        quote!(::phenix_runtime::base::utils::decode_discriminant_relaxed)
+    } else {
+        quote!(::phenix_runtime::base::utils::decode_discriminant)
+    };
+
+    let decode_header = if is_exhaustive {
+        quote! {
+            #decode_discriminant(self.bytes).map(|discriminant| (discriminant, ::core::option::Option::None))
+        }
+    } else {
+        quote! {
+            #decode_discriminant(self.bytes).and_then(|discriminant| {
+                let len = ::phenix_runtime::base::utils::decode_body_len(self.bytes)?;
+                ::core::result::Result::Ok((discriminant, ::core::option::Option::Some((self.bytes.mark(), len))))
+            })
+        }
+    };
+
+    let unknown_variant = if is_exhaustive {
+        quote!(::core::result::Result::Err(
+            ::phenix_runtime::InvalidPrefix::new(self.bytes).into()
+        ))
+    } else {
+        quote!(::core::result::Result::Ok(#state :: #final_state))
+    };
+
+    quote! {
+        #[derive(Clone, Copy, PartialEq)]
+        enum #state {
+            #discriminant_state,
+            #(#states,)*
+            #final_state,
+            #error_state,
+        }
+
+        struct #by_parts #iter_ty_generics {
+            bytes: &'input mut ::phenix_runtime::bytes::Bytes<#lifetime>,
+            // The start and length of the body of a non-exhaustive enum.
+            body: ::core::option::Option<(::phenix_runtime::bytes::Mark, usize)>,
+            state: #state,
+            error: ::core::option::Option<::phenix_runtime::DecodingError>,
+            ty: ::core::marker::PhantomData<fn() -> #parts_name #parts_ty_generics>,
+        }
+
+        impl #iter_impl_generics ::core::iter::Iterator for #by_parts #iter_ty_generics #iter_where_clause {
+            type Item = ::core::result::Result<#parts_name #parts_ty_generics, ::phenix_runtime::DecodingError>;
+
+            fn next(&mut self) -> ::core::option::Option<Self::Item> {
+                let (value, state) = match self.state {
+                    #state :: #discriminant_state => match #decode_header {
+                        ::core::result::Result::Ok((discriminant, body)) => {
+                            self.body = body;
+
+                            let state = match discriminant {
+                                #(#variant_states,)*
+                                _ => #unknown_variant,
+                            };
+
+                            match state {
+                                ::core::result::Result::Ok(state) => (::core::result::Result::Ok(#parts_name :: Discriminant(discriminant)), state),
+                                ::core::result::Result::Err(error) => (::core::result::Result::Err(error), #state :: #final_state),
+                            }
+                        }
+                        ::core::result::Result::Err(error) => (::core::result::Result::Err(error), #state :: #final_state),
+                    },
+                    #(#recognizers,)*
+                    #state :: #final_state => return ::core::option::Option::None,
+                    #state :: #error_state => return self.error.take().map(::core::result::Result::Err),
+                };
+
+                // Nothing can be recognized after an error.
+                if value.is_err() {
+                    self.body = ::core::option::Option::None;
+                    self.state = #state :: #final_state;
+                    return ::core::option::Option::Some(value);
+                }
+
+                self.state = state;
+
+                // Skip the fields unknown to this version.
+                if self.state == #state :: #final_state {
+                    if let ::core::option::Option::Some((start, len)) = self.body.take() {
+                        if let ::core::result::Result::Err(error) = ::phenix_runtime::base::utils::skip_body(self.bytes, start, len) {
+                            self.state = #state :: #error_state;
+                            self.error = ::core::option::Option::Some(error);
+                        }
+                    }
+                }
+
+                ::core::option::Option::Some(value)
+            }
+        }
+
+        #by_parts {
+            bytes,
+            body: ::core::option::Option::None,
+            state: #state :: #discriminant_state,
+            error: ::core::option::Option::None,
+            ty: ::core::marker::PhantomData,
+        }
+    }
+}
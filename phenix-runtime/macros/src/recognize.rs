@@ -1,19 +1,28 @@
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{DataEnum, DataStruct, Ident};
+use syn::{DataEnum, DataStruct};
 
 use crate::util;
 
-pub fn recognize_struct(data: &DataStruct, name: Ident, is_exhaustive: bool) -> TokenStream2 {
-    if !is_exhaustive {
-        return util::non_exhaustive_not_supported(name.span(), util::TypeKind::Struct)
-            .into_compile_error();
-    }
-
+pub fn recognize_struct(data: &DataStruct, is_exhaustive: bool) -> TokenStream2 {
     let mut body = TokenStream2::new();
 
     body.extend(quote!(let mark = bytes.mark();));
 
+    if !is_exhaustive {
+        if let Err(error) = util::check_non_exhaustive_struct(data) {
+            return error.into_compile_error();
+        }
+
+        // The fields are not recognized individually, because the body may
+        // contain fields unknown to this version.
+        body.extend(quote! {
+            ::phenix_runtime::base::utils::recognize_body(bytes)?;
+            ::core::result::Result::Ok(bytes.take_slice_from(mark))
+        });
+        return body;
+    }
+
     let optional_fields = util::get_optional_fields(&data.fields);
 
     if !optional_fields.is_empty() {
@@ -34,71 +43,77 @@ pub fn recognize_struct(data: &DataStruct, name: Ident, is_exhaustive: bool) ->
             Some(option_ty) => {
                 let recognize_field = quote! {
                     if ::phenix_runtime::base::utils::test_bit_at(#optional_bit, optional) {
-                        <#option_ty>::recognize(bytes)?;
+                        <#option_ty as ::phenix_runtime::Decodable>::recognize(bytes)?;
                     }
                 };
 
                 optional_bit += 1;
                 recognize_field
             }
-            None => quote!(<#field_ty>::recognize(bytes)?;),
+            None => quote!(<#field_ty as ::phenix_runtime::Decodable>::recognize(bytes)?;),
         };
 
         body.extend(recognize_field);
     }
 
-    body.extend(quote!(::std::result::Result::Ok(
+    body.extend(quote!(::core::result::Result::Ok(
         bytes.take_slice_from(mark)
     )));
     body
 }
 
-pub fn recognize_enum(data: &DataEnum, name: Ident, is_exhaustive: bool) -> TokenStream2 {
-    if !is_exhaustive {
-        return util::non_exhaustive_not_supported(name.span(), util::TypeKind::Enum)
-            .into_compile_error();
-    }
-
+pub fn recognize_enum(data: &DataEnum, is_exhaustive: bool) -> TokenStream2 {
     let mut body = TokenStream2::new();
 
     body.extend(quote!(let mark = bytes.mark();));
 
-    body.extend(
-        quote!(let discriminant = ::phenix_runtime::base::utils::decode_discriminant(bytes)?;),
-    );
+    let discriminants = match util::discriminants(data) {
+        Ok(discriminants) => discriminants,
+        Err(error) => return error.into_compile_error(),
+    };
 
-    let mut match_body = TokenStream2::new();
+    if !is_exhaustive {
+        // Unknown variants are skipped the same way as the known ones.
+        body.extend(quote! {
+            ::phenix_runtime::base::utils::decode_discriminant_relaxed(bytes)?;
+            ::phenix_runtime::base::utils::recognize_body(bytes)?;
+            ::core::result::Result::Ok(bytes.take_slice_from(mark))
+        });
+        return body;
+    }
 
-    for (i, variant) in data.variants.iter().enumerate() {
-        if variant.discriminant.is_some() {
-            return syn::Error::new(
-                variant.ident.span(),
-                "explicit discriminants are not supported",
-            )
-            .into_compile_error();
-        }
+    let decode_discriminant = if util::is_relaxed_discriminant(&discriminants, is_exhaustive) {
+        quote!(::phenix_runtime::base::utils::decode_discriminant_relaxed)
+    } else {
+        quote!(::phenix_runtime::base::utils::decode_discriminant)
+    };
+
+    body.extend(quote!(let discriminant = #decode_discriminant(bytes)?;));
+
+    let mut match_body = TokenStream2::new();
 
+    for (variant, discriminant) in util::known_variants(data).zip(discriminants.iter()) {
         let recognize_fields = variant.fields.iter().map(|field| {
             let field_ty = &field.ty;
-            quote!(<#field_ty>::recognize(bytes)?;)
+            quote!(<#field_ty as ::phenix_runtime::Decodable>::recognize(bytes)?;)
         });
 
         match_body.extend(quote! {
-            #i => {
+            #discriminant => {
                 #(#recognize_fields)*
             }
         });
     }
 
     match_body
-        .extend(quote!(_ => return ::std::result::Result::Err(::phenix_runtime::InvalidPrefix::new(bytes).into())));
+        .extend(quote!(_ => return ::core::result::Result::Err(::phenix_runtime::InvalidPrefix::new(bytes).into())));
 
     body.extend(quote! {
         match discriminant {
             #match_body
         }
 
-        ::std::result::Result::Ok(bytes.take_slice_from(mark))
+        ::core::result::Result::Ok(bytes.take_slice_from(mark))
     });
     body
 }
@@ -1,15 +1,10 @@
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{quote, ToTokens};
 use syn::{
-    parse_quote, punctuated::Punctuated, DeriveInput, Fields, GenericParam, Generics, Ident,
-    ItemStruct, Type, TypePath, Variant,
+    parse_quote, punctuated::Punctuated, spanned::Spanned, DataEnum, DataStruct, DeriveInput, Expr,
+    ExprLit, Fields, GenericParam, Generics, Ident, Lit, Type, TypePath, Variant,
 };
 
-pub enum TypeKind {
-    Struct,
-    Enum,
-}
-
 pub fn is_exhaustive(input: &DeriveInput) -> bool {
     !input
         .attrs
@@ -17,13 +12,6 @@ pub fn is_exhaustive(input: &DeriveInput) -> bool {
         .any(|attr| attr.path.is_ident("non_exhaustive"))
 }
 
-pub fn is_exhaustive_struct(input: &ItemStruct) -> bool {
-    !input
-        .attrs
-        .iter()
-        .any(|attr| attr.path.is_ident("non_exhaustive"))
-}
-
 pub fn add_trait_bounds(mut generics: Generics, trait_ty: TypePath) -> Generics {
     for param in &mut generics.params {
         if let GenericParam::Type(ref mut type_param) = *param {
@@ -95,10 +83,131 @@ pub fn unwrap_option_type(ty: &Type) -> Option<&Type> {
     }
 }
 
+pub enum StreamKind {
+    Plain,
+    Framed,
+}
+
+// Returns the kind and the item type if the type is `phenix_runtime::Stream<T>`
+// or `phenix_runtime::FramedStream<T>`. The full path is required, so that
+// user types of the same name are not taken for streams.
+pub fn unwrap_stream_type(ty: &Type) -> Option<(StreamKind, &Type)> {
+    let segments = match ty {
+        Type::Path(path_ty) if path_ty.qself.is_none() => &path_ty.path.segments,
+        _ => return None,
+    };
+
+    if segments.len() != 2 || segments[0].ident != "phenix_runtime" {
+        return None;
+    }
+
+    let segment = &segments[1];
+
+    let kind = if segment.ident == "Stream" {
+        StreamKind::Plain
+    } else if segment.ident == "FramedStream" {
+        StreamKind::Framed
+    } else {
+        return None;
+    };
+
+    match segment.arguments {
+        syn::PathArguments::AngleBracketed(ref arguments) => match arguments.args.first()? {
+            syn::GenericArgument::Type(ty) => Some((kind, ty)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 pub fn is_option_type(ty: &Type) -> bool {
     unwrap_option_type(ty).is_some()
 }
 
+// The catch-all variant of a non-exhaustive enum holds the variants unknown to
+// this version, recognized by its only field of type `UnknownVariant`.
+pub fn is_unknown_variant(variant: &Variant) -> bool {
+    match variant.fields {
+        Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => match fields.unnamed[0].ty {
+            Type::Path(ref path_ty) => path_ty
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "UnknownVariant"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+pub fn known_variants(data: &DataEnum) -> impl Iterator<Item = &Variant> {
+    data.variants
+        .iter()
+        .filter(|variant| !is_unknown_variant(variant))
+}
+
+pub fn unknown_variant(
+    data: &DataEnum,
+    is_exhaustive: bool,
+) -> Result<Option<&Variant>, syn::Error> {
+    let mut unknown = data
+        .variants
+        .iter()
+        .filter(|variant| is_unknown_variant(variant));
+
+    match (unknown.next(), unknown.next()) {
+        (Some(variant), _) if is_exhaustive => Err(syn::Error::new(
+            variant.span(),
+            "only non-exhaustive enums can have a variant for unknown variants",
+        )),
+        (Some(_), Some(variant)) => Err(syn::Error::new(
+            variant.span(),
+            "only one variant can hold unknown variants",
+        )),
+        (variant, _) => Ok(variant),
+    }
+}
+
+// Variants without an explicit discriminant have the discriminant of the
+// previous variant plus one, as in Rust. The variant for unknown variants has
+// no discriminant of its own.
+pub fn discriminants(data: &DataEnum) -> Result<Vec<usize>, syn::Error> {
+    let mut next = 0;
+
+    known_variants(data)
+        .map(|variant| {
+            let discriminant = match variant.discriminant {
+                Some((_, ref expr)) => match expr {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Int(lit), ..
+                    }) => lit.base10_parse()?,
+                    _ => {
+                        return Err(syn::Error::new(
+                            expr.span(),
+                            "only integer literals are supported as discriminants",
+                        ))
+                    }
+                },
+                None => next,
+            };
+
+            next = discriminant + 1;
+            Ok(discriminant)
+        })
+        .collect()
+}
+
+// Discriminants that do not fit into a byte and those of non-exhaustive enums
+// are encoded as variable-length integers. Mirrors
+// `phenix_codegen::EnumType::is_relaxed_discriminant`, which is checked by the
+// reflect tests.
+pub fn is_relaxed_discriminant(discriminants: &[usize], is_exhaustive: bool) -> bool {
+    !is_exhaustive
+        || discriminants
+            .iter()
+            .any(|discriminant| *discriminant > u8::MAX as usize)
+}
+
 pub fn into_variant_pat(ty_name: &Ident, variant: &Variant, field_names: bool) -> TokenStream2 {
     let mut pat = TokenStream2::new();
 
@@ -144,19 +253,33 @@ pub fn into_variant_pat(ty_name: &Ident, variant: &Variant, field_names: bool) -
     pat
 }
 
+// Human-readable name of a type for error messages.
+pub fn type_name(ty: &Type) -> String {
+    ty.to_token_stream()
+        .to_string()
+        .replace(" < ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+        .replace("& ", "&")
+        .replace(" :: ", "::")
+}
+
 pub fn unnamed_field_name(i: usize) -> Ident {
     Ident::new(&format!("f{}", i), Span::call_site())
 }
 
-pub fn non_exhaustive_not_supported(span: Span, ty_kind: TypeKind) -> syn::Error {
-    let ty_kind = match ty_kind {
-        TypeKind::Struct => "structs",
-        TypeKind::Enum => "enums",
-    };
-    syn::Error::new(
-        span,
-        format!("non exhaustive {} are not yet supported", ty_kind),
-    )
+// The length of the body of a non-exhaustive struct must be known, so it
+// cannot end with an unbounded stream.
+pub fn check_non_exhaustive_struct(data: &DataStruct) -> Result<(), syn::Error> {
+    match data.fields.iter().last() {
+        Some(field) if matches!(unwrap_stream_type(&field.ty), Some((StreamKind::Plain, _))) => {
+            Err(syn::Error::new(
+                field.ty.span(),
+                "non exhaustive structs cannot contain streams, use framed streams instead",
+            ))
+        }
+        _ => Ok(()),
+    }
 }
 
 pub fn unions_not_supported(span: Span) -> syn::Error {
@@ -0,0 +1,134 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{DataEnum, DataStruct};
+
+use crate::util;
+
+pub fn validate_struct(data: &DataStruct, is_exhaustive: bool) -> TokenStream2 {
+    let mut body = TokenStream2::new();
+
+    if !is_exhaustive {
+        if let Err(error) = util::check_non_exhaustive_struct(data) {
+            return error.into_compile_error();
+        }
+
+        body.extend(quote! {
+            let len__ = ::phenix_runtime::base::utils::decode_body_len(bytes)?;
+            let start__ = bytes.mark();
+        });
+    }
+
+    let optional_fields = util::get_optional_fields(&data.fields);
+
+    if !is_exhaustive {
+        body.extend(quote! {
+            let optional__ = ::phenix_runtime::base::utils::decode_optional_relaxed(bytes)?;
+        });
+    } else if !optional_fields.is_empty() {
+        let count = optional_fields.len();
+
+        body.extend(quote! {
+            let optional__ = ::phenix_runtime::base::bool::recognize_many(bytes, #count)?;
+            let optional__ = optional__.as_bytes();
+        });
+    }
+
+    let mut optional_bit = 0usize;
+
+    for field in data.fields.iter() {
+        let field_ty = &field.ty;
+
+        let validate_field = match util::unwrap_option_type(&field.ty) {
+            Some(option_ty) => {
+                let test_bit = if is_exhaustive {
+                    quote!(::phenix_runtime::base::utils::test_bit_at(#optional_bit, optional__))
+                } else {
+                    quote!(::phenix_runtime::base::utils::try_test_bit_at(#optional_bit, optional__) == ::core::option::Option::Some(true))
+                };
+                let validate_field = quote! {
+                    if #test_bit {
+                        <#option_ty as ::phenix_runtime::Decodable>::validate(bytes)?;
+                    }
+                };
+
+                optional_bit += 1;
+                validate_field
+            }
+            None => quote!(<#field_ty as ::phenix_runtime::Decodable>::validate(bytes)?;),
+        };
+
+        body.extend(validate_field);
+    }
+
+    if !is_exhaustive {
+        body.extend(quote! {
+            ::phenix_runtime::base::utils::skip_body(bytes, start__, len__)?;
+        });
+    }
+
+    body.extend(quote!(::core::result::Result::Ok(())));
+    body
+}
+
+pub fn validate_enum(data: &DataEnum, is_exhaustive: bool) -> TokenStream2 {
+    let mut body = TokenStream2::new();
+
+    let discriminants = match util::discriminants(data) {
+        Ok(discriminants) => discriminants,
+        Err(error) => return error.into_compile_error(),
+    };
+
+    let decode_discriminant = if util::is_relaxed_discriminant(&discriminants, is_exhaustive) {
+        quote!(::phenix_runtime::base::utils::decode_discriminant_relaxed)
+    } else {
+        quote!(::phenix_runtime::base::utils::decode_discriminant)
+    };
+
+    body.extend(quote!(let discriminant = #decode_discriminant(bytes)?;));
+
+    if !is_exhaustive {
+        body.extend(quote! {
+            let len__ = ::phenix_runtime::base::utils::decode_body_len(bytes)?;
+            let start__ = bytes.mark();
+        });
+    }
+
+    let mut match_body = TokenStream2::new();
+
+    for (variant, discriminant) in util::known_variants(data).zip(discriminants.iter()) {
+        let validate_fields = variant.fields.iter().map(|field| {
+            let field_ty = &field.ty;
+            quote!(<#field_ty as ::phenix_runtime::Decodable>::validate(bytes)?;)
+        });
+
+        match_body.extend(quote! {
+            #discriminant => {
+                #(#validate_fields)*
+            }
+        });
+    }
+
+    // Unknown variants of non-exhaustive enums are valid only if they can be
+    // decoded into the variant for unknown variants.
+    if data.variants.iter().any(util::is_unknown_variant) && !is_exhaustive {
+        match_body.extend(quote!(_ => {}));
+    } else {
+        match_body
+            .extend(quote!(_ => return ::core::result::Result::Err(::phenix_runtime::InvalidPrefix::new(bytes).into())));
+    }
+
+    body.extend(quote! {
+        match discriminant {
+            #match_body
+        }
+    });
+
+    if !is_exhaustive {
+        body.extend(quote! {
+            ::phenix_runtime::base::utils::skip_body(bytes, start__, len__)?;
+        });
+    }
+
+    body.extend(quote!(::core::result::Result::Ok(())));
+    body
+}
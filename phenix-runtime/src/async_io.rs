@@ -0,0 +1,465 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use futures::{
+    future::BoxFuture,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+
+use crate::{
+    base,
+    bytes::{Bytes, DecodeLimits},
+    read::{Buffer, ReadError},
+    Decodable, DecodingError, Encodable, Flags, Float, FramedStream, IsFlag, Lazy, Sint, Stream,
+    Uint, UnknownVariant,
+};
+
+pub trait AsyncEncodable: Encodable + Sync {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send;
+
+    fn encode_many_async<'a, W>(
+        values: &'a [Self],
+        writer: &'a mut W,
+    ) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(async move {
+            for item in values {
+                item.encode_async(writer).await?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+pub trait AsyncDecodable: Decodable + Send {
+    fn decode_async<'a, R>(
+        source: &'a mut AsyncReadBytes<R>,
+    ) -> BoxFuture<'a, Result<Self, ReadError>>
+    where
+        Self: 'a,
+        R: AsyncRead + Unpin + Send;
+}
+
+impl<T: Decodable + Send> AsyncDecodable for T {
+    fn decode_async<'a, R>(
+        source: &'a mut AsyncReadBytes<R>,
+    ) -> BoxFuture<'a, Result<Self, ReadError>>
+    where
+        Self: 'a,
+        R: AsyncRead + Unpin + Send,
+    {
+        Box::pin(source.decode())
+    }
+}
+
+// Encodes a value of bounded size into a temporary buffer and writes it at
+// once. Useful for encoders that are only available for blocking writers.
+pub async fn write_with<W, F>(writer: &mut W, encode: F) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+{
+    let mut buf = Vec::new();
+    encode(&mut buf)?;
+    writer.write_all(&buf).await
+}
+
+// Asynchronous counterpart of `read::ReadBytes`.
+#[derive(Debug)]
+pub struct AsyncReadBytes<R> {
+    reader: R,
+    buffer: Buffer,
+}
+
+impl<R: AsyncRead + Unpin> AsyncReadBytes<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Buffer::default(),
+        }
+    }
+
+    // The limits apply to each decoded value separately.
+    pub fn with_limits(reader: R, limits: DecodeLimits) -> Self {
+        Self {
+            reader,
+            buffer: Buffer::with_limits(limits),
+        }
+    }
+
+    // See `read::ReadBytes::max_buffer`.
+    pub fn max_buffer(&mut self, size: usize) -> &mut Self {
+        self.buffer.max_buffer(size);
+        self
+    }
+
+    pub fn position(&self) -> usize {
+        self.buffer.position()
+    }
+
+    pub async fn is_empty(&mut self) -> Result<bool, ReadError> {
+        while self.buffer.needs_fill() {
+            self.fill().await?;
+        }
+
+        Ok(self.buffer.is_drained())
+    }
+
+    pub async fn decode<T: Decodable>(&mut self) -> Result<T, ReadError> {
+        self.decode_with(T::decode).await
+    }
+
+    pub async fn decode_with<T, F>(&mut self, mut decode: F) -> Result<T, ReadError>
+    where
+        F: FnMut(&mut Bytes<'_>) -> Result<T, DecodingError>,
+    {
+        loop {
+            match self.buffer.try_decode(&mut decode)? {
+                Some(value) => return Ok(value),
+                None => self.fill().await?,
+            }
+        }
+    }
+
+    pub fn stream<T: Decodable>(
+        &mut self,
+    ) -> impl futures::Stream<Item = Result<T, ReadError>> + '_ {
+        futures::stream::unfold(self, |source| async move {
+            match source.is_empty().await {
+                Ok(true) => None,
+                Ok(false) => Some((source.decode().await, source)),
+                Err(error) => Some((Err(error), source)),
+            }
+        })
+    }
+
+    // Returns the reader, the bytes that were read but not decoded yet are
+    // lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    async fn fill(&mut self) -> io::Result<()> {
+        let spare = self.buffer.spare();
+
+        loop {
+            match self.reader.read(spare).await {
+                Ok(n) => {
+                    self.buffer.filled(n);
+                    return Ok(());
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl<T: Decodable> Stream<T> {
+    // The items are expected to follow in the source right after the value
+    // which contains this stream.
+    pub fn stream_from<'r, R: AsyncRead + Unpin>(
+        &self,
+        source: &'r mut AsyncReadBytes<R>,
+    ) -> impl futures::Stream<Item = Result<T, ReadError>> + 'r {
+        source.stream()
+    }
+}
+
+// Types of small size are encoded into a temporary buffer first.
+macro_rules! impl_buffered {
+    ($ty:ty) => {
+        impl AsyncEncodable for $ty {
+            fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+            where
+                W: AsyncWrite + Unpin + Send,
+            {
+                Box::pin(write_with(writer, |buf| self.encode(buf)))
+            }
+        }
+    };
+}
+
+impl_buffered!(u8);
+impl_buffered!(u16);
+impl_buffered!(u32);
+impl_buffered!(u64);
+
+impl_buffered!(i8);
+impl_buffered!(i16);
+impl_buffered!(i32);
+impl_buffered!(i64);
+
+impl_buffered!(f32);
+impl_buffered!(f64);
+
+impl_buffered!(Uint);
+impl_buffered!(Sint);
+impl_buffered!(Float);
+
+impl AsyncEncodable for bool {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(write_with(writer, |buf| self.encode(buf)))
+    }
+
+    fn encode_many_async<'a, W>(
+        values: &'a [Self],
+        writer: &'a mut W,
+    ) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(write_with(writer, |buf| {
+            base::bool::encode_many(values, buf)
+        }))
+    }
+}
+
+impl AsyncEncodable for String {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(encode_str(self, writer))
+    }
+}
+
+impl AsyncEncodable for &str {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(encode_str(self, writer))
+    }
+}
+
+impl AsyncEncodable for Cow<'_, str> {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(encode_str(self, writer))
+    }
+}
+
+async fn encode_str<W: AsyncWrite + Unpin>(value: &str, writer: &mut W) -> io::Result<()> {
+    write_with(writer, |buf| base::uint::encode(value.len() as u64, buf)).await?;
+    writer.write_all(value.as_bytes()).await
+}
+
+impl<T: AsyncEncodable> AsyncEncodable for Vec<T> {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(encode_slice(self, writer))
+    }
+}
+
+impl<T: AsyncEncodable> AsyncEncodable for &[T] {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(encode_slice(self, writer))
+    }
+}
+
+async fn encode_slice<T, W>(values: &[T], writer: &mut W) -> io::Result<()>
+where
+    T: AsyncEncodable,
+    W: AsyncWrite + Unpin + Send,
+{
+    write_with(writer, |buf| base::uint::encode(values.len() as u64, buf)).await?;
+    T::encode_many_async(values, writer).await
+}
+
+impl<T: Sync> AsyncEncodable for Stream<T> {
+    fn encode_async<'a, W>(&'a self, _: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl<T: AsyncEncodable> AsyncEncodable for FramedStream<T> {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(write_with(writer, |buf| self.encode(buf)))
+    }
+}
+
+impl<T: AsyncEncodable> AsyncEncodable for Lazy<T> {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(async move {
+            match self.value() {
+                Some(value) => value.encode_async(writer).await,
+                // Fails the same way as the blocking encoding.
+                None => write_with(writer, |buf| self.encode(buf)).await,
+            }
+        })
+    }
+}
+
+impl AsyncEncodable for UnknownVariant {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(writer.write_all(self.body()))
+    }
+}
+
+impl<T: AsyncEncodable, const N: usize> AsyncEncodable for [T; N] {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(write_with(writer, |buf| self.encode(buf)))
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: AsyncEncodable),+> AsyncEncodable for ($($name,)+) {
+            fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+            where
+                W: AsyncWrite + Unpin + Send,
+            {
+                Box::pin(write_with(writer, |buf| self.encode(buf)))
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+impl_tuple!(A, B, C, D, E, F, G, H, I);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+impl<K: AsyncEncodable, V: AsyncEncodable> AsyncEncodable for BTreeMap<K, V> {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(write_with(writer, |buf| self.encode(buf)))
+    }
+}
+
+impl<K: AsyncEncodable, V: AsyncEncodable, S: Sync> AsyncEncodable for HashMap<K, V, S> {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(write_with(writer, |buf| self.encode(buf)))
+    }
+}
+
+impl<T: IsFlag + Sync> AsyncEncodable for Flags<T> {
+    fn encode_async<'a, W>(&'a self, writer: &'a mut W) -> BoxFuture<'a, io::Result<()>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        Box::pin(async move {
+            if !T::IS_EXHAUSTIVE {
+                write_with(writer, |buf| {
+                    base::utils::encode_discriminant_relaxed(self.flags.len(), buf)
+                })
+                .await?;
+            }
+
+            writer.write_all(self.flags.as_slice()).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use futures::{executor::block_on, io::Cursor as AsyncCursor, StreamExt};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn encode_sync<T: Encodable>(values: &[T]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+
+        for value in values {
+            value.encode(&mut cursor).unwrap();
+        }
+
+        cursor.into_inner()
+    }
+
+    fn encode_async<T: AsyncEncodable>(values: &[T]) -> Vec<u8> {
+        let mut cursor = AsyncCursor::new(Vec::new());
+
+        block_on(async {
+            for value in values {
+                value.encode_async(&mut cursor).await.unwrap();
+            }
+        });
+
+        cursor.into_inner()
+    }
+
+    proptest! {
+        #[test]
+        fn uints(values: Vec<u64>) {
+            let values = values.into_iter().map(Uint).collect::<Vec<_>>();
+            assert_eq!(encode_async(&values), encode_sync(&values));
+        }
+
+        #[test]
+        fn strings(values: Vec<String>) {
+            let bytes = encode_async(&values);
+            assert_eq!(bytes, encode_sync(&values));
+
+            let mut source = AsyncReadBytes::new(bytes.as_slice());
+            let decoded = block_on(source.stream::<String>().collect::<Vec<_>>());
+            let decoded = decoded.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(decoded, values);
+        }
+
+        #[test]
+        fn bools(values: Vec<Vec<bool>>) {
+            let bytes = encode_async(&values);
+            assert_eq!(bytes, encode_sync(&values));
+
+            let mut source = AsyncReadBytes::new(bytes.as_slice());
+
+            for value in values {
+                let decoded = block_on(Vec::<bool>::decode_async(&mut source)).unwrap();
+                assert_eq!(decoded, value);
+            }
+
+            assert!(block_on(source.is_empty()).unwrap());
+        }
+    }
+}
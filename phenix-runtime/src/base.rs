@@ -1,8 +1,13 @@
-use std::{io, mem};
+use alloc::vec::Vec;
+use core::mem;
 
+use crate::io;
+
+#[cfg(test)]
+use super::bytes::DecodeLimits;
 use super::{
-    bytes::{ByteSlice, Bytes},
-    DecodingError, UnexpectedEof,
+    bytes::{ByteSlice, Bytes, Mark},
+    DecodingError, InvalidPrefix, UnexpectedEof, ValueError,
 };
 
 pub mod uint {
@@ -15,6 +20,9 @@ pub mod uint {
 
     const N_BYTES_SHIFT: u8 = u8::MAX - mem::size_of::<u64>() as u8;
 
+    // The maximum number of bytes an encoded value can take.
+    pub const MAX_LEN: usize = 1 + mem::size_of::<u64>();
+
     pub fn encode<W: io::Write>(value: u64, writer: &mut W) -> io::Result<()> {
         let bytes = value.to_le_bytes();
 
@@ -36,6 +44,15 @@ pub mod uint {
         writer.write_all(&bytes[..n_bytes])
     }
 
+    pub fn encoded_len(value: u64) -> usize {
+        if value <= N_BYTES_SHIFT as u64 {
+            1
+        } else {
+            let n_bits = u64::BITS - value.leading_zeros();
+            1 + (n_bits as usize).div_ceil(u8::BITS as usize)
+        }
+    }
+
     pub fn decode(bytes: &mut Bytes<'_>) -> Result<u64, DecodingError> {
         let mut buf = [0u8; mem::size_of::<u64>()];
 
@@ -55,6 +72,10 @@ pub mod uint {
             return Err(UnexpectedEof::new(bytes).into());
         }
 
+        if bytes.is_strict() && !is_canonical(&bytes[1..1 + n_bytes]) {
+            return Err(ValueError::new(bytes).into());
+        }
+
         bytes.consume(1);
         buf[..n_bytes].copy_from_slice(bytes.consume_bytes(n_bytes).unwrap());
 
@@ -73,23 +94,35 @@ pub mod uint {
 
         let n_bytes = (small - N_BYTES_SHIFT) as usize;
 
+        if bytes.is_strict() && bytes.len() > n_bytes && !is_canonical(&bytes[1..1 + n_bytes]) {
+            return Err(ValueError::new(bytes).into());
+        }
+
         bytes
             .consume_slice(1 + n_bytes)
             .ok_or_else(|| UnexpectedEof::new(bytes).into())
     }
 
+    // The value bytes following the first byte must not have trailing zeros
+    // and the value must not fit into the first byte alone.
+    fn is_canonical(value: &[u8]) -> bool {
+        match value {
+            [] => false,
+            [byte] => *byte > N_BYTES_SHIFT,
+            [.., last] => *last != 0,
+        }
+    }
+
     #[cfg(test)]
     mod tests {
-        use std::io::Cursor;
-
         use proptest::prelude::*;
 
         use super::*;
 
         fn encode_to_bytes(value: u64) -> Vec<u8> {
-            let mut cursor = Cursor::new(Vec::new());
-            encode(value, &mut cursor).unwrap();
-            cursor.into_inner()
+            let mut bytes = Vec::new();
+            encode(value, &mut bytes).unwrap();
+            bytes
         }
 
         fn decode_from_bytes(value: &[u8]) -> Result<u64, DecodingError> {
@@ -111,6 +144,7 @@ pub mod uint {
             fn roundtrip(value: u64) {
                 let bytes = encode_to_bytes(value);
 
+                assert_eq!(encoded_len(value), bytes.len());
                 assert_eq!(decode_from_bytes(&bytes), Ok(value));
                 assert_eq!(
                     recognize_from_bytes(&bytes).and_then(decode_from_byte_slice),
@@ -124,6 +158,44 @@ pub mod uint {
                 let recognize_result = recognize_from_bytes(&bytes);
                 assert_eq!(decode_result.is_ok(), recognize_result.is_ok());
             }
+
+            #[test]
+            fn strict_roundtrip(value: u64) {
+                let bytes = encode_to_bytes(value);
+                let limits = *DecodeLimits::default().strict();
+
+                assert_eq!(decode(&mut Bytes::with_limits(&bytes, limits)), Ok(value));
+                assert!(recognize(&mut Bytes::with_limits(&bytes, limits)).is_ok());
+            }
+
+            #[test]
+            fn strict_fuzz(bytes: Vec<u8>) {
+                let limits = *DecodeLimits::default().strict();
+                let decode_result = decode(&mut Bytes::with_limits(&bytes, limits));
+                let recognize_result = recognize(&mut Bytes::with_limits(&bytes, limits));
+                assert_eq!(decode_result.is_ok(), recognize_result.is_ok());
+
+                if let Ok(value) = decode_result {
+                    assert!(bytes.starts_with(&encode_to_bytes(value)));
+                }
+            }
+        }
+
+        #[test]
+        fn non_canonical() {
+            let limits = *DecodeLimits::default().strict();
+
+            for bytes in [&[248, 5][..], &[249, 5, 0], &[250, 44, 1, 0]] {
+                assert!(decode_from_bytes(bytes).is_ok());
+                assert_eq!(
+                    decode(&mut Bytes::with_limits(bytes, limits)),
+                    Err(ValueError::new_at(0).into())
+                );
+                assert_eq!(
+                    recognize(&mut Bytes::with_limits(bytes, limits)),
+                    Err(ValueError::new_at(0).into())
+                );
+            }
         }
     }
 }
@@ -140,6 +212,11 @@ pub mod sint {
         super::uint::encode(value, writer)
     }
 
+    pub fn encoded_len(value: i64) -> usize {
+        let value = value >> (i64::BITS - 1) ^ (value << 1);
+        super::uint::encoded_len(u64::from_le_bytes(value.to_le_bytes()))
+    }
+
     pub fn decode(bytes: &mut Bytes<'_>) -> Result<i64, DecodingError> {
         let value = super::uint::decode(bytes)?;
 
@@ -153,16 +230,14 @@ pub mod sint {
 
     #[cfg(test)]
     mod tests {
-        use std::io::Cursor;
-
         use proptest::prelude::*;
 
         use super::*;
 
         fn encode_to_bytes(value: i64) -> Vec<u8> {
-            let mut cursor = Cursor::new(Vec::new());
-            encode(value, &mut cursor).unwrap();
-            cursor.into_inner()
+            let mut bytes = Vec::new();
+            encode(value, &mut bytes).unwrap();
+            bytes
         }
 
         fn decode_from_bytes(value: &[u8]) -> Result<i64, DecodingError> {
@@ -184,6 +259,7 @@ pub mod sint {
             fn roundtrip(value: i64) {
                 let bytes = encode_to_bytes(value);
 
+                assert_eq!(encoded_len(value), bytes.len());
                 assert_eq!(decode_from_bytes(&bytes), Ok(value));
                 assert_eq!(
                     recognize_from_bytes(&bytes).and_then(decode_from_byte_slice),
@@ -214,6 +290,10 @@ pub mod float {
         super::uint::encode(value, writer)
     }
 
+    pub fn encoded_len(value: f64) -> usize {
+        super::uint::encoded_len(u64::from_le_bytes(value.to_be_bytes()))
+    }
+
     pub fn decode(bytes: &mut Bytes<'_>) -> Result<f64, DecodingError> {
         let value = super::uint::decode(bytes)?;
         Ok(f64::from_be_bytes(value.to_le_bytes()))
@@ -225,16 +305,14 @@ pub mod float {
 
     #[cfg(test)]
     mod tests {
-        use std::io::Cursor;
-
         use proptest::prelude::*;
 
         use super::*;
 
         fn encode_to_bytes(value: f64) -> Vec<u8> {
-            let mut cursor = Cursor::new(Vec::new());
-            encode(value, &mut cursor).unwrap();
-            cursor.into_inner()
+            let mut bytes = Vec::new();
+            encode(value, &mut bytes).unwrap();
+            bytes
         }
 
         fn decode_from_bytes(value: &[u8]) -> Result<f64, DecodingError> {
@@ -270,6 +348,7 @@ pub mod float {
             fn roundtrip(value: f64) {
                 let bytes = encode_to_bytes(value);
 
+                assert_eq!(encoded_len(value), bytes.len());
                 assert_eq!(decode_from_bytes(&bytes), Ok(value));
                 assert_eq!(
                     recognize_from_bytes(&bytes).and_then(decode_from_byte_slice),
@@ -312,8 +391,17 @@ pub mod bool {
         Ok(())
     }
 
+    // The values are packed into bits.
+    pub fn encoded_len_many(n: usize) -> usize {
+        byte_size(n)
+    }
+
     pub fn decode(bytes: &mut Bytes<'_>) -> Result<bool, DecodingError> {
-        if bytes.len() > 0 {
+        if !bytes.is_empty() {
+            if bytes.is_strict() && bytes[0] > 1 {
+                return Err(ValueError::new(bytes).into());
+            }
+
             let value = bytes[0] & 0x01 != 0;
             bytes.consume(1);
 
@@ -332,11 +420,9 @@ pub mod bool {
             return Ok(());
         }
 
-        let (n_bytes, div, rem) = byte_size_extra(n);
+        let (_, div, rem) = byte_size_extra(n);
 
-        let bytes = bytes
-            .consume_bytes(n_bytes)
-            .ok_or_else(|| UnexpectedEof::new(bytes))?;
+        let bytes = recognize_many(bytes, n)?.as_bytes();
 
         // Iterate over bytes, but ignore the last byte if it is "incomplete".
         for mut byte in bytes.iter().copied().take(div) {
@@ -360,6 +446,10 @@ pub mod bool {
     }
 
     pub fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, bool>, DecodingError> {
+        if bytes.is_strict() && bytes.first().is_some_and(|byte| *byte > 1) {
+            return Err(ValueError::new(bytes).into());
+        }
+
         bytes
             .consume_slice(1)
             .ok_or_else(|| UnexpectedEof::new(bytes).into())
@@ -371,11 +461,29 @@ pub mod bool {
     ) -> Result<ByteSlice<'a, bool>, DecodingError> {
         let n_bytes = byte_size(n);
 
+        if bytes.is_strict() && bytes.len() >= n_bytes && has_padding(&bytes[..n_bytes], n) {
+            return Err(ValueError::new(bytes).into());
+        }
+
         bytes
             .consume_slice(n_bytes)
             .ok_or_else(|| UnexpectedEof::new(bytes).into())
     }
 
+    // Checks whether any of the unused bits after the first `n_bits` bits is
+    // set.
+    pub fn has_padding(slice: &[u8], n_bits: usize) -> bool {
+        let (_, div, rem) = byte_size_extra(n_bits);
+
+        slice.iter().enumerate().skip(div).any(|(i, byte)| {
+            if i == div {
+                byte >> rem != 0
+            } else {
+                *byte != 0
+            }
+        })
+    }
+
     pub fn byte_size_extra(n_bits: usize) -> (usize, usize, usize) {
         let div = n_bits / u8::BITS as usize;
         let rem = n_bits % u8::BITS as usize;
@@ -429,12 +537,16 @@ pub mod utils {
     use super::*;
 
     pub fn encode_discriminant<W: io::Write>(n: usize, writer: &mut W) -> io::Result<()> {
-        let n = u8::try_from(n).expect("by default types can only have up to 255 items");
+        let n = u8::try_from(n).expect("discriminants greater than 255 must be encoded as relaxed");
         writer.write_all(&[n])
     }
 
+    pub fn encoded_len_discriminant(_: usize) -> usize {
+        1
+    }
+
     pub fn decode_discriminant(bytes: &mut Bytes<'_>) -> Result<usize, DecodingError> {
-        if bytes.len() > 0 {
+        if !bytes.is_empty() {
             let n = bytes[0] as usize;
             bytes.consume(1);
             Ok(n)
@@ -447,10 +559,100 @@ pub mod utils {
         super::uint::encode(n as u64, writer)
     }
 
+    pub fn encoded_len_discriminant_relaxed(n: usize) -> usize {
+        super::uint::encoded_len(n as u64)
+    }
+
     pub fn decode_discriminant_relaxed(bytes: &mut Bytes<'_>) -> Result<usize, DecodingError> {
         super::uint::decode(bytes).map(|n| n as usize)
     }
 
+    // Non-exhaustive types are encoded as a length-prefixed body, so that
+    // readers can skip fields and variants they do not know.
+    pub fn encode_body<W: io::Write>(body: &[u8], writer: &mut W) -> io::Result<()> {
+        super::uint::encode(body.len() as u64, writer)?;
+        writer.write_all(body)
+    }
+
+    // The encoded length of a body of given length including its prefix.
+    pub fn encoded_len_body(len: usize) -> usize {
+        super::uint::encoded_len(len as u64) + len
+    }
+
+    // Length prefixes that cannot be decoded are invalid, unless more bytes may
+    // follow the incomplete ones.
+    pub fn decode_len(bytes: &mut Bytes<'_>) -> Result<u64, DecodingError> {
+        super::uint::decode(bytes).map_err(|error| match error {
+            DecodingError::UnexpectedEof(_) if bytes.is_incomplete() => error,
+            _ => InvalidPrefix::new(bytes).into(),
+        })
+    }
+
+    pub fn decode_body_len(bytes: &mut Bytes<'_>) -> Result<usize, DecodingError> {
+        let len = decode_discriminant_relaxed(bytes)?;
+
+        if bytes.len() >= len {
+            Ok(len)
+        } else {
+            Err(UnexpectedEof::new(bytes).into())
+        }
+    }
+
+    pub fn recognize_body(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        let len = decode_body_len(bytes)?;
+        bytes.consume(len);
+        Ok(())
+    }
+
+    // Takes the whole body of an unknown variant of a non-exhaustive enum.
+    pub fn decode_unknown_body(
+        bytes: &mut Bytes<'_>,
+        len: usize,
+    ) -> Result<Vec<u8>, DecodingError> {
+        bytes.allocate(len)?;
+
+        bytes
+            .consume_bytes(len)
+            .map(|body| body.to_vec())
+            .ok_or_else(|| UnexpectedEof::new(bytes).into())
+    }
+
+    // Skips the rest of the body starting at given mark, which contains the
+    // unknown trailing fields.
+    pub fn skip_body(bytes: &mut Bytes<'_>, start: Mark, len: usize) -> Result<(), DecodingError> {
+        let consumed = bytes.mark().to_usize() - start.to_usize();
+
+        if consumed <= len {
+            bytes.consume(len - consumed);
+            Ok(())
+        } else {
+            Err(ValueError::new_at(start.to_usize()).into())
+        }
+    }
+
+    // The optional fields flags of non-exhaustive structs are prefixed with
+    // their byte size, because new optional fields can be added.
+    pub fn encode_optional_relaxed<W: io::Write>(
+        values: &[bool],
+        writer: &mut W,
+    ) -> io::Result<()> {
+        encode_discriminant_relaxed(super::bool::byte_size(values.len()), writer)?;
+        super::bool::encode_many(values, writer)
+    }
+
+    pub fn encoded_len_optional_relaxed(n: usize) -> usize {
+        let n_bytes = super::bool::byte_size(n);
+        encoded_len_discriminant_relaxed(n_bytes) + n_bytes
+    }
+
+    pub fn decode_optional_relaxed<'a>(bytes: &mut Bytes<'a>) -> Result<&'a [u8], DecodingError> {
+        let n_bytes = decode_discriminant_relaxed(bytes)?;
+
+        bytes
+            .consume_bytes(n_bytes)
+            .ok_or_else(|| UnexpectedEof::new(bytes).into())
+    }
+
     pub fn byte_index_of(bit: usize) -> usize {
         bit / u8::BITS as usize
     }
@@ -493,8 +695,6 @@ pub mod utils {
 
     #[cfg(test)]
     mod tests {
-        use std::io::Cursor;
-
         use super::*;
 
         #[test]
@@ -506,9 +706,8 @@ pub mod utils {
 
         #[test]
         fn bit_testing_with_bool_encoding() {
-            let mut cursor = Cursor::new(Vec::new());
-            super::bool::encode_many(&[false, true], &mut cursor).unwrap();
-            let bytes = cursor.into_inner();
+            let mut bytes = Vec::new();
+            super::bool::encode_many(&[false, true], &mut bytes).unwrap();
 
             assert!(!test_bit_at(0, &bytes));
             assert!(test_bit_at(1, &bytes));
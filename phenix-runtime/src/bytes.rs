@@ -1,36 +1,136 @@
-use std::{marker::PhantomData, ops::Deref};
+use core::{marker::PhantomData, ops::Deref};
+
+use crate::{DecodingError, Limit, LimitExceeded};
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Bytes<'a> {
     bytes: &'a [u8],
+    // Position of the bytes in the origin, so that marks and slices can be
+    // resolved against it even when decoding a nested value.
+    offset: usize,
     consumed: usize,
+    partial: bool,
+    // More bytes may follow the partial ones, running out of the bytes then
+    // means that they must be read first.
+    incomplete: bool,
+    limits: DecodeLimits,
+    allocated: usize,
+    depth: usize,
 }
 
 impl<'a> Bytes<'a> {
     pub fn new(bytes: &'a [u8]) -> Self {
-        Self::with_consumed(bytes, 0)
+        Self::with_offset(bytes, 0)
+    }
+
+    pub fn with_limits(bytes: &'a [u8], limits: DecodeLimits) -> Self {
+        Self {
+            limits,
+            ..Self::new(bytes)
+        }
+    }
+
+    // The bytes start at the given offset of a larger origin.
+    pub fn with_offset(bytes: &'a [u8], offset: usize) -> Self {
+        Self {
+            bytes,
+            offset,
+            consumed: 0,
+            partial: false,
+            incomplete: false,
+            limits: DecodeLimits::default(),
+            allocated: 0,
+            depth: 0,
+        }
+    }
+
+    // The bytes are only a window at given position of a larger input that is
+    // being read incrementally (see `read::ReadBytes`).
+    #[cfg(feature = "std")]
+    pub(crate) fn partial(
+        bytes: &'a [u8],
+        offset: usize,
+        limits: DecodeLimits,
+        incomplete: bool,
+    ) -> Self {
+        Self {
+            partial: true,
+            incomplete,
+            limits,
+            ..Self::with_offset(bytes, offset)
+        }
+    }
+
+    pub fn limits(&self) -> &DecodeLimits {
+        &self.limits
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.limits.strict
+    }
+
+    pub fn check_collection_len(&self, len: usize) -> Result<(), DecodingError> {
+        self.check(Limit::CollectionLen, len, self.limits.max_collection_len)
+    }
+
+    pub fn check_string_len(&self, len: usize) -> Result<(), DecodingError> {
+        self.check(Limit::StringLen, len, self.limits.max_string_len)
+    }
+
+    // Accounts the size of memory about to be allocated for decoded values.
+    pub fn allocate(&mut self, size: usize) -> Result<(), DecodingError> {
+        let allocated = self.allocated.saturating_add(size);
+        self.check(Limit::Alloc, allocated, self.limits.max_alloc)?;
+        self.allocated = allocated;
+        Ok(())
+    }
+
+    // Must be paired with `leave` when decoding of a nested value succeeds.
+    pub fn enter(&mut self) -> Result<(), DecodingError> {
+        self.check(Limit::Depth, self.depth + 1, self.limits.max_depth)?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn check(&self, limit: Limit, value: usize, max: Option<usize>) -> Result<(), DecodingError> {
+        match max {
+            Some(max) if value > max => Err(LimitExceeded::new(self, limit).into()),
+            _ => Ok(()),
+        }
     }
 
-    pub(crate) fn with_consumed(bytes: &'a [u8], consumed: usize) -> Self {
-        Self { bytes, consumed }
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    // Whether the error only means that more bytes must be read.
+    #[cfg(feature = "std")]
+    pub(crate) fn needs_more(&self, error: &DecodingError) -> bool {
+        self.incomplete && matches!(error.root(), DecodingError::UnexpectedEof(_))
     }
 
     pub fn consume(&mut self, len: usize) {
         self.consumed += len;
     }
 
+    // The length usually comes from the input, so it is not trusted.
     pub fn consume_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
-        if self.consumed + len <= self.bytes.len() {
-            let offset = self.consumed;
-            self.consumed += len;
-            Some(&self.bytes[offset..self.consumed])
-        } else {
-            None
-        }
+        let end = self.consumed.checked_add(len)?;
+        let slice = self.bytes.get(self.consumed..end)?;
+        self.consumed = end;
+        Some(slice)
     }
 
     pub fn consume_slice<T>(&mut self, len: usize) -> Option<ByteSlice<'a, T>> {
-        let offset = self.consumed;
+        let offset = self.offset + self.consumed;
 
         self.consume_bytes(len).map(|slice| ByteSlice {
             slice,
@@ -41,7 +141,7 @@ impl<'a> Bytes<'a> {
 
     pub fn take_slice_from<T>(&self, offset: Mark) -> ByteSlice<'a, T> {
         let offset = offset.to_usize();
-        let slice = &self.bytes[offset..self.consumed];
+        let slice = &self.bytes[offset - self.offset..self.consumed];
 
         ByteSlice {
             slice,
@@ -51,7 +151,7 @@ impl<'a> Bytes<'a> {
     }
 
     pub fn mark(&self) -> Mark {
-        Mark(self.consumed)
+        Mark(self.offset + self.consumed)
     }
 }
 
@@ -63,6 +163,47 @@ impl Deref for Bytes<'_> {
     }
 }
 
+// Bounds for decoding of untrusted input. There are no limits by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecodeLimits {
+    max_alloc: Option<usize>,
+    max_collection_len: Option<usize>,
+    max_string_len: Option<usize>,
+    max_depth: Option<usize>,
+    strict: bool,
+}
+
+impl DecodeLimits {
+    // Total size in bytes of the decoded strings and collections.
+    pub fn max_alloc(&mut self, size: usize) -> &mut Self {
+        self.max_alloc = Some(size);
+        self
+    }
+
+    pub fn max_collection_len(&mut self, len: usize) -> &mut Self {
+        self.max_collection_len = Some(len);
+        self
+    }
+
+    pub fn max_string_len(&mut self, len: usize) -> &mut Self {
+        self.max_string_len = Some(len);
+        self
+    }
+
+    // Nesting of structs and enums.
+    pub fn max_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    // Rejects encodings that are not the canonical ones produced by the
+    // encoder, such as integers with redundant bytes or set padding bits.
+    pub fn strict(&mut self) -> &mut Self {
+        self.strict = true;
+        self
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Mark(usize);
 
@@ -78,18 +219,41 @@ impl From<Mark> for usize {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct ByteSlice<'a, T> {
     slice: &'a [u8],
     offset: usize,
     ty: PhantomData<fn() -> T>,
 }
 
+// Not derived, because that would require `T: Copy`.
+impl<T> Clone for ByteSlice<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ByteSlice<'_, T> {}
+
 impl<'a, T> ByteSlice<'a, T> {
-    pub fn as_bytes(&self) -> &[u8] {
+    pub fn as_bytes(&self) -> &'a [u8] {
         self.slice
     }
 
+    // Bytes for decoding the slice, positioned at the slice in its origin.
+    pub fn to_bytes(&self) -> Bytes<'a> {
+        Bytes::with_offset(self.slice, self.offset)
+    }
+
+    // The limits are not kept in the slice, a slice of untrusted input must be
+    // decoded with them again.
+    pub fn to_bytes_with_limits(&self, limits: DecodeLimits) -> Bytes<'a> {
+        Bytes {
+            limits,
+            ..self.to_bytes()
+        }
+    }
+
     pub fn span(&self) -> ByteSpan<T> {
         ByteSpan {
             offset: self.offset,
@@ -114,12 +278,42 @@ pub struct ByteSpan<T> {
     ty: PhantomData<fn() -> T>,
 }
 
+impl<T> Default for ByteSpan<T> {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            len: 0,
+            ty: PhantomData,
+        }
+    }
+}
+
 impl<T> ByteSpan<T> {
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
     pub fn as_bytes<'a>(&self, origin: &'a [u8]) -> &'a [u8] {
         let end = self.offset + self.len;
         &origin[self.offset..end]
     }
 
+    pub fn as_slice<'a>(&self, origin: &'a [u8]) -> ByteSlice<'a, T> {
+        ByteSlice {
+            slice: self.as_bytes(origin),
+            offset: self.offset,
+            ty: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn cast<U>(self) -> ByteSpan<U> {
         ByteSpan {
             offset: self.offset,
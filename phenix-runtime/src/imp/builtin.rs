@@ -1,9 +1,14 @@
-use std::{io, mem};
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::mem;
 
 use crate::{
     base,
     bytes::{ByteSlice, Bytes},
-    Decodable, DecodingError, Encodable, Float, InvalidPrefix, Sint, Uint, UnexpectedEof,
+    io, Decodable, DecodableRef, DecodingError, Encodable, Float, Sint, Uint, UnexpectedEof,
     ValueError,
 };
 
@@ -13,9 +18,15 @@ macro_rules! impl_num {
             fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
                 writer.write_all(&self.to_le_bytes())
             }
+
+            fn encoded_len(&self) -> usize {
+                mem::size_of::<$num>()
+            }
         }
 
         impl Decodable for $num {
+            const FIXED_SIZE: Option<usize> = Some(mem::size_of::<$num>());
+
             fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
                 bytes
                     .consume_bytes(mem::size_of::<$num>())
@@ -33,10 +44,18 @@ macro_rules! impl_num {
                 bytes: &mut Bytes<'a>,
                 n: usize,
             ) -> Result<ByteSlice<'a, Self>, DecodingError> {
-                bytes
-                    .consume_slice(n * mem::size_of::<$num>())
+                n.checked_mul(mem::size_of::<$num>())
+                    .and_then(|len| bytes.consume_slice(len))
                     .ok_or_else(|| UnexpectedEof::new(bytes).into())
             }
+
+            fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+                Self::recognize(bytes).map(|_| ())
+            }
+
+            fn validate_many(bytes: &mut Bytes<'_>, n: usize) -> Result<(), DecodingError> {
+                Self::recognize_many(bytes, n).map(|_| ())
+            }
         }
     };
 }
@@ -62,9 +81,19 @@ impl Encodable for bool {
     fn encode_many<W: io::Write>(values: &[Self], writer: &mut W) -> io::Result<()> {
         base::bool::encode_many(values, writer)
     }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
+
+    fn encoded_len_many(values: &[Self]) -> usize {
+        base::bool::encoded_len_many(values.len())
+    }
 }
 
 impl Decodable for bool {
+    const IS_PACKED: bool = true;
+
     fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
         base::bool::decode(bytes)
     }
@@ -87,6 +116,14 @@ impl Decodable for bool {
     ) -> Result<ByteSlice<'a, Self>, DecodingError> {
         base::bool::recognize_many(bytes, n)
     }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        base::bool::decode(bytes).map(|_| ())
+    }
+
+    fn validate_many(bytes: &mut Bytes<'_>, n: usize) -> Result<(), DecodingError> {
+        base::bool::recognize_many(bytes, n).map(|_| ())
+    }
 }
 
 impl Encodable for String {
@@ -96,27 +133,23 @@ impl Encodable for String {
 
         writer.write_all(self.as_bytes())
     }
+
+    fn encoded_len(&self) -> usize {
+        self.as_str().encoded_len()
+    }
 }
 
 impl Decodable for String {
     fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
-        let len = base::uint::decode(bytes).map_err(|_| InvalidPrefix::new(bytes))?;
-        let mark = bytes.mark();
-
-        let bytes = bytes
-            .consume_bytes(len as usize)
-            .ok_or_else(|| UnexpectedEof::new(bytes))?;
-
-        match std::str::from_utf8(bytes) {
-            Ok(string) => Ok(string.to_string()),
-            Err(error) => Err(ValueError::new_at(mark.to_usize() + error.valid_up_to()).into()),
-        }
+        let string = decode_str(bytes)?;
+        bytes.allocate(string.len())?;
+        Ok(string.to_string())
     }
 
     fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
         let mark = bytes.mark();
 
-        let len = base::uint::decode(bytes).map_err(|_| InvalidPrefix::new(bytes))?;
+        let len = base::utils::decode_len(bytes)?;
         let len = len as usize;
 
         if bytes.len() < len {
@@ -126,6 +159,10 @@ impl Decodable for String {
         bytes.consume(len);
         Ok(bytes.take_slice_from(mark))
     }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        decode_str(bytes).map(|_| ())
+    }
 }
 
 impl Encodable for &str {
@@ -135,12 +172,112 @@ impl Encodable for &str {
 
         writer.write_all(self.as_bytes())
     }
+
+    fn encoded_len(&self) -> usize {
+        base::uint::encoded_len(self.len() as u64) + self.len()
+    }
+}
+
+impl<'a, 'b: 'a> DecodableRef<'b> for &'a str {
+    fn decode_ref(bytes: &mut Bytes<'b>) -> Result<Self, DecodingError> {
+        decode_str(bytes)
+    }
+}
+
+impl Encodable for Cow<'_, str> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.as_ref().encode(writer)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.as_ref().encoded_len()
+    }
+}
+
+impl<'a, 'b: 'a> DecodableRef<'b> for Cow<'a, str> {
+    fn decode_ref(bytes: &mut Bytes<'b>) -> Result<Self, DecodingError> {
+        decode_str(bytes).map(Cow::Borrowed)
+    }
+}
+
+// The encoding is the same as of `Vec<u8>`.
+impl<'a, 'b: 'a> DecodableRef<'b> for &'a [u8] {
+    fn decode_ref(bytes: &mut Bytes<'b>) -> Result<Self, DecodingError> {
+        let len = base::utils::decode_len(bytes)?;
+        bytes.check_collection_len(len as usize)?;
+
+        bytes
+            .consume_bytes(len as usize)
+            .ok_or_else(|| UnexpectedEof::new(bytes).into())
+    }
+}
+
+fn decode_str<'a>(bytes: &mut Bytes<'a>) -> Result<&'a str, DecodingError> {
+    let len = base::utils::decode_len(bytes)?;
+    bytes.check_string_len(len as usize)?;
+    let mark = bytes.mark();
+
+    let bytes = bytes
+        .consume_bytes(len as usize)
+        .ok_or_else(|| UnexpectedEof::new(bytes))?;
+
+    core::str::from_utf8(bytes)
+        .map_err(|error| ValueError::new_at(mark.to_usize() + error.valid_up_to()).into())
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Encodable),+> Encodable for ($($name,)+) {
+            fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+                $(self.$index.encode(writer)?;)+
+                Ok(())
+            }
+
+            fn encoded_len(&self) -> usize {
+                0 $(+ self.$index.encoded_len())+
+            }
+        }
+
+        impl<$($name: Decodable),+> Decodable for ($($name,)+) {
+            fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
+                Ok(($($name::decode(bytes)?,)+))
+            }
+
+            fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
+                let mark = bytes.mark();
+                $($name::recognize(bytes)?;)+
+                Ok(bytes.take_slice_from(mark))
+            }
+
+            fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+                $($name::validate(bytes)?;)+
+                Ok(())
+            }
+        }
+    };
 }
 
+impl_tuple!(A 0);
+impl_tuple!(A 0, B 1);
+impl_tuple!(A 0, B 1, C 2);
+impl_tuple!(A 0, B 1, C 2, D 3);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
 impl Encodable for Uint {
     fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
         base::uint::encode(self.0, writer)
     }
+
+    fn encoded_len(&self) -> usize {
+        base::uint::encoded_len(self.0)
+    }
 }
 
 impl Decodable for Uint {
@@ -151,12 +288,20 @@ impl Decodable for Uint {
     fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
         base::uint::recognize(bytes).map(ByteSlice::cast)
     }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        base::uint::decode(bytes).map(|_| ())
+    }
 }
 
 impl Encodable for Sint {
     fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
         base::sint::encode(self.0, writer)
     }
+
+    fn encoded_len(&self) -> usize {
+        base::sint::encoded_len(self.0)
+    }
 }
 
 impl Decodable for Sint {
@@ -167,12 +312,20 @@ impl Decodable for Sint {
     fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
         base::sint::recognize(bytes).map(ByteSlice::cast)
     }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        base::sint::decode(bytes).map(|_| ())
+    }
 }
 
 impl Encodable for Float {
     fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
         base::float::encode(self.0, writer)
     }
+
+    fn encoded_len(&self) -> usize {
+        base::float::encoded_len(self.0)
+    }
 }
 
 impl Decodable for Float {
@@ -183,4 +336,8 @@ impl Decodable for Float {
     fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
         base::float::recognize(bytes).map(ByteSlice::cast)
     }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        base::float::decode(bytes).map(|_| ())
+    }
 }
@@ -1,9 +1,16 @@
-use std::{io, marker::PhantomData};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{marker::PhantomData, mem};
+#[cfg(feature = "std")]
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+};
 
 use crate::{
     base,
-    bytes::{ByteSlice, Bytes},
-    Decodable, DecodingError, Encodable, InvalidPrefix, Stream,
+    bytes::{ByteSlice, Bytes, Mark},
+    io, Decodable, DecodingError, Encodable, FramedSpan, FramedStream, Stream, UnexpectedEof,
+    ValueError,
 };
 
 impl<T: Encodable> Encodable for Vec<T> {
@@ -13,15 +20,29 @@ impl<T: Encodable> Encodable for Vec<T> {
 
         T::encode_many(self, writer)
     }
+
+    fn encoded_len(&self) -> usize {
+        self.as_slice().encoded_len()
+    }
 }
 
 impl<T: Decodable> Decodable for Vec<T> {
     fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
-        let len = base::uint::decode(bytes).map_err(|_| InvalidPrefix::new(bytes))?;
+        let len = base::utils::decode_len(bytes)?;
         let len = len as usize;
 
-        let mut values = Vec::with_capacity(len);
-        T::decode_many(bytes, len, &mut values)?;
+        bytes.check_collection_len(len)?;
+        bytes.allocate(len.saturating_mul(mem::size_of::<T>()))?;
+
+        // The length is not trusted until the values are actually decoded.
+        let mut values = Vec::with_capacity(len.min(bytes.len()));
+        let result = T::decode_many(bytes, len, &mut values);
+
+        // The values decoded so far determine the index of the failing one.
+        #[cfg(feature = "context")]
+        let result = result.map_err(|error| error.in_index(values.len()));
+
+        result?;
 
         Ok(values)
     }
@@ -29,11 +50,19 @@ impl<T: Decodable> Decodable for Vec<T> {
     fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
         let mark = bytes.mark();
 
-        let len = base::uint::decode(bytes).map_err(|_| InvalidPrefix::new(bytes))?;
+        let len = base::utils::decode_len(bytes)?;
         T::recognize_many(bytes, len as usize)?;
 
         Ok(bytes.take_slice_from(mark))
     }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        let len = base::utils::decode_len(bytes)?;
+        let len = len as usize;
+
+        bytes.check_collection_len(len)?;
+        T::validate_many(bytes, len)
+    }
 }
 
 impl<T: Encodable> Encodable for &[T] {
@@ -43,18 +72,288 @@ impl<T: Encodable> Encodable for &[T] {
 
         T::encode_many(self, writer)
     }
+
+    fn encoded_len(&self) -> usize {
+        base::uint::encoded_len(self.len() as u64) + T::encoded_len_many(self)
+    }
+}
+
+// Arrays have no length prefix, the items are encoded as if they were in a
+// vector.
+impl<T: Encodable, const N: usize> Encodable for [T; N] {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        T::encode_many(self, writer)
+    }
+
+    fn encoded_len(&self) -> usize {
+        T::encoded_len_many(self)
+    }
+}
+
+impl<T: Decodable, const N: usize> Decodable for [T; N] {
+    const FIXED_SIZE: Option<usize> = match T::FIXED_SIZE {
+        Some(size) => Some(size * N),
+        None => None,
+    };
+
+    fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
+        let mut values = Vec::with_capacity(N);
+        let result = T::decode_many(bytes, N, &mut values);
+
+        #[cfg(feature = "context")]
+        let result = result.map_err(|error| error.in_index(values.len()));
+
+        result?;
+
+        match values.try_into() {
+            Ok(values) => Ok(values),
+            Err(_) => unreachable!("exactly N values are decoded"),
+        }
+    }
+
+    fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
+        T::recognize_many(bytes, N).map(ByteSlice::cast)
+    }
+
+    fn recognize_many<'a>(
+        bytes: &mut Bytes<'a>,
+        n: usize,
+    ) -> Result<ByteSlice<'a, Self>, DecodingError> {
+        let mark = bytes.mark();
+
+        for _ in 0..n {
+            T::recognize_many(bytes, N)?;
+        }
+
+        Ok(bytes.take_slice_from(mark))
+    }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        T::validate_many(bytes, N)
+    }
+
+    fn validate_many(bytes: &mut Bytes<'_>, n: usize) -> Result<(), DecodingError> {
+        for _ in 0..n {
+            T::validate_many(bytes, N)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<K: Encodable, V: Encodable> Encodable for BTreeMap<K, V> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        encode_map(self.len(), self.iter(), writer)
+    }
+
+    fn encoded_len(&self) -> usize {
+        encoded_len_map(self.len(), self.iter())
+    }
+}
+
+impl<K: Decodable + Ord, V: Decodable> Decodable for BTreeMap<K, V> {
+    fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
+        let mut map = BTreeMap::new();
+        decode_map(bytes, |key, value| {
+            map.insert(key, value);
+        })?;
+        Ok(map)
+    }
+
+    fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
+        recognize_map::<K, V>(bytes).map(ByteSlice::cast)
+    }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        validate_map::<K, V>(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Encodable, V: Encodable, S> Encodable for HashMap<K, V, S> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        encode_map(self.len(), self.iter(), writer)
+    }
+
+    fn encoded_len(&self) -> usize {
+        encoded_len_map(self.len(), self.iter())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> Decodable for HashMap<K, V, S>
+where
+    K: Decodable + Eq + Hash,
+    V: Decodable,
+    S: BuildHasher + Default,
+{
+    fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
+        let mut map = HashMap::default();
+        decode_map(bytes, |key, value| {
+            map.insert(key, value);
+        })?;
+        Ok(map)
+    }
+
+    fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
+        recognize_map::<K, V>(bytes).map(ByteSlice::cast)
+    }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        validate_map::<K, V>(bytes)
+    }
+}
+
+// Maps are encoded as the number of entries followed by the entries, each
+// being the key followed by the value. The entries are sorted by the encoded
+// keys, so that the encoding does not depend on the order of iteration (of
+// `HashMap` in particular) and can be checked without decoding the keys.
+fn encode_map<'a, K, V, W, I>(len: usize, entries: I, writer: &mut W) -> io::Result<()>
+where
+    K: Encodable + 'a,
+    V: Encodable + 'a,
+    W: io::Write,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    let mut entries = entries
+        .map(|(key, value)| {
+            let mut encoded = Vec::new();
+            key.encode(&mut encoded)?;
+            Ok((encoded, value))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    base::uint::encode(len as u64, writer)?;
+
+    for (key, value) in entries {
+        writer.write_all(&key)?;
+        value.encode(writer)?;
+    }
+
+    Ok(())
+}
+
+fn encoded_len_map<'a, K, V, I>(len: usize, entries: I) -> usize
+where
+    K: Encodable + 'a,
+    V: Encodable + 'a,
+    I: Iterator<Item = (&'a K, &'a V)>,
+{
+    entries.fold(base::uint::encoded_len(len as u64), |acc, (key, value)| {
+        acc + key.encoded_len() + value.encoded_len()
+    })
+}
+
+// Keys that are not strictly ascending in their encoding (including duplicate
+// keys) are an error in strict decoding, see `encode_map`.
+fn decode_map<'a, K, V, F>(bytes: &mut Bytes<'a>, mut insert: F) -> Result<(), DecodingError>
+where
+    K: Decodable,
+    V: Decodable,
+    F: FnMut(K, V),
+{
+    let len = base::utils::decode_len(bytes)?;
+    let len = len as usize;
+
+    bytes.check_collection_len(len)?;
+    bytes.allocate(len.saturating_mul(mem::size_of::<(K, V)>()))?;
+
+    let mut previous = None;
+
+    for _index in 0..len {
+        let start = bytes.mark();
+        let entry = K::decode(bytes).and_then(|key| {
+            check_key_order(bytes, start, &mut previous)?;
+            Ok((key, V::decode(bytes)?))
+        });
+
+        #[cfg(feature = "context")]
+        let entry = entry.map_err(|error| error.in_index(_index));
+
+        let (key, value) = entry?;
+        insert(key, value);
+    }
+
+    Ok(())
+}
+
+fn recognize_map<'a, K: Decodable, V: Decodable>(
+    bytes: &mut Bytes<'a>,
+) -> Result<ByteSlice<'a, ()>, DecodingError> {
+    let mark = bytes.mark();
+
+    let len = base::utils::decode_len(bytes)?;
+
+    for _ in 0..len {
+        K::recognize(bytes)?;
+        V::recognize(bytes)?;
+    }
+
+    Ok(bytes.take_slice_from(mark))
+}
+
+fn validate_map<'a, K: Decodable, V: Decodable>(
+    bytes: &mut Bytes<'a>,
+) -> Result<(), DecodingError> {
+    let len = base::utils::decode_len(bytes)?;
+    let len = len as usize;
+
+    bytes.check_collection_len(len)?;
+
+    let mut previous = None;
+
+    for _ in 0..len {
+        let start = bytes.mark();
+        K::validate(bytes)?;
+        check_key_order(bytes, start, &mut previous)?;
+        V::validate(bytes)?;
+    }
+
+    Ok(())
+}
+
+// Checks in strict decoding that the key just read from the start is greater
+// than the previous one, which is then replaced by it.
+fn check_key_order<'a>(
+    bytes: &Bytes<'a>,
+    start: Mark,
+    previous: &mut Option<&'a [u8]>,
+) -> Result<(), DecodingError> {
+    if !bytes.is_strict() {
+        return Ok(());
+    }
+
+    let key = bytes.take_slice_from::<()>(start).as_bytes();
+
+    if previous.is_some_and(|previous| previous >= key) {
+        return Err(ValueError::new_at(start.to_usize()).into());
+    }
+
+    *previous = Some(key);
+    Ok(())
 }
 
 impl<T> Encodable for Stream<T> {
     fn encode<W: io::Write>(&self, _: &mut W) -> io::Result<()> {
         Ok(())
     }
+
+    fn encoded_len(&self) -> usize {
+        0
+    }
 }
 
-impl<T> Decodable for Stream<T> {
+impl<T: Decodable> Decodable for Stream<T> {
     fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
         let offset = bytes.mark();
-        bytes.consume(bytes.len());
+
+        // When reading incrementally, the items were not read yet and are left
+        // in the source.
+        if !bytes.is_partial() {
+            bytes.consume(bytes.len());
+        }
 
         Ok(Self {
             offset: offset.to_usize(),
@@ -63,6 +362,107 @@ impl<T> Decodable for Stream<T> {
     }
 
     fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
-        Ok(bytes.consume_slice(bytes.len()).unwrap())
+        let len = if bytes.is_partial() { 0 } else { bytes.len() };
+        Ok(bytes.consume_slice(len).unwrap())
+    }
+
+    // The items follow until the end of the input.
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        if !bytes.is_partial() {
+            while !bytes.is_empty() {
+                T::validate(bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A decoded stream does not have its items, so it would be encoded without
+// them.
+impl<T: Encodable> Encodable for FramedStream<T> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            FramedStream::Items(items) => {
+                Self::frame_encode(items, writer)?;
+                Self::end_encode(writer)
+            }
+            FramedStream::Decoded(_) => Err(io::invalid_input(
+                "decoded framed stream cannot be encoded without its origin",
+            )),
+        }
+    }
+
+    // The length in the origin for a decoded stream.
+    fn encoded_len(&self) -> usize {
+        match self {
+            FramedStream::Items(items) => {
+                Self::frame_encoded_len(items) + base::uint::encoded_len(0)
+            }
+            FramedStream::Decoded(span) => span.len(),
+        }
+    }
+}
+
+impl<T: Decodable> Decodable for FramedStream<T> {
+    fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
+        let slice = Self::recognize(bytes)?;
+        let span = slice.span();
+
+        Ok(FramedStream::Decoded(FramedSpan::new(
+            span.offset(),
+            span.len(),
+        )))
+    }
+
+    // Only the frame headers are read, the items are skipped.
+    fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
+        let mark = bytes.mark();
+
+        loop {
+            let count = base::utils::decode_len(bytes)?;
+
+            if count == 0 {
+                break;
+            }
+
+            let len = base::utils::decode_len(bytes)?;
+
+            if len > bytes.len() as u64 {
+                return Err(UnexpectedEof::new(bytes).into());
+            }
+
+            bytes.consume(len as usize);
+        }
+
+        Ok(bytes.take_slice_from(mark))
+    }
+
+    // The items of each frame must fill the frame exactly.
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        loop {
+            let count = base::utils::decode_len(bytes)?;
+
+            if count == 0 {
+                break;
+            }
+
+            let len = base::utils::decode_len(bytes)?;
+            let start = bytes.mark();
+
+            if len > bytes.len() as u64 {
+                return Err(UnexpectedEof::new(bytes).into());
+            }
+
+            for _ in 0..count {
+                T::validate(bytes)?;
+            }
+
+            if bytes.mark().to_usize() - start.to_usize() != len as usize {
+                return Err(ValueError::new_at(start.to_usize()).into());
+            }
+        }
+
+        Ok(())
     }
 }
@@ -1,9 +1,10 @@
-use std::{io, marker::PhantomData};
+use core::marker::PhantomData;
 
 use crate::{
     base,
     bytes::{ByteSlice, Bytes},
-    Decodable, DecodingError, Encodable, Flags, IsFlag, UnexpectedEof,
+    io, Decodable, DecodingError, Encodable, Flags, IsFlag, Lazy, UnexpectedEof, UnknownVariant,
+    ValueError,
 };
 
 impl<T: IsFlag> Encodable for Flags<T> {
@@ -14,6 +15,14 @@ impl<T: IsFlag> Encodable for Flags<T> {
 
         writer.write_all(self.flags.as_slice())
     }
+
+    fn encoded_len(&self) -> usize {
+        if T::IS_EXHAUSTIVE {
+            self.flags.len()
+        } else {
+            base::utils::encoded_len_discriminant_relaxed(self.flags.len()) + self.flags.len()
+        }
+    }
 }
 
 impl<T: IsFlag> Decodable for Flags<T> {
@@ -24,6 +33,9 @@ impl<T: IsFlag> Decodable for Flags<T> {
             base::utils::decode_discriminant_relaxed(bytes)?
         };
 
+        bytes.allocate(n_bytes)?;
+        check_padding::<T>(bytes, n_bytes)?;
+
         let flags = bytes
             .consume_bytes(n_bytes)
             .ok_or_else(|| UnexpectedEof::new(bytes))?
@@ -44,6 +56,9 @@ impl<T: IsFlag> Decodable for Flags<T> {
             base::utils::decode_discriminant_relaxed(bytes)?
         };
 
+        bytes.allocate(n_bytes)?;
+        check_padding::<T>(bytes, n_bytes)?;
+
         if bytes.len() >= n_bytes {
             bytes.consume(n_bytes);
             Ok(bytes.take_slice_from(mark))
@@ -51,4 +66,73 @@ impl<T: IsFlag> Decodable for Flags<T> {
             Err(UnexpectedEof::new(bytes).into())
         }
     }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        Self::recognize(bytes).map(|_| ())
+    }
+}
+
+// A decoded lazy value can be encoded only from the original buffer (see
+// `Lazy::encode_from`), which is not available here.
+impl<T: Encodable> Encodable for Lazy<T> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self.value() {
+            Some(value) => value.encode(writer),
+            None => Err(io::invalid_input(
+                "decoded lazy value cannot be encoded without its origin",
+            )),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self.value() {
+            Some(value) => value.encoded_len(),
+            None => self.span().len(),
+        }
+    }
+}
+
+impl<T: Decodable> Decodable for Lazy<T> {
+    fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError> {
+        let slice = T::recognize(bytes)?;
+
+        Ok(Self {
+            value: None,
+            span: slice.span(),
+        })
+    }
+
+    fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError> {
+        T::recognize(bytes).map(ByteSlice::cast)
+    }
+
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        T::validate(bytes)
+    }
+}
+
+// Only the body is encoded, the discriminant and the length prefix are written
+// by the enum.
+impl Encodable for UnknownVariant {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.body())
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.body().len()
+    }
+}
+
+// Unknown flags of non-exhaustive types may come from a newer version, so only
+// the exhaustive ones are checked.
+fn check_padding<T: IsFlag>(bytes: &Bytes<'_>, n_bytes: usize) -> Result<(), DecodingError> {
+    if bytes.is_strict()
+        && T::IS_EXHAUSTIVE
+        && bytes.len() >= n_bytes
+        && base::bool::has_padding(&bytes[..n_bytes], T::COUNT)
+    {
+        Err(ValueError::new(bytes).into())
+    } else {
+        Ok(())
+    }
 }
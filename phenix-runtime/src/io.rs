@@ -0,0 +1,135 @@
+// Minimal sink for the encoded data. With `std`, it is implemented for all
+// `std::io::Write` types and the errors are the standard I/O errors.
+
+#[cfg(feature = "std")]
+pub use std::io::Error;
+
+#[cfg(not(feature = "std"))]
+pub use self::no_std::Error;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+// The value cannot be encoded, for example a decoded value that refers to its
+// origin.
+#[cfg(feature = "std")]
+pub(crate) fn invalid_input(message: &'static str) -> Error {
+    Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn invalid_input(_: &'static str) -> Error {
+    Error::InvalidInput
+}
+
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Write for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        std::io::Write::write_all(self, buf)
+    }
+}
+
+// Counts the written bytes without storing them.
+#[derive(Debug, Default)]
+pub(crate) struct Counter {
+    pub len: usize,
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for Counter {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.len += buf.len();
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    use super::{Result, Write};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Error {
+        WriteZero,
+        InvalidInput,
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::WriteZero => write!(f, "failed to write whole buffer"),
+                Error::InvalidInput => write!(f, "value cannot be encoded"),
+            }
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    // Writes to the beginning of the slice and advances it, fails if there is
+    // not enough space left.
+    impl Write for &mut [u8] {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            if buf.len() > self.len() {
+                return Err(Error::WriteZero);
+            }
+
+            let (head, tail) = core::mem::take(self).split_at_mut(buf.len());
+            head.copy_from_slice(buf);
+            *self = tail;
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    use super::*;
+    use crate::{base, Encodable};
+
+    #[test]
+    fn slice() {
+        let mut buf = [0; 4];
+        let mut sink = &mut buf[..];
+
+        "hi".encode(&mut sink).unwrap();
+        assert_eq!(sink.len(), 1);
+        assert_eq!(base::uint::encode(300, &mut sink), Err(Error::WriteZero));
+        assert_eq!(buf[..3], [2, 104, 105]);
+    }
+
+    #[test]
+    fn vec() {
+        let mut buf = Vec::new();
+        true.encode(&mut buf).unwrap();
+        "hi".encode(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 104, 105]);
+    }
+}
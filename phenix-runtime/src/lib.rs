@@ -1,19 +1,70 @@
-use std::{fmt, io, marker::PhantomData};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "context")]
+use alloc::boxed::Box;
+use alloc::{vec, vec::Vec};
+use core::{fmt, iter, marker::PhantomData, ops::Range};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "futures")]
+pub mod async_io;
 pub mod base;
 pub mod bytes;
+pub mod io;
+#[cfg(feature = "std")]
+pub mod read;
 pub mod traits;
 
 mod imp;
 
 pub use phenix_runtime_macros::{by_parts, Decodable, Encodable, IsFlag};
-pub use traits::{Decodable, Encodable, IsFlag};
+pub use traits::{Decodable, DecodableRef, Encodable, IsFlag};
+
+#[cfg(feature = "futures")]
+pub use async_io::{AsyncDecodable, AsyncEncodable};
+
+#[cfg(feature = "futures")]
+#[doc(hidden)]
+pub use futures;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+    #[cfg(feature = "std")]
+    pub use std::collections::HashMap;
+
+    // Makes a returned `impl Trait` capture a lifetime that does not appear in
+    // its other bounds.
+    pub trait Captures<'a> {}
+
+    impl<'a, T: ?Sized> Captures<'a> for T {}
+}
 
 pub mod prelude {
-    pub use crate::{Decodable, Encodable, IsFlag};
+    pub use crate::{Decodable, DecodableRef, Encodable, IsFlag};
+
+    #[cfg(feature = "futures")]
+    pub use crate::{AsyncDecodable, AsyncEncodable};
+}
+
+// Checks that the buffer contains exactly one value of given type in its
+// canonical encoding.
+pub fn validate<T: Decodable>(bytes: &[u8]) -> Result<(), DecodingError> {
+    let mut limits = bytes::DecodeLimits::default();
+    limits.strict();
+
+    let mut bytes = bytes::Bytes::with_limits(bytes, limits);
+    T::validate(&mut bytes)?;
+
+    if bytes.is_empty() {
+        Ok(())
+    } else {
+        Err(ValueError::new(&bytes).into())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -96,10 +147,66 @@ impl fmt::Display for ValueError {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Alloc,
+    CollectionLen,
+    StringLen,
+    Depth,
+    // Bytes buffered for a single value when reading (see `read::ReadBytes`).
+    Buffer,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Alloc => write!(f, "allocation"),
+            Limit::CollectionLen => write!(f, "collection length"),
+            Limit::StringLen => write!(f, "string length"),
+            Limit::Depth => write!(f, "nesting depth"),
+            Limit::Buffer => write!(f, "buffer size"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LimitExceeded {
+    pos: usize,
+    limit: Limit,
+}
+
+impl LimitExceeded {
+    pub fn new(bytes: &bytes::Bytes<'_>, limit: Limit) -> Self {
+        Self::new_at(bytes.mark().to_usize(), limit)
+    }
+
+    pub fn new_at(pos: usize, limit: Limit) -> Self {
+        Self { pos, limit }
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn limit(&self) -> Limit {
+        self.limit
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} limit exceeded at byte {}", self.limit, self.pos)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DecodingError {
     UnexpectedEof(UnexpectedEof),
     InvalidPrefix(InvalidPrefix),
     ValueError(ValueError),
+    LimitExceeded(LimitExceeded),
+    // Attached by the derived implementations.
+    #[cfg(feature = "context")]
+    Context(Box<ErrorContext>),
 }
 
 impl From<UnexpectedEof> for DecodingError {
@@ -120,12 +227,208 @@ impl From<ValueError> for DecodingError {
     }
 }
 
+impl From<LimitExceeded> for DecodingError {
+    fn from(error: LimitExceeded) -> Self {
+        DecodingError::LimitExceeded(error)
+    }
+}
+
+impl DecodingError {
+    pub fn pos(&self) -> usize {
+        match self {
+            DecodingError::UnexpectedEof(error) => error.pos(),
+            DecodingError::InvalidPrefix(error) => error.pos(),
+            DecodingError::ValueError(error) => error.pos(),
+            DecodingError::LimitExceeded(error) => error.pos(),
+            #[cfg(feature = "context")]
+            DecodingError::Context(context) => context.error.pos(),
+        }
+    }
+
+    // Returns the error without the context.
+    pub fn root(&self) -> &DecodingError {
+        #[cfg(feature = "context")]
+        if let DecodingError::Context(context) = self {
+            return &context.error;
+        }
+
+        self
+    }
+
+    #[cfg(feature = "context")]
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            DecodingError::Context(context) => Some(context),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "context")]
+    pub fn in_field(self, name: &'static str) -> Self {
+        self.with_context(|context| context.path.push(PathSegment::Field(name)))
+    }
+
+    #[cfg(feature = "context")]
+    pub fn in_index(self, index: usize) -> Self {
+        self.with_context(|context| context.path.push(PathSegment::Index(index)))
+    }
+
+    #[cfg(feature = "context")]
+    pub fn in_variant(self, name: &'static str) -> Self {
+        self.with_context(|context| context.path.push(PathSegment::Variant(name)))
+    }
+
+    // The outermost type is the root of the path.
+    #[cfg(feature = "context")]
+    pub fn in_type(self, name: &'static str) -> Self {
+        self.with_context(|context| context.ty = Some(name))
+    }
+
+    // Only the innermost expectation is kept.
+    #[cfg(feature = "context")]
+    pub fn expected(self, expected: &'static str) -> Self {
+        self.with_context(|context| {
+            context.expected.get_or_insert(expected);
+        })
+    }
+
+    #[cfg(feature = "context")]
+    fn with_context(self, f: impl FnOnce(&mut ErrorContext)) -> Self {
+        let mut context = match self {
+            DecodingError::Context(context) => context,
+            error => Box::new(ErrorContext {
+                error,
+                ty: None,
+                path: Vec::new(),
+                expected: None,
+            }),
+        };
+
+        f(&mut context);
+        DecodingError::Context(context)
+    }
+}
+
 impl fmt::Display for DecodingError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             DecodingError::UnexpectedEof(error) => fmt::Display::fmt(error, f),
             DecodingError::InvalidPrefix(error) => fmt::Display::fmt(error, f),
             DecodingError::ValueError(error) => fmt::Display::fmt(error, f),
+            DecodingError::LimitExceeded(error) => fmt::Display::fmt(error, f),
+            #[cfg(feature = "context")]
+            DecodingError::Context(context) => fmt::Display::fmt(context, f),
+        }
+    }
+}
+
+#[cfg(feature = "context")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ErrorContext {
+    // Never a context itself.
+    error: DecodingError,
+    ty: Option<&'static str>,
+    // The innermost segment is first.
+    path: Vec<PathSegment>,
+    expected: Option<&'static str>,
+}
+
+#[cfg(feature = "context")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Field(&'static str),
+    Index(usize),
+    Variant(&'static str),
+}
+
+#[cfg(feature = "context")]
+impl ErrorContext {
+    pub fn error(&self) -> &DecodingError {
+        &self.error
+    }
+
+    pub fn ty(&self) -> Option<&'static str> {
+        self.ty
+    }
+
+    // The outermost segment is first.
+    pub fn path(&self) -> impl Iterator<Item = PathSegment> + '_ {
+        self.path.iter().rev().copied()
+    }
+
+    pub fn expected(&self) -> Option<&'static str> {
+        self.expected
+    }
+
+    pub fn display_path(&self) -> impl fmt::Display + '_ {
+        DisplayPath(self)
+    }
+}
+
+#[cfg(feature = "context")]
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+
+        if self.ty.is_some() || !self.path.is_empty() {
+            write!(f, " in {}", self.display_path())?;
+        }
+
+        if let Some(expected) = self.expected {
+            write!(f, " (expected {})", expected)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "context")]
+struct DisplayPath<'a>(&'a ErrorContext);
+
+#[cfg(feature = "context")]
+impl fmt::Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut is_first = true;
+
+        if let Some(ty) = self.0.ty {
+            write!(f, "{}", ty)?;
+            is_first = false;
+        }
+
+        for segment in self.0.path() {
+            match segment {
+                PathSegment::Field(name) if is_first => write!(f, "{}", name)?,
+                PathSegment::Field(name) => write!(f, ".{}", name)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+                PathSegment::Variant(name) => write!(f, "::{}", name)?,
+            }
+
+            is_first = false;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnexpectedEof {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidPrefix {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValueError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LimitExceeded {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodingError {
+    #[cfg(feature = "context")]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodingError::Context(context) => Some(&context.error),
+            _ => None,
         }
     }
 }
@@ -228,6 +531,15 @@ impl<T: Decodable> Stream<T> {
     }
 }
 
+impl<'a, T: Decodable> bytes::ByteSlice<'a, Stream<T>> {
+    pub fn iter(&self) -> StreamIter<'a, T> {
+        StreamIter {
+            bytes: self.to_bytes(),
+            ty: PhantomData,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct StreamIter<'a, T> {
     bytes: bytes::Bytes<'a>,
@@ -246,6 +558,865 @@ impl<'a, T: Decodable> Iterator for StreamIter<'a, T> {
     }
 }
 
+impl<'a, T: Decodable> bytes::ByteSlice<'a, Vec<T>> {
+    // Reads the length prefix, the items are recognized on demand. Vectors of
+    // packed items must be decoded as a whole, which is checked at compile
+    // time.
+    pub fn items(&self) -> Result<VectorSlice<'a, T>, DecodingError> {
+        let () = NotPacked::<T>::CHECK;
+
+        let mut bytes = self.to_bytes();
+        let len = base::utils::decode_len(&mut bytes)?;
+
+        Ok(VectorSlice {
+            slice: *self,
+            start: self.as_bytes().len() - bytes.len(),
+            len: len as usize,
+            ty: PhantomData,
+        })
+    }
+
+    pub fn iter(&self) -> VectorIter<'a, T> {
+        match self.items() {
+            Ok(items) => items.iter(),
+            Err(error) => VectorIter {
+                bytes: bytes::Bytes::new(&[]),
+                remaining: 0,
+                error: Some(error),
+                ty: PhantomData,
+            },
+        }
+    }
+}
+
+struct NotPacked<T>(PhantomData<T>);
+
+impl<T: Decodable> NotPacked<T> {
+    const CHECK: () = assert!(
+        !T::IS_PACKED,
+        "items of packed vectors cannot be recognized"
+    );
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VectorSlice<'a, T> {
+    slice: bytes::ByteSlice<'a, Vec<T>>,
+    // Position of the first item in the bytes, right after the length prefix.
+    start: usize,
+    len: usize,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Decodable> VectorSlice<'a, T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<Result<bytes::ByteSlice<'a, T>, DecodingError>> {
+        self.iter().nth(index)
+    }
+
+    pub fn iter(&self) -> VectorIter<'a, T> {
+        let mut bytes = self.slice.to_bytes();
+        bytes.consume(self.start);
+
+        VectorIter {
+            bytes,
+            remaining: self.len,
+            error: None,
+            ty: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Decodable> IntoIterator for VectorSlice<'a, T> {
+    type Item = Result<bytes::ByteSlice<'a, T>, DecodingError>;
+    type IntoIter = VectorIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Debug)]
+pub struct VectorIter<'a, T> {
+    bytes: bytes::Bytes<'a>,
+    remaining: usize,
+    // Reported as the only item if the length prefix is invalid.
+    error: Option<DecodingError>,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Decodable> Iterator for VectorIter<'a, T> {
+    type Item = Result<bytes::ByteSlice<'a, T>, DecodingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let item = T::recognize(&mut self.bytes);
+        // Nothing can be recognized after an error.
+        self.remaining = if item.is_ok() { self.remaining - 1 } else { 0 };
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+
+    // Items of a fixed size are skipped without being recognized.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        match T::FIXED_SIZE {
+            Some(size) if self.error.is_none() => {
+                let n = n.min(self.remaining);
+                self.bytes
+                    .consume(n.saturating_mul(size).min(self.bytes.len()));
+                self.remaining -= n;
+                self.next()
+            }
+            _ => {
+                for _ in 0..n {
+                    let _ = self.next()?;
+                }
+
+                self.next()
+            }
+        }
+    }
+}
+
+impl<T: Decodable> ExactSizeIterator for VectorIter<'_, T> {
+    fn len(&self) -> usize {
+        self.remaining + self.error.is_some() as usize
+    }
+}
+
+// Stream of items split into frames, each prefixed with the number of items
+// and their length in bytes. The stream is terminated with an empty frame, so
+// it does not need to be the last thing in the input.
+//
+// The items are held only for encoding. A decoded stream refers to its items
+// in the origin and cannot be encoded again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FramedStream<T> {
+    Items(Vec<T>),
+    Decoded(FramedSpan<T>),
+}
+
+impl<T> FramedStream<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        FramedStream::Items(items)
+    }
+
+    pub fn items(&self) -> Option<&[T]> {
+        match self {
+            FramedStream::Items(items) => Some(items),
+            FramedStream::Decoded(_) => None,
+        }
+    }
+
+    pub fn into_items(self) -> Option<Vec<T>> {
+        match self {
+            FramedStream::Items(items) => Some(items),
+            FramedStream::Decoded(_) => None,
+        }
+    }
+
+    pub fn span(&self) -> Option<&FramedSpan<T>> {
+        match self {
+            FramedStream::Items(_) => None,
+            FramedStream::Decoded(span) => Some(span),
+        }
+    }
+}
+
+impl<T> Default for FramedStream<T> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T> From<Vec<T>> for FramedStream<T> {
+    fn from(items: Vec<T>) -> Self {
+        Self::new(items)
+    }
+}
+
+impl<T: Encodable> FramedStream<T> {
+    // Encodes a single frame of the stream. Must be followed by other frames
+    // or `end_encode`.
+    pub fn frame_encode<W: io::Write>(items: &[T], writer: &mut W) -> io::Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        // The items are encoded one by one so that they can be recognized
+        // individually.
+        let mut buf = Vec::new();
+
+        for item in items {
+            item.encode(&mut buf)?;
+        }
+
+        encode_frame(items.len(), &buf, writer)
+    }
+
+    pub fn frame_encoded_len(items: &[T]) -> usize {
+        if items.is_empty() {
+            return 0;
+        }
+
+        let len = items.iter().map(T::encoded_len).sum::<usize>();
+
+        base::uint::encoded_len(items.len() as u64) + base::uint::encoded_len(len as u64) + len
+    }
+
+    pub fn end_encode<W: io::Write>(writer: &mut W) -> io::Result<()> {
+        base::uint::encode(0, writer)
+    }
+}
+
+fn encode_frame<W: io::Write>(count: usize, items: &[u8], writer: &mut W) -> io::Result<()> {
+    base::uint::encode(count as u64, writer)?;
+    base::uint::encode(items.len() as u64, writer)?;
+    writer.write_all(items)
+}
+
+// Location of a decoded framed stream in the origin, the items are read from
+// there on demand.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct FramedSpan<T> {
+    offset: usize,
+    len: usize,
+    ty: PhantomData<fn() -> T>,
+}
+
+// Not derived, because that would require `T: Copy`.
+impl<T> Clone for FramedSpan<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for FramedSpan<T> {}
+
+impl<T> FramedSpan<T> {
+    pub fn new(offset: usize, len: usize) -> Self {
+        Self {
+            offset,
+            len,
+            ty: PhantomData,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Length of the encoded stream in the origin.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: Decodable> FramedSpan<T> {
+    pub fn iter<'a>(&self, origin: &'a [u8]) -> Result<FramedStreamIter<'a, T>, DecodingError> {
+        let mut bytes = bytes::Bytes::new(origin_until(origin, self.offset + self.len)?);
+        bytes.consume(self.offset);
+
+        Ok(FramedStreamIter {
+            bytes,
+            remaining: 0,
+            limit: usize::MAX,
+            ty: PhantomData,
+        })
+    }
+
+    pub fn collect(&self, origin: &[u8]) -> Result<Vec<T>, DecodingError> {
+        self.iter(origin)?.map(|value| value?.decode()).collect()
+    }
+
+    // Builds the index of a stream that was not written with one by reading
+    // the frame headers, each frame is an entry of the index.
+    pub fn index(&self, origin: &[u8]) -> Result<StreamIndex<T>, DecodingError> {
+        let mut bytes = bytes::Bytes::new(origin_until(origin, self.offset + self.len)?);
+        bytes.consume(self.offset);
+
+        let mut entries = Vec::new();
+        let mut len = 0;
+
+        loop {
+            let count = base::uint::decode(&mut bytes)? as usize;
+
+            if count == 0 {
+                break;
+            }
+
+            let byte_len = base::uint::decode(&mut bytes)? as usize;
+
+            entries.push(IndexEntry {
+                first: len,
+                offset: bytes.mark().to_usize() - self.offset,
+                frame: Some(count),
+            });
+
+            bytes
+                .consume_bytes(byte_len)
+                .ok_or_else(|| UnexpectedEof::new(&bytes))?;
+            len += count;
+        }
+
+        Ok(StreamIndex {
+            entries,
+            len,
+            ty: PhantomData,
+        })
+    }
+
+    pub fn get<'a>(
+        &self,
+        origin: &'a [u8],
+        index: &StreamIndex<T>,
+        n: usize,
+    ) -> Result<Option<bytes::ByteSlice<'a, T>>, DecodingError> {
+        match n.checked_add(1) {
+            Some(end) => self.range(origin, index, n..end)?.next().transpose(),
+            None => Ok(None),
+        }
+    }
+
+    // Only the items preceding the start in its frame are read. Disjoint
+    // ranges can be processed independently.
+    pub fn range<'a>(
+        &self,
+        origin: &'a [u8],
+        index: &StreamIndex<T>,
+        range: Range<usize>,
+    ) -> Result<FramedStreamIter<'a, T>, DecodingError> {
+        let end = self.offset + self.len;
+        let mut bytes = bytes::Bytes::new(origin_until(origin, end)?);
+        let (entry, range) = index.seek(range);
+
+        let remaining = match entry {
+            Some(entry) => {
+                // The index may be decoded, so its offsets are not trusted.
+                self.offset
+                    .checked_add(entry.offset)
+                    .and_then(|offset| bytes.consume_bytes(offset))
+                    .ok_or_else(|| UnexpectedEof::new_at(end))?;
+
+                let mut remaining = entry.frame.unwrap_or(0);
+
+                for _ in entry.first..range.start {
+                    T::recognize(&mut bytes)?;
+                    remaining = remaining.saturating_sub(1);
+                }
+
+                remaining
+            }
+            None => {
+                bytes.consume(end);
+                0
+            }
+        };
+
+        Ok(FramedStreamIter {
+            bytes,
+            remaining,
+            limit: range.len(),
+            ty: PhantomData,
+        })
+    }
+}
+
+// The origin up to the end of a stream, which is missing if the origin is not
+// the one the stream was decoded from.
+fn origin_until(origin: &[u8], end: usize) -> Result<&[u8], DecodingError> {
+    origin
+        .get(..end)
+        .ok_or_else(|| UnexpectedEof::new_at(origin.len()).into())
+}
+
+// Sparse index of a stream that allows to access the items without reading the
+// stream from the beginning. It is written by `StreamWriter` (see
+// `StreamWriter::index_every`) and can be stored next to the data, because it
+// is encodable. The index of a framed stream can be also built from its frame
+// headers (see `FramedSpan::index`).
+//
+// The offsets are relative to the start of the stream, so the index is valid
+// wherever the stream is in the origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamIndex<T> {
+    entries: Vec<IndexEntry>,
+    len: usize,
+    ty: PhantomData<fn() -> T>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IndexEntry {
+    // Index of the first item after the entry.
+    first: usize,
+    // Position of the item relative to the start of the stream.
+    offset: usize,
+    // Number of items in the frame that starts with the item, only for framed
+    // streams.
+    frame: Option<usize>,
+}
+
+impl<T> StreamIndex<T> {
+    // Number of items in the stream.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Returns the closest entry preceding the start of the range clamped to
+    // the stream, if the range is not empty.
+    fn seek(&self, range: Range<usize>) -> (Option<IndexEntry>, Range<usize>) {
+        let start = range.start.min(self.len);
+        let end = range.end.clamp(start, self.len);
+
+        if start == end {
+            return (None, start..end);
+        }
+
+        let i = self.entries.partition_point(|entry| entry.first <= start);
+        (i.checked_sub(1).map(|i| self.entries[i]), start..end)
+    }
+}
+
+impl<T> Encodable for StreamIndex<T> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        base::uint::encode(self.len as u64, writer)?;
+        base::uint::encode(self.entries.len() as u64, writer)?;
+
+        for entry in self.entries.iter() {
+            base::uint::encode(entry.first as u64, writer)?;
+            base::uint::encode(entry.offset as u64, writer)?;
+            // Frames are never empty, so zero stands for a plain stream.
+            base::uint::encode(entry.frame.unwrap_or(0) as u64, writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Decodable for StreamIndex<T> {
+    fn decode(bytes: &mut bytes::Bytes<'_>) -> Result<Self, DecodingError> {
+        let len = base::uint::decode(bytes)? as usize;
+        let n = base::utils::decode_len(bytes)? as usize;
+        bytes.check_collection_len(n)?;
+        bytes.allocate(n.saturating_mul(core::mem::size_of::<IndexEntry>()))?;
+
+        let mut entries = Vec::with_capacity(n.min(bytes.len()));
+
+        for _ in 0..n {
+            let first = base::uint::decode(bytes)? as usize;
+            let offset = base::uint::decode(bytes)? as usize;
+            let frame = base::uint::decode(bytes)? as usize;
+
+            entries.push(IndexEntry {
+                first,
+                offset,
+                frame: (frame > 0).then_some(frame),
+            });
+        }
+
+        Ok(Self {
+            entries,
+            len,
+            ty: PhantomData,
+        })
+    }
+
+    fn recognize<'a>(
+        bytes: &mut bytes::Bytes<'a>,
+    ) -> Result<bytes::ByteSlice<'a, Self>, DecodingError> {
+        let mark = bytes.mark();
+        Self::decode(bytes)?;
+        Ok(bytes.take_slice_from(mark))
+    }
+}
+
+impl<T: Decodable> Stream<T> {
+    pub fn get<'a>(
+        &self,
+        origin: &'a [u8],
+        index: &StreamIndex<T>,
+        n: usize,
+    ) -> Result<Option<bytes::ByteSlice<'a, T>>, DecodingError> {
+        match n.checked_add(1) {
+            Some(end) => self.range(origin, index, n..end)?.next().transpose(),
+            None => Ok(None),
+        }
+    }
+
+    // Only the items between the closest preceding entry and the start are
+    // read. Disjoint ranges can be processed independently.
+    pub fn range<'a>(
+        &self,
+        origin: &'a [u8],
+        index: &StreamIndex<T>,
+        range: Range<usize>,
+    ) -> Result<iter::Take<StreamIter<'a, T>>, DecodingError> {
+        let (entry, range) = index.seek(range);
+        let (first, offset) = entry.map_or((0, 0), |entry| (entry.first, entry.offset));
+
+        let mut bytes = bytes::Bytes::new(origin);
+        bytes
+            .consume_bytes(self.offset.saturating_add(offset))
+            .ok_or_else(|| UnexpectedEof::new_at(origin.len()))?;
+
+        if entry.is_some() {
+            for _ in first..range.start {
+                T::recognize(&mut bytes)?;
+            }
+        }
+
+        let iter = StreamIter {
+            bytes,
+            ty: PhantomData,
+        };
+
+        Ok(iter.take(range.len()))
+    }
+}
+
+#[derive(Debug)]
+pub struct FramedStreamIter<'a, T> {
+    bytes: bytes::Bytes<'a>,
+    // Number of items left in the current frame.
+    remaining: usize,
+    // Number of items left to be yielded.
+    limit: usize,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Decodable> FramedStreamIter<'a, T> {
+    fn next_frame(&mut self) -> Result<usize, DecodingError> {
+        let count = base::uint::decode(&mut self.bytes)?;
+
+        if count > 0 {
+            base::uint::decode(&mut self.bytes)?;
+        }
+
+        Ok(count as usize)
+    }
+}
+
+impl<'a, T: Decodable> Iterator for FramedStreamIter<'a, T> {
+    type Item = Result<bytes::ByteSlice<'a, T>, DecodingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() || self.limit == 0 {
+            return None;
+        }
+
+        if self.remaining == 0 {
+            match self.next_frame() {
+                Ok(0) => {
+                    self.bytes.consume(self.bytes.len());
+                    return None;
+                }
+                Ok(count) => self.remaining = count,
+                Err(error) => {
+                    self.bytes.consume(self.bytes.len());
+                    return Some(Err(error));
+                }
+            }
+        }
+
+        self.remaining -= 1;
+        self.limit -= 1;
+        Some(T::recognize(&mut self.bytes))
+    }
+}
+
+// Size of the encoded items after which a frame is written out.
+const FRAME_SIZE: usize = 4096;
+
+// Writes the items of a stream after the value containing it was encoded.
+// Obtained from `encode_with_stream` of structs whose last field is a stream.
+#[derive(Debug)]
+pub struct StreamWriter<W, T> {
+    writer: W,
+    len: usize,
+    // Encoded items of the current frame and their count, only for framed
+    // streams.
+    frame: Option<(Vec<u8>, usize)>,
+    items_per_frame: Option<usize>,
+    // Number of items between the index entries of a plain stream.
+    index_every: Option<usize>,
+    entries: Vec<IndexEntry>,
+    // Number of bytes of the stream written so far.
+    written: usize,
+    ty: PhantomData<fn(T)>,
+}
+
+impl<W: io::Write, T: Encodable> StreamWriter<W, T> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            len: 0,
+            frame: None,
+            items_per_frame: None,
+            index_every: None,
+            entries: Vec::new(),
+            written: 0,
+            ty: PhantomData,
+        }
+    }
+
+    pub fn framed(writer: W) -> Self {
+        Self {
+            frame: Some((Vec::new(), 0)),
+            ..Self::new(writer)
+        }
+    }
+
+    // Writes frames of exactly given number of items (except the last one)
+    // instead of frames of roughly the same byte size. This determines the
+    // granularity of the stream index, see `FramedSpan::index`.
+    pub fn items_per_frame(&mut self, count: usize) -> &mut Self {
+        self.items_per_frame = Some(count.max(1));
+        self
+    }
+
+    // Records an entry of the index returned by `finish_with_index` every given
+    // number of items. Every frame of a framed stream is an entry, so this sets
+    // the number of items per frame for them.
+    pub fn index_every(&mut self, count: usize) -> &mut Self {
+        if self.frame.is_some() {
+            self.items_per_frame(count)
+        } else {
+            self.index_every = Some(count.max(1));
+            self
+        }
+    }
+
+    pub fn push(&mut self, value: &T) -> io::Result<()> {
+        match (&mut self.frame, self.index_every) {
+            (Some((buf, count)), _) => {
+                value.encode(buf)?;
+                *count += 1;
+                self.len += 1;
+
+                let is_full = match self.items_per_frame {
+                    Some(items_per_frame) => *count >= items_per_frame,
+                    None => buf.len() >= FRAME_SIZE,
+                };
+
+                if is_full {
+                    self.write_frame()?;
+                }
+            }
+            (None, Some(index_every)) => {
+                if self.len.is_multiple_of(index_every) {
+                    self.entries.push(IndexEntry {
+                        first: self.len,
+                        offset: self.written,
+                        frame: None,
+                    });
+                }
+
+                let mut buf = Vec::new();
+                value.encode(&mut buf)?;
+                self.writer.write_all(&buf)?;
+                self.written += buf.len();
+                self.len += 1;
+            }
+            (None, None) => {
+                value.encode(&mut self.writer)?;
+                self.len += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn push_many(&mut self, values: &[T]) -> io::Result<()> {
+        values.iter().try_for_each(|value| self.push(value))
+    }
+
+    // Number of items pushed so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Writes the trailer of the stream if needed and returns the writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.finish_with_index().map(|(writer, _)| writer)
+    }
+
+    // Also returns the index of the stream, which has only the first item of a
+    // plain stream unless `index_every` was set.
+    pub fn finish_with_index(mut self) -> io::Result<(W, StreamIndex<T>)> {
+        if self.frame.is_some() {
+            self.write_frame()?;
+            FramedStream::<T>::end_encode(&mut self.writer)?;
+        } else if self.entries.is_empty() && self.len > 0 {
+            self.entries.push(IndexEntry {
+                first: 0,
+                offset: 0,
+                frame: None,
+            });
+        }
+
+        let index = StreamIndex {
+            entries: self.entries,
+            len: self.len,
+            ty: PhantomData,
+        };
+
+        Ok((self.writer, index))
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        if let Some((ref mut buf, ref mut count)) = self.frame {
+            if *count > 0 {
+                encode_frame(*count, buf, &mut self.writer)?;
+
+                let header = base::uint::encoded_len(*count as u64)
+                    + base::uint::encoded_len(buf.len() as u64);
+
+                self.entries.push(IndexEntry {
+                    first: self.len - *count,
+                    offset: self.written + header,
+                    frame: Some(*count),
+                });
+
+                self.written += header + buf.len();
+            }
+
+            buf.clear();
+            *count = 0;
+        }
+
+        Ok(())
+    }
+}
+
+// Value that is only recognized when decoded, so that it can be decoded on
+// demand from the original buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lazy<T> {
+    // Value to be encoded, a decoded lazy value does not contain any.
+    value: Option<T>,
+    span: bytes::ByteSpan<T>,
+}
+
+impl<T> Lazy<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Some(value),
+            span: bytes::ByteSpan::default(),
+        }
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    pub fn into_value(self) -> Option<T> {
+        self.value
+    }
+
+    // Location of the decoded value in the origin. It is empty for values
+    // created for encoding.
+    pub fn span(&self) -> &bytes::ByteSpan<T> {
+        &self.span
+    }
+
+    pub fn get<'a>(&self, origin: &'a [u8]) -> bytes::ByteSlice<'a, T> {
+        self.span.as_slice(origin)
+    }
+}
+
+impl<T: Encodable> Lazy<T> {
+    // A decoded value is encoded by copying it from the origin it was decoded
+    // from, plain `encode` fails for it.
+    pub fn encode_from<W: io::Write>(&self, origin: &[u8], writer: &mut W) -> io::Result<()> {
+        match self.value() {
+            Some(value) => value.encode(writer),
+            None => {
+                let span = self.span.offset()..self.span.offset() + self.span.len();
+                let bytes = origin
+                    .get(span)
+                    .ok_or_else(|| io::invalid_input("lazy value is not in the origin"))?;
+                writer.write_all(bytes)
+            }
+        }
+    }
+}
+
+impl<T: Decodable> Lazy<T> {
+    pub fn decode(&self, origin: &[u8]) -> Result<T, DecodingError> {
+        self.span.decode(origin)
+    }
+
+    pub fn decode_with_limits(
+        &self,
+        origin: &[u8],
+        limits: bytes::DecodeLimits,
+    ) -> Result<T, DecodingError> {
+        self.span.decode_with_limits(origin, limits)
+    }
+}
+
+impl<T> From<T> for Lazy<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+// Variant of a non-exhaustive enum unknown to this version. The body is kept
+// as it was encoded, so that the value can be encoded again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnknownVariant {
+    discriminant: usize,
+    body: Vec<u8>,
+}
+
+impl UnknownVariant {
+    pub fn new(discriminant: usize, body: Vec<u8>) -> Self {
+        Self { discriminant, body }
+    }
+
+    pub fn discriminant(&self) -> usize {
+        self.discriminant
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Flags<T> {
@@ -332,8 +1503,8 @@ impl<T: IsFlag> PartialEq for Flags<T> {
 
 impl<T: IsFlag> Eq for Flags<T> {}
 
-impl<T: IsFlag> std::hash::Hash for Flags<T> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl<T: IsFlag> core::hash::Hash for Flags<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         for flag in T::all() {
             self.is_set(flag).hash(state);
         }
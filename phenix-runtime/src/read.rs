@@ -0,0 +1,450 @@
+use std::{fmt, io, marker::PhantomData};
+
+use crate::{
+    bytes::{Bytes, DecodeLimits},
+    Decodable, DecodingError, Limit, LimitExceeded, Stream,
+};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+const MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Decoding(DecodingError),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+impl From<DecodingError> for ReadError {
+    fn from(error: DecodingError) -> Self {
+        ReadError::Decoding(error)
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(error) => fmt::Display::fmt(error, f),
+            ReadError::Decoding(error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(error) => Some(error),
+            ReadError::Decoding(error) => Some(error),
+        }
+    }
+}
+
+// Buffered source for decoding values directly from a reader. Only the value
+// being currently decoded is kept in memory, the bytes of already decoded
+// values are discarded whenever more data are needed.
+#[derive(Debug)]
+pub struct ReadBytes<R> {
+    reader: R,
+    buffer: Buffer,
+}
+
+impl<R: io::Read> ReadBytes<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Buffer::default(),
+        }
+    }
+
+    // The limits apply to each decoded value separately.
+    pub fn with_limits(reader: R, limits: DecodeLimits) -> Self {
+        Self {
+            reader,
+            buffer: Buffer::with_limits(limits),
+        }
+    }
+
+    // Maximum number of bytes buffered for a single value, 64 MiB by default.
+    // The length prefixes come from the input, so the buffer does not grow
+    // just because a value claims to be large.
+    pub fn max_buffer(&mut self, size: usize) -> &mut Self {
+        self.buffer.max_buffer(size);
+        self
+    }
+
+    pub fn position(&self) -> usize {
+        self.buffer.position()
+    }
+
+    pub fn is_empty(&mut self) -> Result<bool, ReadError> {
+        while self.buffer.needs_fill() {
+            self.fill()?;
+        }
+
+        Ok(self.buffer.is_drained())
+    }
+
+    pub fn decode<T: Decodable>(&mut self) -> Result<T, ReadError> {
+        self.decode_with(T::decode)
+    }
+
+    pub fn decode_with<T, F>(&mut self, mut decode: F) -> Result<T, ReadError>
+    where
+        F: FnMut(&mut Bytes<'_>) -> Result<T, DecodingError>,
+    {
+        loop {
+            match self.buffer.try_decode(&mut decode)? {
+                Some(value) => return Ok(value),
+                None => self.fill()?,
+            }
+        }
+    }
+
+    pub fn iter<T: Decodable>(&mut self) -> ReadIter<'_, R, T> {
+        ReadIter {
+            source: self,
+            ty: PhantomData,
+        }
+    }
+
+    // Returns the reader, the bytes that were read but not decoded yet are
+    // lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let spare = self.buffer.spare();
+
+        loop {
+            match self.reader.read(spare) {
+                Ok(n) => {
+                    self.buffer.filled(n);
+                    return Ok(());
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl<T: Decodable> Stream<T> {
+    // The items are expected to follow in the source right after the value
+    // which contains this stream.
+    pub fn iter_from<'r, R: io::Read>(&self, source: &'r mut ReadBytes<R>) -> ReadIter<'r, R, T> {
+        source.iter()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Buffer {
+    buf: Vec<u8>,
+    pos: usize,
+    end: usize,
+    // Number of bytes discarded from the beginning of the buffer.
+    offset: usize,
+    eof: bool,
+    // Number of available bytes needed before decoding is tried again.
+    wanted: usize,
+    max_size: usize,
+    limits: DecodeLimits,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+            end: 0,
+            offset: 0,
+            eof: false,
+            wanted: 0,
+            max_size: MAX_BUFFER_SIZE,
+            limits: DecodeLimits::default(),
+        }
+    }
+}
+
+impl Buffer {
+    pub fn with_limits(limits: DecodeLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn max_buffer(&mut self, size: usize) {
+        self.max_size = size;
+    }
+
+    pub fn position(&self) -> usize {
+        self.offset + self.pos
+    }
+
+    pub fn is_drained(&self) -> bool {
+        self.pos == self.end
+    }
+
+    pub fn needs_fill(&self) -> bool {
+        self.is_drained() && !self.eof
+    }
+
+    // Returns `None` if more data must be read to decode the value.
+    pub fn try_decode<T, F>(&mut self, decode: &mut F) -> Result<Option<T>, DecodingError>
+    where
+        F: FnMut(&mut Bytes<'_>) -> Result<T, DecodingError>,
+    {
+        let available = self.end - self.pos;
+
+        if available < self.wanted && !self.eof {
+            return Ok(None);
+        }
+
+        let mut bytes = Bytes::partial(
+            &self.buf[self.pos..self.end],
+            self.position(),
+            self.limits,
+            !self.eof,
+        );
+
+        match decode(&mut bytes) {
+            Ok(value) => {
+                self.pos = self.end - bytes.len();
+                self.wanted = 0;
+                Ok(Some(value))
+            }
+            // The value is decoded from the start again, so the available bytes
+            // are doubled first, so that a value that comes in many short reads
+            // is not decoded too many times.
+            Err(error) if bytes.needs_more(&error) => {
+                if available >= self.max_size {
+                    return Err(LimitExceeded::new_at(self.position(), Limit::Buffer).into());
+                }
+
+                self.wanted = (2 * available.max(1)).min(self.max_size);
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    // Returns the space to read the next data into.
+    pub fn spare(&mut self) -> &mut [u8] {
+        if self.pos > 0 {
+            self.buf.copy_within(self.pos..self.end, 0);
+            self.offset += self.pos;
+            self.end -= self.pos;
+            self.pos = 0;
+        }
+
+        if self.end == self.buf.len() {
+            let size = (2 * self.end).max(CHUNK_SIZE).min(self.max_size);
+            self.buf.resize(size.max(self.end + 1), 0);
+        }
+
+        &mut self.buf[self.end..]
+    }
+
+    pub fn filled(&mut self, n: usize) {
+        self.end += n;
+        self.eof = n == 0;
+    }
+}
+
+#[derive(Debug)]
+pub struct ReadIter<'r, R, T> {
+    source: &'r mut ReadBytes<R>,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<R: io::Read, T: Decodable> Iterator for ReadIter<'_, R, T> {
+    type Item = Result<T, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.source.is_empty() {
+            Ok(true) => None,
+            Ok(false) => Some(self.source.decode()),
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{base, Encodable, InvalidPrefix, UnexpectedEof};
+
+    // Reader that returns a single byte at a time to exercise refilling.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(dest)) => {
+                    *dest = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn encode_all<T: Encodable>(values: &[T]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+
+        for value in values {
+            value.encode(&mut cursor).unwrap();
+        }
+
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn truncated() {
+        let mut source = ReadBytes::new(Trickle(&[0, 4, 104, 105]));
+        assert_eq!(source.decode::<String>().unwrap(), "");
+
+        let error = source.decode::<String>().unwrap_err();
+        assert!(matches!(
+            error,
+            ReadError::Decoding(error) if error == UnexpectedEof::new_at(2).into()
+        ));
+    }
+
+    #[test]
+    fn truncated_prefix() {
+        let mut source = ReadBytes::new(Trickle(&[0, 249, 1]));
+        assert_eq!(source.decode::<String>().unwrap(), "");
+
+        let error = source.decode::<String>().unwrap_err();
+        assert!(matches!(
+            error,
+            ReadError::Decoding(error) if error == InvalidPrefix::new_at(1).into()
+        ));
+    }
+
+    // Reader that fails after the data, for checking that nothing more is read.
+    struct Failing;
+
+    impl io::Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("read too far"))
+        }
+    }
+
+    #[test]
+    fn invalid_prefix() {
+        let mut limits = DecodeLimits::default();
+        limits.strict();
+
+        // Non-canonical length prefix is not truncated.
+        let mut source = ReadBytes::with_limits(io::Read::chain(&[248, 5][..], Failing), limits);
+
+        let error = source.decode::<String>().unwrap_err();
+        assert!(matches!(
+            error,
+            ReadError::Decoding(error) if error == InvalidPrefix::new_at(0).into()
+        ));
+    }
+
+    #[test]
+    fn short_reads() {
+        let value = "a".repeat(10_000);
+        let bytes = encode_all(std::slice::from_ref(&value));
+        let mut source = ReadBytes::new(Trickle(&bytes));
+
+        let mut attempts = 0;
+        let decoded = source
+            .decode_with(|bytes| {
+                attempts += 1;
+                String::decode(bytes)
+            })
+            .unwrap();
+
+        assert_eq!(decoded, value);
+        assert!(attempts < 20);
+    }
+
+    #[test]
+    fn max_buffer() {
+        // The length prefix claims much more bytes than there are.
+        let mut bytes = Vec::new();
+        base::uint::encode(u32::MAX as u64, &mut bytes).unwrap();
+        bytes.extend(std::iter::repeat_n(b'a', 100));
+
+        let mut source = ReadBytes::new(io::Read::chain(bytes.as_slice(), io::repeat(b'a')));
+        source.max_buffer(64);
+
+        let error = source.decode::<String>().unwrap_err();
+        assert!(matches!(
+            error,
+            ReadError::Decoding(error) if error == LimitExceeded::new_at(0, Limit::Buffer).into()
+        ));
+    }
+
+    #[test]
+    fn stream_offset() {
+        let bytes = encode_all(&["ab".to_string(), "cd".to_string()]);
+        let mut source = ReadBytes::new(Trickle(&bytes));
+
+        assert_eq!(source.decode::<String>().unwrap(), "ab");
+
+        let stream = source.decode::<Stream<String>>().unwrap();
+        assert_eq!(stream, Stream::with_offset(3));
+
+        let items = stream
+            .iter_from(&mut source)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(items, ["cd"]);
+    }
+
+    proptest! {
+        #[test]
+        fn uints(values: Vec<u64>) {
+            let mut cursor = Cursor::new(Vec::new());
+
+            for value in values.iter().copied() {
+                base::uint::encode(value, &mut cursor).unwrap();
+            }
+
+            let bytes = cursor.into_inner();
+            let mut source = ReadBytes::new(Trickle(&bytes));
+
+            for value in values {
+                assert_eq!(source.decode_with(base::uint::decode).unwrap(), value);
+            }
+
+            assert!(source.is_empty().unwrap());
+        }
+
+        #[test]
+        fn strings(values: Vec<String>) {
+            let bytes = encode_all(&values);
+            let mut source = ReadBytes::new(Trickle(&bytes));
+
+            let decoded = source.iter::<String>().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(decoded, values);
+        }
+
+        #[test]
+        fn vectors(values: Vec<Vec<bool>>) {
+            let bytes = encode_all(&values);
+            let mut source = ReadBytes::new(bytes.as_slice());
+
+            let decoded = source.iter::<Vec<bool>>().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(decoded, values);
+        }
+    }
+}
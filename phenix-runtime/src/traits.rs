@@ -1,8 +1,8 @@
-use std::io;
+use alloc::vec::Vec;
 
 use super::{
-    bytes::{ByteSlice, ByteSpan, Bytes},
-    DecodingError,
+    bytes::{ByteSlice, ByteSpan, Bytes, DecodeLimits},
+    io, DecodingError,
 };
 
 pub trait Encodable: Sized {
@@ -15,9 +15,32 @@ pub trait Encodable: Sized {
 
         Ok(())
     }
+
+    // The number of bytes written by `encode`. By default, the value is
+    // encoded into a sink that only counts the bytes, implementations should
+    // override it to compute the length without encoding.
+    fn encoded_len(&self) -> usize {
+        let mut counter = io::Counter::default();
+        // Writing to the counter never fails, so an error can only come from
+        // a value that cannot be encoded.
+        let _ = self.encode(&mut counter);
+        counter.len
+    }
+
+    fn encoded_len_many(values: &[Self]) -> usize {
+        values.iter().map(Self::encoded_len).sum()
+    }
 }
 
 pub trait Decodable: Sized {
+    // The encoded size if it is the same for all values, in which case the
+    // items of collections can be accessed directly.
+    const FIXED_SIZE: Option<usize> = None;
+
+    // Whether multiple values are packed together in collections, such as
+    // bools into a bitmap, so that the items cannot be recognized one by one.
+    const IS_PACKED: bool = false;
+
     fn decode(bytes: &mut Bytes<'_>) -> Result<Self, DecodingError>;
     fn recognize<'a>(bytes: &mut Bytes<'a>) -> Result<ByteSlice<'a, Self>, DecodingError>;
 
@@ -45,23 +68,70 @@ pub trait Decodable: Sized {
 
         Ok(bytes.take_slice_from(mark))
     }
+
+    // Checks the value fully, including the contents that `recognize` skips,
+    // but without materializing it.
+    fn validate(bytes: &mut Bytes<'_>) -> Result<(), DecodingError> {
+        Self::decode(bytes).map(|_| ())
+    }
+
+    fn validate_many(bytes: &mut Bytes<'_>, n: usize) -> Result<(), DecodingError> {
+        for _ in 0..n {
+            Self::validate(bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Decoding of values that may borrow from the input. All owned values can be
+// decoded this way too.
+pub trait DecodableRef<'a>: Sized {
+    fn decode_ref(bytes: &mut Bytes<'a>) -> Result<Self, DecodingError>;
+}
+
+impl<'a, T: Decodable> DecodableRef<'a> for T {
+    fn decode_ref(bytes: &mut Bytes<'a>) -> Result<Self, DecodingError> {
+        T::decode(bytes)
+    }
 }
 
 impl<T: Decodable> ByteSlice<'_, T> {
     pub fn decode(&self) -> Result<T, DecodingError> {
-        let mut bytes = Bytes::new(self.as_bytes());
+        let mut bytes = self.to_bytes();
+        T::decode(&mut bytes)
+    }
+
+    pub fn decode_with_limits(&self, limits: DecodeLimits) -> Result<T, DecodingError> {
+        let mut bytes = self.to_bytes_with_limits(limits);
         T::decode(&mut bytes)
     }
 
     pub fn decode_many(&self, n: usize, values: &mut Vec<T>) -> Result<(), DecodingError> {
-        let mut bytes = Bytes::new(self.as_bytes());
+        let mut bytes = self.to_bytes();
         T::decode_many(&mut bytes, n, values)
     }
 }
 
+impl<'a, T: DecodableRef<'a>> ByteSlice<'a, T> {
+    pub fn decode_ref(&self) -> Result<T, DecodingError> {
+        let mut bytes = self.to_bytes();
+        T::decode_ref(&mut bytes)
+    }
+}
+
 impl<T: Decodable> ByteSpan<T> {
     pub fn decode(&self, origin: &[u8]) -> Result<T, DecodingError> {
-        let mut bytes = Bytes::new(self.as_bytes(origin));
+        let mut bytes = self.as_slice(origin).to_bytes();
+        T::decode(&mut bytes)
+    }
+
+    pub fn decode_with_limits(
+        &self,
+        origin: &[u8],
+        limits: DecodeLimits,
+    ) -> Result<T, DecodingError> {
+        let mut bytes = self.as_slice(origin).to_bytes_with_limits(limits);
         T::decode(&mut bytes)
     }
 
@@ -71,7 +141,7 @@ impl<T: Decodable> ByteSpan<T> {
         n: usize,
         values: &mut Vec<T>,
     ) -> Result<(), DecodingError> {
-        let mut bytes = Bytes::new(self.as_bytes(origin));
+        let mut bytes = self.as_slice(origin).to_bytes();
         T::decode_many(&mut bytes, n, values)
     }
 }
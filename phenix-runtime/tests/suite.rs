@@ -1,8 +1,17 @@
-use std::{fmt, io::Cursor, marker::PhantomData};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::Cursor,
+    marker::PhantomData,
+};
 
 use phenix_runtime::{
-    bytes::Bytes, Decodable, Encodable, Flags, Float, InvalidPrefix, IsFlag, Sint, Uint,
-    UnexpectedEof, ValueError,
+    bytes::{Bytes, DecodeLimits},
+    read::ReadBytes,
+    Decodable, DecodableRef, DecodingError, Encodable, Flags, Float, FramedStream, InvalidPrefix,
+    IsFlag, Lazy, Limit, LimitExceeded, Sint, Stream, StreamIndex, Uint, UnexpectedEof,
+    UnknownVariant, ValueError,
 };
 use serde::Deserialize;
 
@@ -21,6 +30,11 @@ fn recognize_uint() {
     TestSuite::<Uint>::run_recognize(include_str!("data/uint.json"));
 }
 
+#[test]
+fn validate_uint() {
+    TestSuite::<Uint>::run_validate(include_str!("data/uint.json"));
+}
+
 #[test]
 fn encode_sint() {
     TestSuite::<Sint>::run_encode(include_str!("data/sint.json"));
@@ -36,6 +50,11 @@ fn recognize_sint() {
     TestSuite::<Sint>::run_recognize(include_str!("data/sint.json"));
 }
 
+#[test]
+fn validate_sint() {
+    TestSuite::<Sint>::run_validate(include_str!("data/sint.json"));
+}
+
 #[test]
 fn encode_float() {
     TestSuite::<Float>::run_encode(include_str!("data/float.json"));
@@ -51,6 +70,11 @@ fn recognize_float() {
     TestSuite::<Float>::run_recognize(include_str!("data/float.json"));
 }
 
+#[test]
+fn validate_float() {
+    TestSuite::<Float>::run_validate(include_str!("data/float.json"));
+}
+
 #[test]
 fn encode_bool() {
     TestSuite::<bool>::run_encode(include_str!("data/bool.json"));
@@ -66,6 +90,11 @@ fn recognize_bool() {
     TestSuite::<bool>::run_recognize(include_str!("data/bool.json"));
 }
 
+#[test]
+fn validate_bool() {
+    TestSuite::<bool>::run_validate(include_str!("data/bool.json"));
+}
+
 #[test]
 fn encode_string() {
     TestSuite::<String>::run_encode(include_str!("data/string.json"));
@@ -81,6 +110,86 @@ fn recognize_string() {
     TestSuite::<String>::run_recognize(include_str!("data/string.json"));
 }
 
+#[test]
+fn validate_string() {
+    TestSuite::<String>::run_validate(include_str!("data/string.json"));
+}
+
+#[test]
+fn encode_map() {
+    TestSuite::<BTreeMap<String, Uint>>::run_encode(include_str!("data/map.json"));
+}
+
+#[test]
+fn decode_map() {
+    TestSuite::<BTreeMap<String, Uint>>::run_decode(include_str!("data/map.json"));
+}
+
+#[test]
+fn recognize_map() {
+    TestSuite::<BTreeMap<String, Uint>>::run_recognize(include_str!("data/map.json"));
+}
+
+#[test]
+fn validate_map() {
+    TestSuite::<BTreeMap<String, Uint>>::run_validate(include_str!("data/map.json"));
+}
+
+#[test]
+fn encode_array() {
+    TestSuite::<[bool; 10]>::run_encode(include_str!("data/array.json"));
+}
+
+#[test]
+fn decode_array() {
+    TestSuite::<[bool; 10]>::run_decode(include_str!("data/array.json"));
+}
+
+#[test]
+fn recognize_array() {
+    TestSuite::<[bool; 10]>::run_recognize(include_str!("data/array.json"));
+}
+
+#[test]
+fn validate_array() {
+    TestSuite::<[bool; 10]>::run_validate(include_str!("data/array.json"));
+}
+
+#[test]
+fn encode_tuple() {
+    TestSuite::<(Uint, String, bool)>::run_encode(include_str!("data/tuple.json"));
+}
+
+#[test]
+fn decode_tuple() {
+    TestSuite::<(Uint, String, bool)>::run_decode(include_str!("data/tuple.json"));
+}
+
+#[test]
+fn recognize_tuple() {
+    TestSuite::<(Uint, String, bool)>::run_recognize(include_str!("data/tuple.json"));
+}
+
+#[test]
+fn validate_tuple() {
+    TestSuite::<(Uint, String, bool)>::run_validate(include_str!("data/tuple.json"));
+}
+
+#[test]
+fn encode_hash_map() {
+    TestSuite::<HashMap<String, Uint>>::run_encode(include_str!("data/map.json"));
+}
+
+#[test]
+fn decode_hash_map() {
+    TestSuite::<HashMap<String, Uint>>::run_decode(include_str!("data/map.json"));
+}
+
+#[test]
+fn validate_hash_map() {
+    TestSuite::<HashMap<String, Uint>>::run_validate(include_str!("data/map.json"));
+}
+
 #[derive(Debug, PartialEq, Deserialize, Encodable, Decodable)]
 struct Struct {
     string: String,
@@ -104,6 +213,61 @@ fn recognize_struct() {
     TestSuite::<Struct>::run_recognize(include_str!("data/struct.json"));
 }
 
+#[test]
+fn validate_struct() {
+    TestSuite::<Struct>::run_validate(include_str!("data/struct.json"));
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn encode_struct_async() {
+    TestSuite::<Struct>::run_encode_async(include_str!("data/struct.json"));
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct OptionalStrings {
+    first: Option<String>,
+    second: String,
+    third: Option<String>,
+}
+
+#[test]
+fn encode_optional_strings() {
+    let value = OptionalStrings {
+        first: None,
+        second: "b".to_string(),
+        third: Some("c".to_string()),
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+    assert_eq!(bytes, [2, 1, b'b', 1, b'c']);
+
+    let decoded = OptionalStrings::decode(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(decoded, value);
+}
+
+// Implemented by hand without `encoded_len`.
+struct Pair(Uint, String);
+
+impl Encodable for Pair {
+    fn encode<W: phenix_runtime::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.0.encode(writer)?;
+        self.1.encode(writer)
+    }
+}
+
+#[test]
+fn default_encoded_len() {
+    let value = Pair(Uint(300), "pair".to_string());
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    assert_eq!(value.encoded_len(), cursor.into_inner().len());
+    assert_eq!(Pair::encoded_len_many(&[value]), 8);
+}
+
 #[derive(Debug, PartialEq, Deserialize, Encodable, Decodable)]
 enum Enum {
     Foo,
@@ -125,6 +289,436 @@ fn recognize_enum() {
     TestSuite::<Enum>::run_recognize(include_str!("data/enum.json"));
 }
 
+#[test]
+fn validate_enum() {
+    TestSuite::<Enum>::run_validate(include_str!("data/enum.json"));
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn encode_enum_async() {
+    TestSuite::<Enum>::run_encode_async(include_str!("data/enum.json"));
+}
+
+#[phenix_runtime::by_parts]
+#[derive(Debug, PartialEq, Deserialize, Encodable, Decodable)]
+#[non_exhaustive]
+struct StructNe {
+    string: String,
+    optional: Option<Uint>,
+    number: Uint,
+}
+
+#[test]
+fn encode_struct_non_exhaustive() {
+    TestSuite::<StructNe>::run_encode(include_str!("data/struct_ne.json"));
+}
+
+#[test]
+fn decode_struct_non_exhaustive() {
+    TestSuite::<StructNe>::run_decode(include_str!("data/struct_ne.json"));
+}
+
+#[test]
+fn recognize_struct_non_exhaustive() {
+    TestSuite::<StructNe>::run_recognize(include_str!("data/struct_ne.json"));
+}
+
+#[test]
+fn validate_struct_non_exhaustive() {
+    TestSuite::<StructNe>::run_validate(include_str!("data/struct_ne.json"));
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn encode_struct_non_exhaustive_async() {
+    TestSuite::<StructNe>::run_encode_async(include_str!("data/struct_ne.json"));
+}
+
+// Newer version of `StructNe`.
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+struct StructNeV2 {
+    string: String,
+    optional: Option<Uint>,
+    number: Uint,
+    added: Option<Uint>,
+    extra: Vec<bool>,
+}
+
+// Newer version of `StructNe` that adds only optional fields.
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+struct StructNeV3 {
+    string: String,
+    optional: Option<Uint>,
+    number: Uint,
+    added: Option<Uint>,
+}
+
+#[test]
+fn non_exhaustive_struct_compat() {
+    let mut cursor = Cursor::new(Vec::new());
+    StructNeV2 {
+        string: "ab".to_string(),
+        optional: None,
+        number: Uint(5),
+        added: Some(Uint(7)),
+        extra: vec![true, false],
+    }
+    .encode(&mut cursor)
+    .unwrap();
+    Uint(9).encode(&mut cursor).unwrap();
+    let new = cursor.into_inner();
+
+    let bytes = &mut Bytes::new(&new);
+    let value = StructNe::decode(bytes).unwrap();
+    assert_eq!(
+        value,
+        StructNe {
+            string: "ab".to_string(),
+            optional: None,
+            number: Uint(5),
+        }
+    );
+    assert_eq!(Uint::decode(bytes).unwrap(), Uint(9));
+
+    let bytes = &mut Bytes::new(&new);
+    StructNe::recognize(bytes).unwrap();
+    assert_eq!(Uint::decode(bytes).unwrap(), Uint(9));
+
+    let bytes = &mut Bytes::new(&new);
+    let parts = StructNe::recognize_by_parts(bytes).collect::<Result<Vec<_>, _>>();
+    assert_eq!(parts.unwrap().len(), 3);
+    assert_eq!(Uint::decode(bytes).unwrap(), Uint(9));
+
+    let mut cursor = Cursor::new(Vec::new());
+    StructNe {
+        string: "ab".to_string(),
+        optional: Some(Uint(3)),
+        number: Uint(5),
+    }
+    .encode(&mut cursor)
+    .unwrap();
+    let old = cursor.into_inner();
+
+    let value = StructNeV3::decode(&mut Bytes::new(&old)).unwrap();
+    assert_eq!(
+        value,
+        StructNeV3 {
+            string: "ab".to_string(),
+            optional: Some(Uint(3)),
+            number: Uint(5),
+            added: None,
+        }
+    );
+}
+
+#[phenix_runtime::by_parts]
+#[derive(Debug, PartialEq, Deserialize, Encodable, Decodable)]
+#[non_exhaustive]
+enum EnumNe {
+    Foo,
+    Bar { number: Uint, boolean: bool },
+}
+
+#[test]
+fn encode_enum_non_exhaustive() {
+    TestSuite::<EnumNe>::run_encode(include_str!("data/enum_ne.json"));
+}
+
+#[test]
+fn decode_enum_non_exhaustive() {
+    TestSuite::<EnumNe>::run_decode(include_str!("data/enum_ne.json"));
+}
+
+#[test]
+fn recognize_enum_non_exhaustive() {
+    TestSuite::<EnumNe>::run_recognize(include_str!("data/enum_ne.json"));
+}
+
+#[test]
+fn validate_enum_non_exhaustive() {
+    TestSuite::<EnumNe>::run_validate(include_str!("data/enum_ne.json"));
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn encode_enum_non_exhaustive_async() {
+    TestSuite::<EnumNe>::run_encode_async(include_str!("data/enum_ne.json"));
+}
+
+// Newer version of `EnumNe`.
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+enum EnumNeV2 {
+    Foo,
+    Bar { number: Uint, boolean: bool },
+    Baz(String),
+}
+
+#[test]
+fn non_exhaustive_enum_compat() {
+    let mut cursor = Cursor::new(Vec::new());
+    EnumNeV2::Baz("unknown".to_string())
+        .encode(&mut cursor)
+        .unwrap();
+    EnumNeV2::Foo.encode(&mut cursor).unwrap();
+    let new = cursor.into_inner();
+
+    let bytes = &mut Bytes::new(&new);
+    assert!(EnumNe::decode(&mut Bytes::new(&new)).is_err());
+    EnumNe::recognize(bytes).unwrap();
+    assert_eq!(EnumNe::decode(bytes).unwrap(), EnumNe::Foo);
+    assert!(bytes.is_empty());
+
+    // The unknown variant has only the discriminant.
+    let bytes = &mut Bytes::new(&new);
+    let parts = EnumNe::recognize_by_parts(bytes)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(matches!(parts.as_slice(), [EnumNePart::Discriminant(2)]));
+    assert_eq!(EnumNe::decode(bytes).unwrap(), EnumNe::Foo);
+}
+
+// `EnumNe` with a variant for the unknown variants.
+#[phenix_runtime::by_parts]
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+enum EnumNeOpen {
+    Foo,
+    Bar { number: Uint, boolean: bool },
+    Unknown(UnknownVariant),
+}
+
+#[test]
+fn non_exhaustive_enum_unknown_variant() {
+    let mut cursor = Cursor::new(Vec::new());
+    EnumNeV2::Baz("unknown".to_string())
+        .encode(&mut cursor)
+        .unwrap();
+    let new = cursor.into_inner();
+
+    let value = EnumNeOpen::decode(&mut Bytes::new(&new)).unwrap();
+    assert_eq!(
+        value,
+        EnumNeOpen::Unknown(UnknownVariant::new(2, b"\x07unknown".to_vec()))
+    );
+    phenix_runtime::validate::<EnumNeOpen>(&new).unwrap();
+
+    // The unknown variant is encoded as it was.
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    assert_eq!(cursor.into_inner(), new);
+    assert_eq!(value.encoded_len(), new.len());
+
+    let bytes = &mut Bytes::new(&new);
+    let parts = EnumNeOpen::recognize_by_parts(bytes)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(matches!(
+        parts.as_slice(),
+        [EnumNeOpenPart::Discriminant(2)]
+    ));
+    assert!(bytes.is_empty());
+
+    // The known variants are not affected.
+    let mut cursor = Cursor::new(Vec::new());
+    EnumNeOpen::Bar {
+        number: Uint(3),
+        boolean: true,
+    }
+    .encode(&mut cursor)
+    .unwrap();
+    assert_eq!(cursor.into_inner(), [1, 2, 3, 1]);
+}
+
+#[phenix_runtime::by_parts]
+#[derive(Debug, PartialEq, Deserialize, Encodable, Decodable)]
+#[repr(u16)]
+enum EnumTagged {
+    Foo { number: Uint } = 2,
+    Bar(bool) = 1000,
+    Baz,
+}
+
+#[test]
+fn encode_enum_tagged() {
+    TestSuite::<EnumTagged>::run_encode(include_str!("data/enum_tagged.json"));
+}
+
+#[test]
+fn decode_enum_tagged() {
+    TestSuite::<EnumTagged>::run_decode(include_str!("data/enum_tagged.json"));
+}
+
+#[test]
+fn recognize_enum_tagged() {
+    TestSuite::<EnumTagged>::run_recognize(include_str!("data/enum_tagged.json"));
+}
+
+#[test]
+fn validate_enum_tagged() {
+    TestSuite::<EnumTagged>::run_validate(include_str!("data/enum_tagged.json"));
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn encode_enum_tagged_async() {
+    TestSuite::<EnumTagged>::run_encode_async(include_str!("data/enum_tagged.json"));
+}
+
+#[test]
+fn enum_by_parts() {
+    let mut cursor = Cursor::new(Vec::new());
+    EnumTagged::Foo { number: Uint(7) }
+        .encode(&mut cursor)
+        .unwrap();
+    EnumTagged::Baz.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let bytes = &mut Bytes::new(&bytes);
+
+    {
+        let mut parts = EnumTagged::recognize_by_parts(bytes);
+
+        assert!(matches!(
+            parts.next(),
+            Some(Ok(EnumTaggedPart::Discriminant(2)))
+        ));
+        match parts.next() {
+            Some(Ok(EnumTaggedPart::FooNumber(part))) => {
+                assert_eq!(part.decode().unwrap(), Uint(7))
+            }
+            part => panic!("unexpected part {:?}", part),
+        }
+        assert!(parts.next().is_none());
+    }
+
+    let parts = EnumTagged::recognize_by_parts(bytes)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(matches!(
+        parts.as_slice(),
+        [EnumTaggedPart::Discriminant(1001)]
+    ));
+    assert!(bytes.is_empty());
+
+    // Unknown discriminant.
+    let bytes = &mut Bytes::new(&[3]);
+    let mut parts = EnumTagged::recognize_by_parts(bytes);
+    assert!(matches!(
+        parts.next(),
+        Some(Err(DecodingError::InvalidPrefix(_)))
+    ));
+    assert!(parts.next().is_none());
+}
+
+#[test]
+fn by_parts_error() {
+    let mut cursor = Cursor::new(Vec::new());
+    Generic {
+        items: vec!["a".to_string()],
+        last: Some("b".to_string()),
+    }
+    .encode(&mut cursor)
+    .unwrap();
+    let bytes = cursor.into_inner();
+
+    // The iterators are fused after the first error.
+    let input = &mut Bytes::new(&bytes[..3]);
+    let mut parts = Generic::<String>::recognize_by_parts(input);
+    assert!(matches!(
+        parts.next(),
+        Some(Err(DecodingError::UnexpectedEof(_)))
+    ));
+    assert!(parts.next().is_none());
+
+    let mut cursor = Cursor::new(Vec::new());
+    EnumTagged::Foo { number: Uint(300) }
+        .encode(&mut cursor)
+        .unwrap();
+    let bytes = cursor.into_inner();
+
+    let input = &mut Bytes::new(&bytes[..2]);
+    let mut parts = EnumTagged::recognize_by_parts(input);
+    assert!(matches!(
+        parts.next(),
+        Some(Ok(EnumTaggedPart::Discriminant(2)))
+    ));
+    assert!(matches!(parts.next(), Some(Err(_))));
+    assert!(parts.next().is_none());
+}
+
+#[phenix_runtime::by_parts]
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Empty {}
+
+#[phenix_runtime::by_parts]
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+struct EmptyNe {}
+
+#[test]
+fn empty_by_parts() {
+    let bytes = &mut Bytes::new(&[]);
+    assert!(Empty::recognize_by_parts(bytes).next().is_none());
+
+    // The fields of a newer version are skipped.
+    let mut cursor = Cursor::new(Vec::new());
+    StructNe {
+        string: "ab".to_string(),
+        optional: None,
+        number: Uint(5),
+    }
+    .encode(&mut cursor)
+    .unwrap();
+    let bytes = cursor.into_inner();
+
+    let input = &mut Bytes::new(&bytes);
+    assert!(EmptyNe::recognize_by_parts(input).next().is_none());
+    assert!(input.is_empty());
+
+    let input = &mut Bytes::new(&bytes[..2]);
+    let mut parts = EmptyNe::recognize_by_parts(input);
+    assert!(matches!(
+        parts.next(),
+        Some(Err(DecodingError::UnexpectedEof(_)))
+    ));
+    assert!(parts.next().is_none());
+}
+
+#[phenix_runtime::by_parts]
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Generic<T> {
+    items: Vec<T>,
+    last: Option<T>,
+}
+
+#[test]
+fn generic_by_parts() {
+    let mut cursor = Cursor::new(Vec::new());
+    Generic {
+        items: vec!["a".to_string(), "b".to_string()],
+        last: Some("c".to_string()),
+    }
+    .encode(&mut cursor)
+    .unwrap();
+    let bytes = cursor.into_inner();
+
+    let parts = Generic::<String>::recognize_by_parts(&mut Bytes::new(&bytes))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    match parts.as_slice() {
+        [GenericPart::Items(items), GenericPart::Last(Some(last))] => {
+            assert_eq!(items.decode().unwrap(), ["a", "b"]);
+            assert_eq!(last.decode().unwrap(), "c");
+        }
+        parts => panic!("unexpected parts {:?}", parts),
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Deserialize, IsFlag)]
 enum Flag {
     Foo,
@@ -154,6 +748,11 @@ fn recognize_flags() {
     TestSuite::<Flags<Flag>, Vec<Flag>>::run_recognize(include_str!("data/flags.json"));
 }
 
+#[test]
+fn validate_flags() {
+    TestSuite::<Flags<Flag>, Vec<Flag>>::run_validate(include_str!("data/flags.json"));
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Deserialize, IsFlag)]
 #[non_exhaustive]
 enum FlagNe {
@@ -184,6 +783,767 @@ fn recognize_flags_non_exhaustive() {
     TestSuite::<Flags<FlagNe>, Vec<FlagNe>>::run_recognize(include_str!("data/flags_ne.json"));
 }
 
+#[test]
+fn validate_flags_non_exhaustive() {
+    TestSuite::<Flags<FlagNe>, Vec<FlagNe>>::run_validate(include_str!("data/flags_ne.json"));
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Borrowed<'a> {
+    name: &'a str,
+    data: &'a [u8],
+    optional: Option<&'a str>,
+    note: Cow<'a, str>,
+    inner: BorrowedEnum<'a>,
+    number: Uint,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+enum BorrowedEnum<'a> {
+    Empty,
+    Text(&'a str),
+}
+
+#[test]
+fn decode_borrowed() {
+    let value = Borrowed {
+        name: "name",
+        data: &[1, 2, 3],
+        optional: Some("optional"),
+        note: Cow::Owned("note".to_string()),
+        inner: BorrowedEnum::Text("text"),
+        number: Uint(42),
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let decoded = Borrowed::decode_ref(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(decoded, value);
+
+    // The strings point directly into the input.
+    assert!(bytes.as_ptr_range().contains(&decoded.name.as_ptr()));
+    assert!(matches!(decoded.note, Cow::Borrowed(_)));
+
+    let error = <&str>::decode_ref(&mut Bytes::new(&[2, 0xc3, 0x28])).unwrap_err();
+    assert_eq!(error, ValueError::new_at(1).into());
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Person {
+    name: String,
+    projects: Vec<Project>,
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Project {
+    name: String,
+    url: String,
+}
+
+#[test]
+fn decode_error_context() {
+    let person = Person {
+        name: "Jane".to_string(),
+        projects: (0..5)
+            .map(|i| Project {
+                name: i.to_string(),
+                url: format!("https://example.com/{}", i),
+            })
+            .collect(),
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    person.encode(&mut cursor).unwrap();
+    let mut bytes = cursor.into_inner();
+
+    // Corrupt the url of the fourth project.
+    let pos = bytes
+        .windows(4)
+        .position(|window| window == b"om/3")
+        .unwrap();
+    bytes[pos + 3] = 0xff;
+
+    let error = Person::decode(&mut Bytes::new(&bytes)).unwrap_err();
+    assert_eq!(error.root(), &ValueError::new_at(pos + 3).into());
+
+    #[cfg(feature = "context")]
+    {
+        let context = error.context().unwrap();
+        assert_eq!(context.ty(), Some("Person"));
+        assert_eq!(context.expected(), Some("String"));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "invalid value when parsing from byte {} in Person.projects[3].url (expected String)",
+                pos + 3
+            )
+        );
+    }
+
+    // Without the context, errors stay small.
+    #[cfg(not(feature = "context"))]
+    assert!(std::mem::size_of_val(&error) <= 3 * std::mem::size_of::<usize>());
+}
+
+#[test]
+fn validate_person() {
+    let person = Person {
+        name: "Jane".to_string(),
+        projects: vec![Project {
+            name: "phenix".to_string(),
+            url: "https://example.com".to_string(),
+        }],
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    person.encode(&mut cursor).unwrap();
+    let mut bytes = cursor.into_inner();
+
+    phenix_runtime::validate::<Person>(&bytes).unwrap();
+
+    // Trailing bytes.
+    bytes.push(0);
+    assert_eq!(
+        phenix_runtime::validate::<Person>(&bytes),
+        Err(ValueError::new_at(bytes.len() - 1).into())
+    );
+    bytes.pop();
+
+    // Invalid UTF-8 in a nested string.
+    let pos = bytes
+        .windows(3)
+        .position(|window| window == b"com")
+        .unwrap();
+    bytes[pos] = 0xff;
+    assert_eq!(
+        phenix_runtime::validate::<Person>(&bytes)
+            .unwrap_err()
+            .root(),
+        &ValueError::new_at(pos).into()
+    );
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct Tree {
+    children: Vec<Tree>,
+}
+
+#[test]
+fn decode_limits() {
+    fn encode<T: Encodable>(value: &T) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        value.encode(&mut cursor).unwrap();
+        cursor.into_inner()
+    }
+
+    // The error is `Copy` only without the context.
+    #[allow(clippy::clone_on_copy)]
+    fn decode<'a, T: DecodableRef<'a>>(
+        bytes: &'a [u8],
+        limits: &DecodeLimits,
+    ) -> Result<T, DecodingError> {
+        // Ignore the context if enabled.
+        T::decode_ref(&mut Bytes::with_limits(bytes, *limits)).map_err(|error| error.root().clone())
+    }
+
+    // A huge length without the actual data.
+    let bytes = encode(&Uint(1 << 60));
+    let error = decode::<Vec<Uint>>(&bytes, &DecodeLimits::default()).unwrap_err();
+    assert_eq!(error, UnexpectedEof::new_at(bytes.len()).into());
+
+    let error =
+        decode::<Vec<Uint>>(&bytes, DecodeLimits::default().max_collection_len(100)).unwrap_err();
+    assert_eq!(
+        error,
+        LimitExceeded::new_at(bytes.len(), Limit::CollectionLen).into()
+    );
+
+    let bytes = encode(&"x".repeat(20));
+    assert!(decode::<String>(&bytes, DecodeLimits::default().max_string_len(20)).is_ok());
+
+    let error = decode::<&str>(&bytes, DecodeLimits::default().max_string_len(19)).unwrap_err();
+    assert_eq!(error, LimitExceeded::new_at(1, Limit::StringLen).into());
+
+    let bytes = encode(&vec!["x".repeat(20); 10]);
+    let limits = DecodeLimits::default()
+        .max_alloc(10 * std::mem::size_of::<String>() + 150)
+        .to_owned();
+    let error = decode::<Vec<String>>(&bytes, &limits).unwrap_err();
+    assert_eq!(
+        error,
+        LimitExceeded::new_at(1 + 8 * 21, Limit::Alloc).into()
+    );
+
+    let mut tree = Tree {
+        children: Vec::new(),
+    };
+
+    for _ in 0..9 {
+        tree = Tree {
+            children: vec![tree],
+        };
+    }
+
+    let bytes = encode(&tree);
+    assert!(decode::<Tree>(&bytes, DecodeLimits::default().max_depth(10)).is_ok());
+
+    let error = decode::<Tree>(&bytes, DecodeLimits::default().max_depth(9)).unwrap_err();
+    assert_eq!(error, LimitExceeded::new_at(9, Limit::Depth).into());
+
+    let error = Tree::recognize(&mut Bytes::with_limits(
+        &bytes,
+        *DecodeLimits::default().max_depth(9),
+    ))
+    .unwrap_err();
+    assert_eq!(error.root(), &LimitExceeded::new_at(9, Limit::Depth).into());
+
+    // Slices and lazy values do not keep the limits, they are passed again
+    // when decoding them later.
+    let bytes = encode(&vec![Uint(1); 10]);
+    let limits = DecodeLimits::default().max_collection_len(5).to_owned();
+    let error = LimitExceeded::new_at(1, Limit::CollectionLen).into();
+
+    let slice = Vec::<Uint>::recognize(&mut Bytes::with_limits(&bytes, limits)).unwrap();
+    assert_eq!(slice.decode_with_limits(limits).unwrap_err().root(), &error);
+    assert_eq!(slice.decode().unwrap().len(), 10);
+
+    let lazy = decode::<Lazy<Vec<Uint>>>(&bytes, &limits).unwrap();
+    assert_eq!(
+        lazy.decode_with_limits(&bytes, limits).unwrap_err().root(),
+        &error
+    );
+
+    let bytes = encode(&vec!["x".repeat(20)]);
+    let limits = DecodeLimits::default().max_string_len(10).to_owned();
+    let slice = Vec::<String>::recognize(&mut Bytes::with_limits(&bytes, limits)).unwrap();
+    let item = slice.iter().next().unwrap().unwrap();
+    assert_eq!(
+        item.decode_with_limits(limits).unwrap_err().root(),
+        &LimitExceeded::new_at(2, Limit::StringLen).into()
+    );
+
+    // Lengths that do not fit into memory are reported as unexpected end of
+    // input instead of overflowing.
+    let bytes = [0xff; 9];
+    assert_eq!(
+        String::decode(&mut Bytes::new(&bytes)).unwrap_err().root(),
+        &UnexpectedEof::new_at(9).into()
+    );
+    assert_eq!(
+        Vec::<u32>::recognize(&mut Bytes::new(&bytes))
+            .unwrap_err()
+            .root(),
+        &UnexpectedEof::new_at(9).into()
+    );
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct WithStream {
+    name: String,
+    items: phenix_runtime::Stream<Struct>,
+}
+
+#[test]
+fn read_stream() {
+    let header = WithStream {
+        name: "header".to_string(),
+        items: Stream::default(),
+    };
+
+    let items = (0..100)
+        .map(|i| Struct {
+            string: i.to_string(),
+            optional1: (i % 2 == 0).then_some(Uint(i)),
+            generic: vec![true; i as usize],
+            optional2: None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut writer = header.encode_with_stream(Cursor::new(Vec::new())).unwrap();
+    writer.push(&items[0]).unwrap();
+    writer.push_many(&items[1..]).unwrap();
+    assert_eq!(writer.len(), items.len());
+
+    let bytes = writer.finish().unwrap().into_inner();
+    let mut source = ReadBytes::new(bytes.as_slice());
+
+    let decoded = source.decode::<WithStream>().unwrap();
+    assert_eq!(decoded.name, header.name);
+
+    let collected = decoded
+        .items
+        .iter_from(&mut source)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(collected, items);
+}
+
+mod user {
+    use super::*;
+
+    // Unrelated to `phenix_runtime::Stream`.
+    #[derive(Debug, PartialEq, Encodable, Decodable)]
+    pub struct Stream<T> {
+        pub items: Vec<T>,
+    }
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+#[non_exhaustive]
+struct WithUserStream {
+    name: String,
+    items: user::Stream<Uint>,
+}
+
+#[test]
+fn user_stream_type() {
+    let value = WithUserStream {
+        name: "user".to_string(),
+        items: user::Stream {
+            items: vec![Uint(1), Uint(2)],
+        },
+    };
+
+    // Encoded as any other field, not as a trailing stream.
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+
+    let mut input = Bytes::new(&bytes);
+    assert_eq!(WithUserStream::decode(&mut input).unwrap(), value);
+    assert!(input.is_empty());
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn read_stream_async() {
+    use futures::{executor::block_on, io::Cursor, StreamExt};
+    use phenix_runtime::{async_io::AsyncReadBytes, AsyncEncodable};
+
+    let header = WithStream {
+        name: "header".to_string(),
+        items: Stream::default(),
+    };
+
+    let items = (0..100)
+        .map(|i| Struct {
+            string: i.to_string(),
+            optional1: (i % 2 == 0).then_some(Uint(i)),
+            generic: vec![true; i as usize],
+            optional2: None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut cursor = Cursor::new(Vec::new());
+
+    block_on(async {
+        header.encode_async(&mut cursor).await.unwrap();
+
+        for item in items.iter() {
+            item.encode_async(&mut cursor).await.unwrap();
+        }
+    });
+
+    let bytes = cursor.into_inner();
+    let mut source = AsyncReadBytes::new(bytes.as_slice());
+
+    let decoded = block_on(source.decode::<WithStream>()).unwrap();
+    assert_eq!(decoded.name, header.name);
+
+    let collected = block_on(decoded.items.stream_from(&mut source).collect::<Vec<_>>())
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(collected, items);
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct WithFramedStreams {
+    numbers: phenix_runtime::FramedStream<Uint>,
+    names: phenix_runtime::FramedStream<String>,
+    trailer: String,
+}
+
+#[test]
+fn framed_stream() {
+    let value = WithFramedStreams {
+        numbers: (0..100).map(Uint).collect::<Vec<_>>().into(),
+        names: FramedStream::default(),
+        trailer: "trailer".to_string(),
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+    assert_eq!(value.encoded_len(), bytes.len());
+
+    let decoded = WithFramedStreams::decode(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(decoded.trailer, value.trailer);
+    assert_eq!(
+        decoded.numbers.span().unwrap().collect(&bytes).unwrap(),
+        value.numbers.items().unwrap()
+    );
+    assert_eq!(
+        decoded.names.span().unwrap().iter(&bytes).unwrap().count(),
+        0
+    );
+    assert_eq!(decoded.numbers.items(), None);
+
+    // The items of a decoded stream are not lost silently.
+    let error = decoded.encode(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+    let slice = WithFramedStreams::recognize(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(slice.as_bytes(), bytes.as_slice());
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct WithFramedStream {
+    name: String,
+    items: phenix_runtime::FramedStream<Uint>,
+}
+
+#[test]
+fn write_framed_stream() {
+    let header = WithFramedStream {
+        name: "header".to_string(),
+        items: FramedStream::default(),
+    };
+
+    let items = (0..10_000).map(Uint).collect::<Vec<_>>();
+
+    let mut writer = header.encode_with_stream(Cursor::new(Vec::new())).unwrap();
+    writer.push_many(&items).unwrap();
+    assert_eq!(writer.len(), items.len());
+
+    let mut cursor = writer.finish().unwrap();
+    "after".encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let mut input = Bytes::new(&bytes);
+    let decoded = WithFramedStream::decode(&mut input).unwrap();
+    assert_eq!(String::decode(&mut input).unwrap(), "after");
+    assert!(input.is_empty());
+
+    assert_eq!(decoded.name, header.name);
+    assert_eq!(
+        decoded.items.span().unwrap().collect(&bytes).unwrap(),
+        items
+    );
+}
+
+#[test]
+fn framed_stream_index() {
+    let header = WithFramedStream {
+        name: "header".to_string(),
+        items: FramedStream::default(),
+    };
+
+    let items = (0..1000).map(|i| Uint(i * 1000)).collect::<Vec<_>>();
+
+    let mut writer = header.encode_with_stream(Cursor::new(Vec::new())).unwrap();
+    writer.index_every(64).push_many(&items).unwrap();
+    let (cursor, index) = writer.finish_with_index().unwrap();
+    let bytes = cursor.into_inner();
+
+    let decoded = WithFramedStream::decode(&mut Bytes::new(&bytes)).unwrap();
+    let span = decoded.items.span().unwrap();
+    assert_eq!(index.len(), items.len());
+    assert_eq!(index.entries(), 16);
+
+    // The index written along the stream is the same as read from the frames.
+    assert_eq!(span.index(&bytes).unwrap(), index);
+
+    for n in [0, 63, 64, 500, 999] {
+        let item = span.get(&bytes, &index, n).unwrap().unwrap();
+        assert_eq!(item.decode().unwrap(), items[n]);
+    }
+
+    assert_eq!(span.get(&bytes, &index, 1000).unwrap(), None);
+    assert_eq!(span.get(&bytes, &index, usize::MAX).unwrap(), None);
+
+    let collect = |range| {
+        span.range(&bytes, &index, range)
+            .unwrap()
+            .map(|item| item?.decode())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    };
+
+    assert_eq!(collect(60..200), items[60..200]);
+    assert_eq!(collect(990..2000), items[990..]);
+    assert!(collect(1000..1010).is_empty());
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct WithUintStream {
+    name: String,
+    items: phenix_runtime::Stream<Uint>,
+}
+
+#[test]
+fn stream_index() {
+    let header = WithUintStream {
+        name: "header".to_string(),
+        items: Stream::default(),
+    };
+
+    let items = (0..1000).map(|i| Uint(i * 1000)).collect::<Vec<_>>();
+
+    let mut writer = header.encode_with_stream(Cursor::new(Vec::new())).unwrap();
+    writer.index_every(100).push_many(&items).unwrap();
+    let (cursor, index) = writer.finish_with_index().unwrap();
+    let bytes = cursor.into_inner();
+
+    let decoded = WithUintStream::decode(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(index.len(), items.len());
+    assert_eq!(index.entries(), 10);
+
+    for n in [0, 99, 100, 555, 999] {
+        let item = decoded.items.get(&bytes, &index, n).unwrap().unwrap();
+        assert_eq!(item.decode().unwrap(), items[n]);
+    }
+
+    assert_eq!(decoded.items.get(&bytes, &index, 1000).unwrap(), None);
+
+    let collect = |range| {
+        decoded
+            .items
+            .range(&bytes, &index, range)
+            .unwrap()
+            .map(|item| item?.decode())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    };
+
+    assert_eq!(collect(150..420), items[150..420]);
+    assert_eq!(collect(990..2000), items[990..]);
+    assert!(collect(1000..1010).is_empty());
+
+    // The index can be stored next to the data.
+    let mut encoded = Vec::new();
+    index.encode(&mut encoded).unwrap();
+    assert_eq!(
+        StreamIndex::<Uint>::decode(&mut Bytes::new(&encoded)).unwrap(),
+        index
+    );
+
+    // Without `index_every` only the start of the stream is recorded.
+    let mut writer = header.encode_with_stream(Vec::new()).unwrap();
+    writer.push_many(&items).unwrap();
+    let (bytes, index) = writer.finish_with_index().unwrap();
+    assert_eq!(index.entries(), 1);
+    assert_eq!(
+        decoded
+            .items
+            .get(&bytes, &index, 555)
+            .unwrap()
+            .unwrap()
+            .decode()
+            .unwrap(),
+        items[555]
+    );
+
+    // Offsets of a decoded index are not trusted.
+    let corrupted = [
+        1, 1, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0,
+    ];
+    let index = StreamIndex::<Uint>::decode(&mut Bytes::new(&corrupted)).unwrap();
+    assert!(decoded.items.get(&bytes, &index, 0).is_err());
+}
+
+#[test]
+fn framed_stream_frames() {
+    let mut cursor = Cursor::new(Vec::new());
+    FramedStream::frame_encode(&["a", "b"], &mut cursor).unwrap();
+    FramedStream::<&str>::frame_encode(&[], &mut cursor).unwrap();
+    FramedStream::frame_encode(&["c"], &mut cursor).unwrap();
+    FramedStream::<&str>::end_encode(&mut cursor).unwrap();
+    "after".encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let mut input = Bytes::new(&bytes);
+    let stream = *FramedStream::<String>::decode(&mut input)
+        .unwrap()
+        .span()
+        .unwrap();
+    assert_eq!(String::decode(&mut input).unwrap(), "after");
+    assert!(input.is_empty());
+
+    assert_eq!(stream.collect(&bytes).unwrap(), ["a", "b", "c"]);
+
+    // The items are recognized lazily.
+    let mut iter = stream.iter(&bytes).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().as_bytes(), &[1, b'a']);
+
+    // The origin is not the one the stream was decoded from.
+    assert_eq!(
+        stream.collect(&bytes[..8]).unwrap_err(),
+        UnexpectedEof::new_at(8).into()
+    );
+    assert!(stream.index(&bytes[..8]).is_err());
+
+    // Truncated frame.
+    let error = FramedStream::<String>::decode(&mut Bytes::new(&bytes[..5])).unwrap_err();
+    assert_eq!(error, UnexpectedEof::new_at(2).into());
+
+    // Frame length that does not fit into the input.
+    let bytes = [1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    let error = FramedStream::<u8>::decode(&mut Bytes::new(&bytes)).unwrap_err();
+    assert_eq!(error, UnexpectedEof::new_at(10).into());
+    assert_eq!(
+        FramedStream::<u8>::validate(&mut Bytes::new(&bytes)).unwrap_err(),
+        UnexpectedEof::new_at(10).into()
+    );
+}
+
+#[derive(Debug, PartialEq, Encodable, Decodable)]
+struct WithLazy {
+    name: String,
+    body: Lazy<Vec<Uint>>,
+    note: Option<Lazy<String>>,
+}
+
+#[test]
+fn lazy() {
+    let value = WithLazy {
+        name: "lazy".to_string(),
+        body: vec![Uint(1), Uint(300)].into(),
+        note: Some("note".to_string().into()),
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+    assert_eq!(value.encoded_len(), bytes.len());
+
+    let decoded = WithLazy::decode(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(decoded.name, value.name);
+    assert_eq!(decoded.body.value(), None);
+    assert_eq!(decoded.body.get(&bytes).as_bytes(), &[2, 1, 249, 44, 1]);
+    assert_eq!(decoded.body.decode(&bytes).unwrap(), [Uint(1), Uint(300)]);
+
+    let note = decoded.note.as_ref().unwrap();
+    assert_eq!(note.decode(&bytes).unwrap(), "note");
+
+    // The encoded length is known, but the value cannot be encoded without the
+    // original buffer.
+    assert_eq!(decoded.encoded_len(), bytes.len());
+    let error = decoded.encode(&mut Cursor::new(Vec::new())).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+    // Which is copied from.
+    let mut body = Vec::new();
+    decoded.body.encode_from(&bytes, &mut body).unwrap();
+    assert_eq!(body, [2, 1, 249, 44, 1]);
+    assert!(decoded.body.encode_from(&bytes[..8], &mut body).is_err());
+
+    // Decoding does not copy the value.
+    let limits = *DecodeLimits::default().max_alloc(0);
+    let lazy: Lazy<Vec<Uint>> = Decodable::decode(&mut Bytes::with_limits(&body, limits)).unwrap();
+    assert_eq!(lazy.span().len(), body.len());
+
+    let slice = WithLazy::recognize(&mut Bytes::new(&bytes)).unwrap();
+    assert_eq!(slice.as_bytes(), bytes.as_slice());
+
+    // Lazy values are still validated.
+    let error = WithLazy::validate(&mut Bytes::new(&bytes[..8])).unwrap_err();
+    assert!(matches!(error, DecodingError::UnexpectedEof(_)));
+}
+
+#[test]
+fn nested_offsets() {
+    let value = vec![
+        WithLazy {
+            name: "first".to_string(),
+            body: vec![Uint(1)].into(),
+            note: None,
+        },
+        WithLazy {
+            name: "second".to_string(),
+            body: vec![Uint(2), Uint(300)].into(),
+            note: Some("note".to_string().into()),
+        },
+    ];
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let slice = Vec::<WithLazy>::recognize(&mut Bytes::new(&bytes)).unwrap();
+    let item = slice.items().unwrap().get(1).unwrap().unwrap();
+    assert_eq!(item.span().offset(), 10);
+
+    // Spans recognized in the nested value are resolved against the origin.
+    let decoded = item.decode().unwrap();
+    assert_eq!(decoded.body.span().offset(), 18);
+    assert_eq!(decoded.body.decode(&bytes).unwrap(), [Uint(2), Uint(300)]);
+    assert_eq!(decoded.note.unwrap().decode(&bytes).unwrap(), "note");
+
+    // So are the positions of errors.
+    let error = item.cast::<[u64; 3]>().decode().unwrap_err();
+    assert_eq!(error.pos(), 26);
+}
+
+#[test]
+fn vector_iter() {
+    let value = vec!["a".to_string(), "bc".to_string()];
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let slice = Vec::<String>::recognize(&mut Bytes::new(&bytes)).unwrap();
+    let items = slice
+        .iter()
+        .map(|item| item?.decode())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(items, value);
+
+    let mut iter = slice.iter();
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.nth(1).unwrap().unwrap().as_bytes(), &[2, b'b', b'c']);
+    assert!(iter.next().is_none());
+
+    let items = slice.items().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items.get(0).unwrap().unwrap().decode().unwrap(), "a");
+    assert!(items.get(2).is_none());
+}
+
+#[test]
+fn vector_iter_fixed_size() {
+    let value = (0..300u32).collect::<Vec<_>>();
+
+    let mut cursor = Cursor::new(Vec::new());
+    value.encode(&mut cursor).unwrap();
+    let bytes = cursor.into_inner();
+
+    let slice = Vec::<u32>::recognize(&mut Bytes::new(&bytes)).unwrap();
+    let items = slice.items().unwrap();
+    assert_eq!(items.len(), 300);
+    assert_eq!(items.get(257).unwrap().unwrap().decode().unwrap(), 257);
+    assert!(items.get(300).is_none());
+
+    let mut iter = items.iter();
+    assert_eq!(iter.nth(298).unwrap().unwrap().decode().unwrap(), 298);
+    assert_eq!(iter.len(), 1);
+    assert_eq!(iter.next().unwrap().unwrap().decode().unwrap(), 299);
+    assert!(iter.next().is_none());
+
+    // The length prefix claims more items than there are.
+    let mut truncated = bytes.clone();
+    truncated.truncate(bytes.len() - 4);
+    let slice = Bytes::new(&truncated)
+        .consume_slice::<Vec<u32>>(truncated.len())
+        .unwrap();
+    let mut iter = slice.iter();
+    assert!(iter.nth(299).unwrap().is_err());
+    assert!(iter.next().is_none());
+}
+
 // ============================================================================
 
 #[derive(Debug, Deserialize)]
@@ -232,6 +1592,8 @@ struct TestItem<T> {
     no_encode: bool,
     #[serde(default)]
     no_decode: bool,
+    #[serde(default)]
+    non_canonical: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -242,6 +1604,20 @@ struct ErrorItem {
     error: Error,
     #[serde(default)]
     no_recognize: bool,
+    #[serde(default)]
+    strict: bool,
+}
+
+impl ErrorItem {
+    fn input(&self) -> Bytes<'_> {
+        let mut limits = DecodeLimits::default();
+
+        if self.strict {
+            limits.strict();
+        }
+
+        Bytes::with_limits(&self.bytes, limits)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -276,11 +1652,48 @@ where
 
             let mut cursor = Cursor::new(Vec::new());
 
-            match test.value.cast() {
-                Value::One(value) => value.encode(&mut cursor).unwrap(),
-                Value::Many(values) => T::encode_many(&values, &mut cursor).unwrap(),
+            let len = match test.value.cast() {
+                Value::One(value) => {
+                    value.encode(&mut cursor).unwrap();
+                    value.encoded_len()
+                }
+                Value::Many(values) => {
+                    T::encode_many(&values, &mut cursor).unwrap();
+                    T::encoded_len_many(&values)
+                }
+            };
+
+            let actual = cursor.into_inner();
+            assert_eq!(actual, test.bytes);
+            assert_eq!(len, test.bytes.len());
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    fn run_encode_async(suite: &'static str)
+    where
+        T: phenix_runtime::AsyncEncodable,
+    {
+        use futures::{executor::block_on, io::Cursor};
+
+        let suite = Self::parse(suite);
+
+        for test in suite.tests {
+            if test.no_encode {
+                continue;
             }
 
+            let mut cursor = Cursor::new(Vec::new());
+
+            block_on(async {
+                match test.value.cast() {
+                    Value::One(value) => value.encode_async(&mut cursor).await.unwrap(),
+                    Value::Many(values) => {
+                        T::encode_many_async(&values, &mut cursor).await.unwrap()
+                    }
+                }
+            });
+
             let actual = cursor.into_inner();
             assert_eq!(actual, test.bytes);
         }
@@ -316,14 +1729,15 @@ where
         }
 
         for error in suite.errors {
-            let bytes = &mut Bytes::new(&error.bytes);
+            let bytes = &mut error.input();
 
             let actual = match error.many {
                 None => T::decode(bytes).unwrap_err(),
                 Some(n) => T::decode_many(bytes, n, &mut Vec::new()).unwrap_err(),
             };
 
-            assert_eq!(actual, error.error.into());
+            // Ignore the context attached to errors in collections.
+            assert_eq!(actual.root(), &error.error.into());
         }
     }
 
@@ -367,7 +1781,7 @@ where
                 continue;
             }
 
-            let bytes = &mut Bytes::new(&error.bytes);
+            let bytes = &mut error.input();
 
             let actual = match error.many {
                 None => T::recognize(bytes).unwrap_err(),
@@ -377,4 +1791,37 @@ where
             assert_eq!(actual, error.error.into());
         }
     }
+
+    fn run_validate(suite: &'static str)
+    where
+        T: Decodable,
+    {
+        let suite = Self::parse(suite);
+
+        for test in suite.tests {
+            if test.no_decode || test.non_canonical {
+                continue;
+            }
+
+            match test.value.cast() {
+                Value::One(_) => phenix_runtime::validate::<T>(&test.bytes).unwrap(),
+                Value::Many(expected) => {
+                    let bytes = &mut Bytes::new(&test.bytes);
+                    T::validate_many(bytes, expected.len()).unwrap();
+                    assert!(bytes.is_empty());
+                }
+            }
+        }
+
+        for error in suite.errors {
+            let bytes = &mut error.input();
+
+            let actual = match error.many {
+                None => T::validate(bytes).unwrap_err(),
+                Some(n) => T::validate_many(bytes, n).unwrap_err(),
+            };
+
+            assert_eq!(actual.root(), &error.error.into());
+        }
+    }
 }